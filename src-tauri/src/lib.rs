@@ -1,5 +1,8 @@
 mod modules;
-use modules::{ai, config, excel, ui, utils};
+use modules::{
+    ai, bundle, catalog, config, corrections, credentials, examples, excel, glossary, inputs,
+    policy, prompts, secrets, settings, templates, ui, utils, workspace,
+};
 
 use std::env;
 
@@ -12,18 +15,68 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .manage(inputs::InputCache::default())
+        .manage(corrections::CorrectionsLock::default())
+        .setup(|app| {
+            policy::status();
+            inputs::clear_work_dir(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             ai::analyze_document,
+            inputs::expand_inputs,
+            templates::get_templates,
+            templates::save_template,
+            templates::remove_template,
+            templates::test_template,
+            examples::confirm_extraction,
+            prompts::get_prompts,
+            prompts::save_prompt,
+            prompts::reset_prompts,
+            glossary::get_glossary,
+            glossary::save_glossary,
+            glossary::import_glossary,
+            glossary::export_glossary,
+            catalog::get_catalog,
+            catalog::save_catalog,
+            catalog::seed_catalog_from_workbook,
+            catalog::map_products,
             excel::export_to_excel,
+            excel::preview_export,
             config::save_api_key,
             config::get_api_key,
-            config::learn_correction,
-            config::get_corrections,
-            config::remove_correction,
+            credentials::get_credentials,
+            credentials::save_credential,
+            credentials::set_active_credential,
+            credentials::remove_credential,
+            credentials::test_credentials,
+            credentials::rotate_credential,
+            secrets::get_secret_status,
+            corrections::learn_correction,
+            corrections::get_corrections,
+            corrections::remove_correction,
+            corrections::export_corrections,
+            corrections::import_corrections,
+            corrections::sync_corrections,
+            corrections::get_correction_history,
+            corrections::restore_correction,
+            corrections::get_correction_usage,
+            corrections::get_correction_conflicts,
+            corrections::resolve_correction_conflict,
             config::move_files,
             excel::check_excel_access,
+            excel::get_excel_sheets,
             ui::set_taskbar_progress,
-            utils::copy_files
+            utils::copy_files,
+            workspace::get_workspaces,
+            workspace::create_workspace,
+            workspace::switch_workspace,
+            workspace::remove_workspace,
+            settings::get_settings,
+            settings::update_settings,
+            bundle::export_config_bundle,
+            bundle::import_config_bundle,
+            policy::get_policy
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,19 +1,27 @@
-use crate::modules::config::get_api_key;
+use crate::modules::corrections;
+use crate::modules::credentials::{self, DEFAULT_PROVIDER};
+use crate::modules::document::DocumentKind;
+use crate::modules::examples;
+use crate::modules::glossary;
+use crate::modules::images;
+use crate::modules::policy;
+use crate::modules::prompts;
+use crate::modules::settings;
+use crate::modules::templates;
+use crate::modules::ubl;
 use crate::modules::utils::format_to_uppercase;
 
 use base64::{engine::general_purpose, Engine as _};
 use keepawake;
 use serde_json::{json, Value};
-use std::collections::HashMap;
 use std::fs;
 use std::time::Duration;
 use tauri::{command, AppHandle};
 use tauri_plugin_shell::ShellExt;
-use tauri_plugin_store::StoreExt;
 use tokio::time::sleep;
 
-const PROMPT_AUFTRAG: &str = include_str!("../../../src/prompts/PromptAuftrag.txt");
-const PROMPT_RECHNUNG: &str = include_str!("../../../src/prompts/PromptRechnung.txt");
+const CHAT_URL: &str = "https://api.mistral.ai/v1/chat/completions";
+const OCR_URL: &str = "https://api.mistral.ai/v1/ocr";
 
 pub async fn run_sidecar(app: &AppHandle, path: &str, use_layout: bool) -> Result<String, String> {
     let mut args = vec!["-enc", "UTF-8"];
@@ -54,8 +62,9 @@ pub async fn call_llm(
         "response_format": { "type": "json_object" }
     });
 
+    policy::check_endpoint(CHAT_URL)?;
     let res = client
-        .post("https://api.mistral.ai/v1/chat/completions")
+        .post(CHAT_URL)
         .header("Authorization", format!("Bearer {}", api_key))
         .json(&body)
         .send()
@@ -78,50 +87,77 @@ pub async fn call_llm(
     serde_json::from_str(content_str).map_err(|e| format!("Errore di analisi JSON: {}", e))
 }
 
+fn ocr_documents(path: &str) -> Result<Vec<Value>, String> {
+    if DocumentKind::from_path(path) == Some(DocumentKind::Image) {
+        return Ok(images::encode_pages(path)?
+            .into_iter()
+            .map(|page| {
+                json!({
+                    "type": "image_url",
+                    "image_url": format!(
+                        "data:{};base64,{}",
+                        page.mime,
+                        general_purpose::STANDARD.encode(page.bytes)
+                    )
+                })
+            })
+            .collect());
+    }
+
+    let file_bytes = fs::read(path).map_err(|e| format!("Impossibile leggere il file: {}", e))?;
+    let b64_doc = general_purpose::STANDARD.encode(file_bytes);
+
+    Ok(vec![json!({
+        "type": "document_url",
+        "document_url": format!("data:application/pdf;base64,{}", b64_doc)
+    })])
+}
+
 pub async fn perform_single_ocr(
     client: &reqwest::Client,
     api_key: &str,
     path: &str,
 ) -> Result<String, String> {
-    let file_bytes = fs::read(path).map_err(|e| format!("Impossibile leggere il file: {}", e))?;
-    let b64_doc = general_purpose::STANDARD.encode(file_bytes);
-
-    let ocr_body = json!({
-        "model": "mistral-ocr-latest",
-        "document": {
-            "type": "document_url",
-            "document_url": format!("data:application/pdf;base64,{}", b64_doc)
+    let mut texts = Vec::new();
+
+    for document in ocr_documents(path)? {
+        let ocr_body = json!({
+            "model": "mistral-ocr-latest",
+            "document": document
+        });
+
+        let ocr_res = client
+            .post(OCR_URL)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&ocr_body)
+            .send()
+            .await
+            .map_err(|e| format!("Richiesta OCR non riuscita: {}", e))?;
+
+        if !ocr_res.status().is_success() {
+            return Err(format!("Stato Mistral OCR: {}", ocr_res.status()));
         }
-    });
 
-    let ocr_res = client
-        .post("https://api.mistral.ai/v1/ocr")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&ocr_body)
-        .send()
-        .await
-        .map_err(|e| format!("Richiesta OCR non riuscita: {}", e))?;
+        let ocr_json: Value = ocr_res.json().await.map_err(|e| e.to_string())?;
 
-    if !ocr_res.status().is_success() {
-        return Err(format!("Stato Mistral OCR: {}", ocr_res.status()));
+        let pages = ocr_json
+            .get("pages")
+            .and_then(|p| p.as_array())
+            .ok_or("Nessuna pagina nel risultato OCR")?;
+
+        texts.extend(
+            pages
+                .iter()
+                .filter_map(|p| p.get("markdown").and_then(|m| m.as_str()))
+                .map(|m| m.to_string()),
+        );
     }
 
-    let ocr_json: Value = ocr_res.json().await.map_err(|e| e.to_string())?;
-
-    if let Some(pages) = ocr_json.get("pages").and_then(|p| p.as_array()) {
-        let text = pages
-            .iter()
-            .filter_map(|p| p.get("markdown").and_then(|m| m.as_str()))
-            .collect::<Vec<&str>>()
-            .join("\n\n");
-
-        if text.trim().is_empty() {
-            return Err("Il risultato OCR era vuoto".to_string());
-        }
-        Ok(text)
-    } else {
-        Err("Nessuna pagina nel risultato OCR".to_string())
+    let text = texts.join("\n\n");
+    if text.trim().is_empty() {
+        return Err("Il risultato OCR era vuoto".to_string());
     }
+    Ok(text)
 }
 
 pub async fn perform_ocr_with_retry(
@@ -129,6 +165,9 @@ pub async fn perform_ocr_with_retry(
     api_key: &str,
     path: &str,
 ) -> Result<String, String> {
+    policy::check_cloud_ocr()?;
+    policy::check_endpoint(OCR_URL)?;
+
     let max_retries = 2;
     let mut last_error = String::new();
 
@@ -155,6 +194,8 @@ pub async fn analyze_document(
     app: tauri::AppHandle,
     path: String,
     doc_type: String,
+    supplier: Option<String>,
+    customer: Option<String>,
 ) -> Result<Value, String> {
     let _guard = keepawake::Builder::default()
         .display(false)
@@ -163,20 +204,82 @@ pub async fn analyze_document(
         .create()
         .map_err(|e| format!("Impossibile attivare la gestione dell'alimentazione: {}", e))?;
 
-    let api_key = get_api_key().await?;
+    let language = settings::load(&app).target_language;
 
-    if api_key.trim().is_empty() {
-        return Err("La chiave API è vuota. Inserirla nelle impostazioni.".to_string());
+    let mut result_obj = match DocumentKind::from_path(&path) {
+        Some(DocumentKind::Ubl) => {
+            let mut parsed = ubl::parse_document(&path)?;
+            format_to_uppercase(&mut parsed);
+            parsed["meta"] = json!({ "source": "xml" });
+            parsed
+        }
+        Some(DocumentKind::Pdf) => {
+            match templates::try_templates(&app, &path, &doc_type, supplier.as_deref()).await {
+                Some(result) => result,
+                None => {
+                    extract_with_llm(&app, &path, &doc_type, supplier.as_deref(), &language).await?
+                }
+            }
+        }
+        Some(DocumentKind::Image) => {
+            extract_with_llm(&app, &path, &doc_type, supplier.as_deref(), &language).await?
+        }
+        Some(DocumentKind::Email) | Some(DocumentKind::Archive) | None => {
+            return Err(format!("Formato file non supportato: {}", path))
+        }
+    };
+
+    if language != "none" {
+        glossary::apply(&app, &mut result_obj, &language);
     }
 
+    let customer = customer.or_else(|| {
+        result_obj
+            .get("kunde")
+            .and_then(|k| k.as_str())
+            .map(|k| k.to_string())
+    });
+    corrections::blocking(&app, move |app| {
+        corrections::apply(
+            app,
+            &mut result_obj,
+            supplier.as_deref(),
+            customer.as_deref(),
+        );
+        Ok(result_obj)
+    })
+    .await
+}
+
+async fn extract_with_llm(
+    app: &AppHandle,
+    path: &str,
+    doc_type: &str,
+    supplier: Option<&str>,
+    language: &str,
+) -> Result<Value, String> {
+    let (credential, api_key) = match credentials::active_credential(app, DEFAULT_PROVIDER) {
+        Some(found) if !found.1.trim().is_empty() => found,
+        _ => return Err("La chiave API è vuota. Inserirla nelle impostazioni.".to_string()),
+    };
+    policy::check_endpoint(CHAT_URL)?;
+
     let client = reqwest::Client::new();
 
-    let mut extracted_text = run_sidecar(&app, &path, false).await.unwrap_or_default();
+    let kind = DocumentKind::from_path(path);
+    let mut extracted_text = if kind == Some(DocumentKind::Image) {
+        images::run_local_ocr(app, path).await.unwrap_or_else(|e| {
+            println!("OCR locale non disponibile: {}", e);
+            String::new()
+        })
+    } else {
+        run_sidecar(app, path, false).await.unwrap_or_default()
+    };
     let mut layout_instruction = "THE LAYOUT IS 'WHITESPACE'. Columns are separated only by spaces. There are no lines. Visualize the columns.".to_string();
     let mut used_ocr = false;
 
     if extracted_text.trim().len() < 50 {
-        match perform_ocr_with_retry(&client, &api_key, &path).await {
+        match perform_ocr_with_retry(&client, &api_key, path).await {
             Ok(text) => {
                 extracted_text = text;
                 used_ocr = true;
@@ -193,11 +296,7 @@ pub async fn analyze_document(
         }
     }
 
-    let base_prompt = if doc_type == "rechnung" {
-        PROMPT_RECHNUNG
-    } else {
-        PROMPT_AUFTRAG
-    };
+    let prompt = prompts::load_prompt(app, doc_type);
 
     let products_non_empty = |v: &Value| -> bool {
         v.get("produkte")
@@ -206,9 +305,18 @@ pub async fn analyze_document(
             .unwrap_or(false)
     };
 
-    let full_prompt = format!(
-        "{}\n\nIMPORTANT LAYOUT-INFORMATION: {}\n\nContent document:\n{}",
-        base_prompt, layout_instruction, extracted_text
+    let examples = examples::prompt_examples(app, supplier, doc_type, path);
+    let glossary_terms = glossary::prompt_terms(app, language);
+    let context = prompts::PromptContext {
+        examples: &examples,
+        language,
+        glossary: &glossary_terms,
+    };
+    let full_prompt = prompts::render(
+        &prompt.template,
+        &context,
+        &layout_instruction,
+        &extracted_text,
     );
 
     let mut result_obj = call_llm(&client, &api_key, &full_prompt).await?;
@@ -216,27 +324,33 @@ pub async fn analyze_document(
 
     if !products_non_empty(&result_obj) {
         if !used_ocr {
-            if let Ok(layout_text) = run_sidecar(&app, &path, true).await {
-                if layout_text.trim().len() > 50 {
-                    let retry_prompt = format!(
-                        "{}\n\nIMPORTANT LAYOUT-INFORMATION: THE LAYOUT IS LAYOUT. Preserve original PDF layout.\n\nContent document:\n{}",
-                        base_prompt, layout_text
-                    );
-
-                    if let Ok(parsed) = call_llm(&client, &api_key, &retry_prompt).await {
-                        if products_non_empty(&parsed) {
-                            result_obj = parsed;
+            if kind != Some(DocumentKind::Image) {
+                if let Ok(layout_text) = run_sidecar(app, path, true).await {
+                    if layout_text.trim().len() > 50 {
+                        let retry_prompt = prompts::render(
+                            &prompt.template,
+                            &context,
+                            "THE LAYOUT IS LAYOUT. Preserve original PDF layout.",
+                            &layout_text,
+                        );
+
+                        if let Ok(parsed) = call_llm(&client, &api_key, &retry_prompt).await {
+                            if products_non_empty(&parsed) {
+                                result_obj = parsed;
+                            }
                         }
                     }
                 }
             }
 
             if !products_non_empty(&result_obj) {
-                match perform_ocr_with_retry(&client, &api_key, &path).await {
+                match perform_ocr_with_retry(&client, &api_key, path).await {
                     Ok(ocr_text) => {
-                        let retry_prompt = format!(
-                            "{}\n\nIMPORTANT LAYOUT-INFORMATION: THE LAYOUT IS MARKDOWN. Tables are marked with pipes '|'. Use this structure.\n\nContent document:\n{}",
-                            base_prompt, ocr_text
+                        let retry_prompt = prompts::render(
+                            &prompt.template,
+                            &context,
+                            "THE LAYOUT IS MARKDOWN. Tables are marked with pipes '|'. Use this structure.",
+                            &ocr_text,
                         );
                         if let Ok(parsed) = call_llm(&client, &api_key, &retry_prompt).await {
                             result_obj = parsed;
@@ -252,25 +366,18 @@ pub async fn analyze_document(
         }
     }
 
-    if let Ok(store) = app.store("corrections.json") {
-        if let Some(val) = store.get("product_corrections") {
-            if let Ok(corrections) = serde_json::from_value::<HashMap<String, String>>(val) {
-                if let Some(products) = result_obj
-                    .get_mut("produkte")
-                    .and_then(|p| p.as_array_mut())
-                {
-                    for prod in products {
-                        if let Some(name_val) = prod.get_mut("produkt") {
-                            if let Some(name) = name_val.as_str() {
-                                if let Some(correction) = corrections.get(name) {
-                                    *name_val = json!(correction);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    if let Some(obj) = result_obj.as_object_mut() {
+        obj.insert(
+            "meta".to_string(),
+            json!({
+                "source": "llm",
+                "docType": doc_type,
+                "promptVersion": prompt.version,
+                "promptCustom": prompt.custom,
+                "targetLanguage": language,
+                "credential": credential.name,
+            }),
+        );
     }
 
     Ok(result_obj)
@@ -0,0 +1,162 @@
+use crate::modules::document::{guess_doc_type, DocumentHints, DocumentKind, InputDocument};
+use crate::modules::email;
+use crate::modules::utils::{sanitize_file_name, unique_path};
+
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Levels of archives and emails inside each other, counted across both.
+pub const MAX_NESTING: usize = 5;
+/// Largest file taken out of an archive; the size in the ZIP header is not trusted.
+const MAX_ENTRY_BYTES: u64 = 200 * 1024 * 1024;
+
+fn extract_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+    source_path: &str,
+    target_dir: &Path,
+    depth: usize,
+    out: &mut Vec<InputDocument>,
+) -> Result<(), String> {
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Voce ZIP illeggibile: {}", e))?;
+
+        if entry.is_dir() {
+            continue;
+        }
+
+        let entry_path = match entry.enclosed_name() {
+            Some(p) => p,
+            None => continue,
+        };
+        let entry_name = format!(
+            "{}{}",
+            prefix,
+            entry_path.to_string_lossy().replace('\\', "/")
+        );
+        let file_name = match entry_path.file_name() {
+            Some(n) => sanitize_file_name(&n.to_string_lossy()),
+            None => continue,
+        };
+
+        if entry_name.contains("__MACOSX/") || file_name.starts_with('.') {
+            continue;
+        }
+
+        let kind = match DocumentKind::from_path(&file_name) {
+            Some(k) => k,
+            None => continue,
+        };
+
+        let mut bytes = Vec::new();
+        if entry.size() <= MAX_ENTRY_BYTES {
+            entry
+                .by_ref()
+                .take(MAX_ENTRY_BYTES + 1)
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("Impossibile estrarre {}: {}", entry_name, e))?;
+        }
+        if entry.size() > MAX_ENTRY_BYTES || bytes.len() as u64 > MAX_ENTRY_BYTES {
+            println!("File troppo grande nell'archivio, ignorato: {}", entry_name);
+            continue;
+        }
+
+        if kind == DocumentKind::Archive {
+            if depth >= MAX_NESTING {
+                println!(
+                    "Archivio annidato troppo profondo, ignorato: {}",
+                    entry_name
+                );
+                continue;
+            }
+            let mut nested = ZipArchive::new(Cursor::new(bytes))
+                .map_err(|e| format!("Archivio annidato non valido {}: {}", entry_name, e))?;
+            let nested_prefix = format!("{}/", entry_name);
+            extract_entries(
+                &mut nested,
+                &nested_prefix,
+                source_path,
+                target_dir,
+                depth + 1,
+                out,
+            )?;
+            continue;
+        }
+
+        let target = unique_path(target_dir, &file_name);
+        fs::write(&target, &bytes)
+            .map_err(|e| format!("Impossibile salvare {}: {}", entry_name, e))?;
+        let target_str = target.to_string_lossy().to_string();
+
+        if kind == DocumentKind::Email {
+            let attachments = match email::extract(&target_str, target_dir, depth + 1) {
+                Ok(docs) => docs,
+                Err(e) => {
+                    println!("Email nell'archivio ignorata: {}", e);
+                    continue;
+                }
+            };
+            for mut doc in attachments {
+                doc.entry_name = Some(format!(
+                    "{}/{}",
+                    entry_name,
+                    doc.entry_name.unwrap_or_default()
+                ));
+                doc.source_path = Some(source_path.to_string());
+                out.push(doc);
+            }
+            continue;
+        }
+
+        out.push(InputDocument {
+            path: target_str,
+            source_path: Some(source_path.to_string()),
+            hints: DocumentHints {
+                doc_type: guess_doc_type(&entry_name),
+                ..Default::default()
+            },
+            entry_name: Some(entry_name),
+        });
+    }
+
+    Ok(())
+}
+
+/// Extracts every supported document of a ZIP archive, including nested archives and
+/// emails, into its own folder below `work_dir`. `depth` counts the containers around it.
+pub fn extract(path: &str, work_dir: &Path, depth: usize) -> Result<Vec<InputDocument>, String> {
+    if depth > MAX_NESTING {
+        return Err(format!("Archivio annidato troppo profondo: {}", path));
+    }
+    let file = fs::File::open(path).map_err(|e| format!("Impossibile aprire l'archivio: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Archivio ZIP non valido {}: {}", path, e))?;
+
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| sanitize_file_name(&s.to_string_lossy()))
+        .unwrap_or_else(|| "archivio".to_string());
+    let target_dir = unique_path(
+        work_dir,
+        &format!("{}_{}", stem, chrono::Local::now().format("%Y%m%d_%H%M%S")),
+    );
+    fs::create_dir_all(&target_dir)
+        .map_err(|e| format!("Impossibile creare la cartella: {}", e))?;
+
+    let mut documents = Vec::new();
+    extract_entries(&mut archive, "", path, &target_dir, depth, &mut documents)?;
+
+    if documents.is_empty() {
+        let _ = fs::remove_dir_all(&target_dir);
+        return Err(format!(
+            "Nessun documento supportato trovato nell'archivio {}",
+            path
+        ));
+    }
+
+    Ok(documents)
+}
@@ -0,0 +1,370 @@
+use crate::modules::corrections::{self, CorrectionEntry, MergePolicy};
+use crate::modules::glossary::{self, GlossaryTerm};
+use crate::modules::policy;
+use crate::modules::prompts::{self, PromptTemplate};
+use crate::modules::settings::{self, Settings};
+use crate::modules::templates::{self, ExtractionTemplate};
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use tauri::{command, AppHandle};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const BUNDLE_FORMAT: &str = "raccolta-dati-config";
+/// Bumped when a file of the bundle changes in a way older versions cannot read.
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub format: String,
+    pub version: u32,
+    pub app_version: String,
+    pub settings_schema: u32,
+    pub created_at: String,
+}
+
+/// The content of a bundle. Credentials and API keys are never part of it.
+struct Bundle {
+    manifest: BundleManifest,
+    settings: Map<String, Value>,
+    corrections: Vec<CorrectionEntry>,
+    prompts: HashMap<String, PromptTemplate>,
+    glossary: Vec<GlossaryTerm>,
+    templates: Vec<ExtractionTemplate>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleChange {
+    pub section: String,
+    pub action: String,
+    pub key: String,
+    pub current: Option<String>,
+    pub incoming: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleImportReport {
+    pub manifest: BundleManifest,
+    pub changes: Vec<BundleChange>,
+    pub applied: bool,
+}
+
+fn write_entry<T: serde::Serialize>(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    value: &T,
+) -> Result<(), String> {
+    let content = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    zip.start_file(name, SimpleFileOptions::default())
+        .and_then(|_| zip.write_all(&content).map_err(Into::into))
+        .map_err(|e| format!("Errore di scrittura: {}", e))
+}
+
+fn read_entry<T: serde::de::DeserializeOwned>(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+) -> Result<T, String> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|_| format!("Il pacchetto non contiene {}.", name))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .map_err(|e| format!("Errore di lettura di {}: {}", name, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("{} non valido: {}", name, e))
+}
+
+fn read_bundle(path: &str) -> Result<Bundle, String> {
+    let file = File::open(path).map_err(|e| format!("Impossibile aprire il file: {}", e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Pacchetto di configurazione non valido: {}", e))?;
+
+    let manifest: BundleManifest = read_entry(&mut archive, "manifest.json")?;
+    if manifest.format != BUNDLE_FORMAT {
+        return Err("Il file non è un pacchetto di configurazione.".to_string());
+    }
+    if manifest.version == 0 || manifest.version > BUNDLE_VERSION {
+        return Err(format!(
+            "Il pacchetto ha la versione {} e richiede una versione più recente dell'app (creato con {}).",
+            manifest.version, manifest.app_version
+        ));
+    }
+    if manifest.settings_schema > settings::SCHEMA_VERSION {
+        return Err(format!(
+            "Le impostazioni del pacchetto usano lo schema {}, questa versione dell'app legge fino allo schema {}.",
+            manifest.settings_schema,
+            settings::SCHEMA_VERSION
+        ));
+    }
+
+    Ok(Bundle {
+        settings: read_entry(&mut archive, "settings.json")?,
+        corrections: read_entry(&mut archive, "corrections.json")?,
+        prompts: read_entry(&mut archive, "prompts.json")?,
+        glossary: read_entry(&mut archive, "glossary.json")?,
+        templates: read_entry(&mut archive, "templates.json")?,
+        manifest,
+    })
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "—".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn change(section: &str, key: String, current: Option<String>, incoming: String) -> BundleChange {
+    BundleChange {
+        section: section.to_string(),
+        action: if current.is_some() { "change" } else { "add" }.to_string(),
+        key,
+        current,
+        incoming,
+    }
+}
+
+fn settings_changes(current: &Settings, incoming: &Settings) -> Result<Vec<BundleChange>, String> {
+    let current = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    let incoming = serde_json::to_value(incoming).map_err(|e| e.to_string())?;
+    let mut changes = Vec::new();
+    for (key, value) in incoming.as_object().into_iter().flatten() {
+        let old = current.get(key).cloned().unwrap_or(Value::Null);
+        if key != "schemaVersion" && &old != value {
+            changes.push(change(
+                "settings",
+                key.clone(),
+                Some(display(&old)),
+                display(value),
+            ));
+        }
+    }
+    Ok(changes)
+}
+
+/// The prompt text a bundle entry stands for. Entries that follow the bundled prompt take
+/// the text of this version.
+fn prompt_text(doc_type: &str, prompt: &PromptTemplate) -> String {
+    if prompt.custom {
+        prompt.template.clone()
+    } else {
+        prompts::default_template(doc_type)
+    }
+}
+
+fn prompt_label(doc_type: &str, text: &str) -> String {
+    if text == prompts::default_template(doc_type) {
+        "predefinito".to_string()
+    } else {
+        format!("personalizzato ({} caratteri)", text.chars().count())
+    }
+}
+
+fn glossary_key(term: &GlossaryTerm) -> (String, String) {
+    (
+        glossary::normalize(&term.source),
+        term.language.trim().to_lowercase(),
+    )
+}
+
+/// Works out every change the bundle makes, validating its content on the way so that
+/// applying it does not stop halfway.
+fn plan(app: &AppHandle, bundle: &Bundle) -> Result<(Settings, Vec<BundleChange>), String> {
+    let mut changes = Vec::new();
+
+    let mut values = bundle.settings.clone();
+    values.remove("apiKey");
+    let incoming_settings = settings::from_values(app, values);
+    incoming_settings
+        .validate()
+        .map_err(|e| format!("Impostazioni del pacchetto non valide: {}", e))?;
+    changes.extend(settings_changes(&settings::load(app), &incoming_settings)?);
+
+    let report = corrections::merge_into(
+        app,
+        bundle.corrections.clone(),
+        MergePolicy::Overwrite,
+        true,
+    )?;
+    for c in report.changes {
+        let scope = [c.supplier, c.customer]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" / ");
+        let key = if scope.is_empty() {
+            c.wrong
+        } else {
+            format!("{} ({})", c.wrong, scope)
+        };
+        changes.push(change("corrections", key, c.current, c.incoming));
+    }
+
+    for doc_type in prompts::DOC_TYPES {
+        let Some(prompt) = bundle.prompts.get(doc_type) else {
+            continue;
+        };
+        let incoming = prompt_text(doc_type, prompt);
+        prompts::check_template(doc_type, &incoming)?;
+        let current = prompts::load_prompt(app, doc_type).template;
+        if current != incoming {
+            changes.push(change(
+                "prompts",
+                doc_type.to_string(),
+                Some(prompt_label(doc_type, &current)),
+                prompt_label(doc_type, &incoming),
+            ));
+        }
+    }
+
+    let terms = glossary::load_terms(app)?;
+    for term in &bundle.glossary {
+        let (source, language) = glossary_key(term);
+        let target = glossary::normalize(&term.target);
+        if source.is_empty() || target.is_empty() {
+            continue;
+        }
+        let current = terms
+            .iter()
+            .find(|t| glossary_key(t) == (source.clone(), language.clone()))
+            .map(|t| glossary::normalize(&t.target));
+        if current.as_deref() != Some(target.as_str()) {
+            changes.push(change(
+                "glossary",
+                format!("{} ({})", source, language),
+                current,
+                target,
+            ));
+        }
+    }
+
+    let existing = templates::load_templates(app)?;
+    for template in &bundle.templates {
+        templates::validate(template)
+            .map_err(|e| format!("Modello {} non valido: {}", template.name, e))?;
+        let incoming = serde_json::to_value(template).map_err(|e| e.to_string())?;
+        let current = existing.iter().find(|t| t.name == template.name);
+        let differs = match current {
+            Some(t) => serde_json::to_value(t).map_err(|e| e.to_string())? != incoming,
+            None => true,
+        };
+        if differs {
+            changes.push(change(
+                "templates",
+                template.name.clone(),
+                current.map(|t| t.supplier.clone()),
+                template.supplier.clone(),
+            ));
+        }
+    }
+
+    Ok((incoming_settings, changes))
+}
+
+fn apply(app: &AppHandle, bundle: Bundle, incoming_settings: Settings) -> Result<(), String> {
+    settings::save(app, incoming_settings)?;
+
+    corrections::merge_into(app, bundle.corrections, MergePolicy::Overwrite, false)?;
+
+    for doc_type in prompts::DOC_TYPES {
+        if let Some(prompt) = bundle.prompts.get(doc_type) {
+            prompts::store_prompt(app, doc_type, prompt_text(doc_type, prompt))?;
+        }
+    }
+
+    let mut terms = glossary::load_terms(app)?;
+    for term in bundle.glossary {
+        let key = glossary_key(&term);
+        let target = glossary::normalize(&term.target);
+        if key.0.is_empty() || target.is_empty() {
+            continue;
+        }
+        terms.retain(|t| glossary_key(t) != key);
+        terms.push(GlossaryTerm {
+            source: key.0,
+            target,
+            language: key.1,
+        });
+    }
+    glossary::save_terms(app, &terms)?;
+
+    let mut existing = templates::load_templates(app)?;
+    for template in bundle.templates {
+        match existing.iter_mut().find(|t| t.name == template.name) {
+            Some(t) => *t = template,
+            None => existing.push(template),
+        }
+    }
+    templates::save_templates(app, &existing)
+}
+
+/// Writes settings, corrections, prompts, glossary and extraction templates of the
+/// active workspace into one archive. Credentials and API keys are left out.
+#[command]
+pub async fn export_config_bundle(app: AppHandle, path: String) -> Result<BundleManifest, String> {
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        app_version: app.package_info().version.to_string(),
+        settings_schema: settings::SCHEMA_VERSION,
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let corrections = corrections::blocking(&app, corrections::load_corrections).await?;
+    let prompts: HashMap<String, PromptTemplate> = prompts::DOC_TYPES
+        .iter()
+        .map(|t| (t.to_string(), prompts::load_prompt(&app, t)))
+        .collect();
+
+    policy::check_export_path(Path::new(&path))?;
+    let file = File::create(&path).map_err(|e| format!("Impossibile creare il file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    write_entry(&mut zip, "manifest.json", &manifest)?;
+    write_entry(&mut zip, "settings.json", &settings::load(&app))?;
+    write_entry(&mut zip, "corrections.json", &corrections)?;
+    write_entry(&mut zip, "prompts.json", &prompts)?;
+    write_entry(&mut zip, "glossary.json", &glossary::load_terms(&app)?)?;
+    write_entry(
+        &mut zip,
+        "templates.json",
+        &templates::load_templates(&app)?,
+    )?;
+    zip.finish()
+        .map_err(|e| format!("Errore di scrittura: {}", e))?;
+    Ok(manifest)
+}
+
+/// Imports a configuration bundle into the active workspace. Settings and prompts are
+/// replaced, corrections, glossary terms and templates from the bundle are added or
+/// overwrite the ones with the same key. With `dry_run` nothing is saved and the report
+/// only lists the differences.
+#[command]
+pub async fn import_config_bundle(
+    app: AppHandle,
+    path: String,
+    dry_run: bool,
+) -> Result<BundleImportReport, String> {
+    let bundle = read_bundle(&path)?;
+    let manifest = bundle.manifest.clone();
+
+    // Merging the corrections may wait for the lock on the shared folder.
+    let changes = corrections::blocking(&app, move |app| {
+        let (incoming_settings, changes) = plan(app, &bundle)?;
+        if !dry_run && !changes.is_empty() {
+            apply(app, bundle, incoming_settings)?;
+        }
+        Ok(changes)
+    })
+    .await?;
+    Ok(BundleImportReport {
+        manifest,
+        applied: !dry_run && !changes.is_empty(),
+        changes,
+    })
+}
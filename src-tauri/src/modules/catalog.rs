@@ -0,0 +1,332 @@
+use crate::modules::excel_columns::ColumnLayout;
+use crate::modules::settings;
+use crate::modules::utils::{same_quantities, token_similarity};
+use crate::modules::workspace;
+
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{command, AppHandle};
+
+/// From this score on a name is replaced by the catalog entry without review.
+const AUTO_MATCH_SCORE: f64 = 0.85;
+/// Below this score a name is not considered a spelling of the entry at all.
+const MIN_MATCH_SCORE: f64 = 0.5;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogEntry {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub article_codes: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProductMapping {
+    pub input: String,
+    pub matched: Option<String>,
+    pub unit: Option<String>,
+    pub score: f64,
+    pub needs_review: bool,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogSeedResult {
+    pub added: usize,
+    pub aliases_added: usize,
+    pub total: usize,
+}
+
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+fn load_catalog(app: &AppHandle) -> Result<Vec<CatalogEntry>, String> {
+    let store = workspace::store(app, "catalog.json")?;
+
+    match store.get("products") {
+        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn save_catalog_entries(app: &AppHandle, entries: &[CatalogEntry]) -> Result<(), String> {
+    let store = workspace::store(app, "catalog.json")?;
+    store.set("products", json!(entries));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+/// Returns the index of the best entry and its score. Article codes that appear as a
+/// word of the name count as an exact match; a name or alias with other quantities
+/// than the name never matches.
+fn best_entry(entries: &[CatalogEntry], name: &str) -> Option<(usize, f64)> {
+    let upper = normalize(name);
+    let words: Vec<&str> = upper.split_whitespace().collect();
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let code_hit = entry
+                .article_codes
+                .iter()
+                .any(|c| words.contains(&normalize(c).as_str()));
+            let score = if code_hit {
+                1.0
+            } else {
+                std::iter::once(&entry.name)
+                    .chain(entry.aliases.iter())
+                    .filter(|candidate| same_quantities(&upper, candidate))
+                    .map(|candidate| {
+                        let fwd = token_similarity(&upper, candidate);
+                        let back = token_similarity(candidate, &upper);
+                        fwd.min(back)
+                    })
+                    .fold(0.0, f64::max)
+            };
+            (i, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[command]
+pub async fn get_catalog(app: AppHandle) -> Result<Vec<CatalogEntry>, String> {
+    load_catalog(&app)
+}
+
+#[command]
+pub async fn save_catalog(app: AppHandle, entries: Vec<CatalogEntry>) -> Result<(), String> {
+    let mut cleaned: Vec<CatalogEntry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = normalize(&entry.name);
+        if name.is_empty() {
+            continue;
+        }
+        if cleaned.iter().any(|e| e.name == name) {
+            return Err(format!("Prodotto duplicato nel catalogo: {}", name));
+        }
+        let mut aliases: Vec<String> = entry
+            .aliases
+            .iter()
+            .map(|a| normalize(a))
+            .filter(|a| !a.is_empty() && *a != name)
+            .collect();
+        aliases.dedup();
+
+        cleaned.push(CatalogEntry {
+            name,
+            aliases,
+            unit: entry.unit.filter(|u| !u.trim().is_empty()),
+            article_codes: entry
+                .article_codes
+                .iter()
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect(),
+        });
+    }
+    save_catalog_entries(&app, &cleaned)
+}
+
+/// Builds catalog entries from the product column of the workbook's data sheets.
+/// Spellings that are close to an existing entry become aliases, the most frequent
+/// spelling of a new group becomes its name.
+#[command]
+pub async fn seed_catalog_from_workbook(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<CatalogSeedResult, String> {
+    let settings = settings::load(&app);
+    let path = match path.or(settings.default_excel_path) {
+        Some(p) if !p.trim().is_empty() => PathBuf::from(p),
+        _ => return Err("Nessun file Excel selezionato.".to_string()),
+    };
+
+    let book = umya_spreadsheet::reader::xlsx::read(&path)
+        .map_err(|e| format!("Errore di lettura: {}", e))?;
+
+    // Every sheet with a recognised header holds data, e.g. one sheet per year next to
+    // an overview.
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut first_error = None;
+    let mut data_sheets = 0;
+    for sheet in book.get_sheet_collection() {
+        let layout = match ColumnLayout::resolve(sheet, &settings.excel_columns) {
+            Ok(l) => l,
+            Err(e) => {
+                first_error.get_or_insert(format!("Foglio \"{}\": {}", sheet.get_name(), e));
+                continue;
+            }
+        };
+        data_sheets += 1;
+        for r in (layout.header_row + 1)..=sheet.get_highest_row() {
+            let name = normalize(&sheet.get_value((layout.product, r)));
+            if !name.is_empty() {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+    }
+    if data_sheets == 0 {
+        return Err(first_error.unwrap_or("Nessun foglio di lavoro trovato.".to_string()));
+    }
+
+    let mut names: Vec<(String, usize)> = counts.into_iter().collect();
+    names.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut entries = load_catalog(&app)?;
+    let result = seed_entries(&mut entries, names);
+    save_catalog_entries(&app, &entries)?;
+    Ok(result)
+}
+
+/// Adds the workbook names, most frequent first, as aliases or new entries.
+fn seed_entries(entries: &mut Vec<CatalogEntry>, names: Vec<(String, usize)>) -> CatalogSeedResult {
+    let mut result = CatalogSeedResult {
+        added: 0,
+        aliases_added: 0,
+        total: 0,
+    };
+
+    for (name, _) in names {
+        let known = entries
+            .iter()
+            .any(|e| e.name == name || e.aliases.contains(&name));
+        if known {
+            continue;
+        }
+
+        match best_entry(entries, &name) {
+            Some((idx, score)) if score >= AUTO_MATCH_SCORE => {
+                entries[idx].aliases.push(name);
+                result.aliases_added += 1;
+            }
+            _ => {
+                entries.push(CatalogEntry {
+                    name,
+                    aliases: Vec::new(),
+                    unit: None,
+                    article_codes: Vec::new(),
+                });
+                result.added += 1;
+            }
+        }
+    }
+
+    result.total = entries.len();
+    result
+}
+
+#[command]
+pub async fn map_products(
+    app: AppHandle,
+    names: Vec<String>,
+) -> Result<Vec<ProductMapping>, String> {
+    let entries = load_catalog(&app)?;
+
+    Ok(names
+        .into_iter()
+        .map(|input| {
+            if entries.is_empty() || input.trim().is_empty() {
+                return ProductMapping {
+                    input,
+                    matched: None,
+                    unit: None,
+                    score: 0.0,
+                    needs_review: false,
+                };
+            }
+
+            let (matched, unit, score) = match best_entry(&entries, &input) {
+                Some((idx, score)) if score >= MIN_MATCH_SCORE => (
+                    Some(entries[idx].name.clone()),
+                    entries[idx].unit.clone(),
+                    score,
+                ),
+                Some((_, score)) => (None, None, score),
+                None => (None, None, 0.0),
+            };
+
+            ProductMapping {
+                needs_review: score < AUTO_MATCH_SCORE,
+                input,
+                matched,
+                unit,
+                score: (score * 100.0).round() / 100.0,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, aliases: &[&str]) -> CatalogEntry {
+        CatalogEntry {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            unit: None,
+            article_codes: Vec::new(),
+        }
+    }
+
+    fn score(entries: &[CatalogEntry], name: &str) -> f64 {
+        best_entry(entries, name).map_or(0.0, |(_, s)| s)
+    }
+
+    #[test]
+    fn pack_sizes_never_match() {
+        let entries = vec![entry("ORTICA FOGLIE 1000G", &[])];
+
+        assert_eq!(score(&entries, "Ortica  foglie 1000G"), 1.0);
+        assert!(score(&entries, "ORTICA FOGLIE 1000G TAGLIATE") >= MIN_MATCH_SCORE);
+        assert!(score(&entries, "ORTICA FOGLIE 2000G") < MIN_MATCH_SCORE);
+        assert!(score(&entries, "ORTICA FOGLIE 1000 G") < MIN_MATCH_SCORE);
+        assert!(score(&entries, "ORTICA FOGLIE 1000KG") < MIN_MATCH_SCORE);
+    }
+
+    #[test]
+    fn aliases_need_the_same_quantities() {
+        let entries = vec![
+            entry("ORTICA FOGLIE 1000G", &["FOGLIE DI ORTICA TAGLIATE 1000G"]),
+            entry("MENTA PIPERITA", &[]),
+        ];
+
+        assert!(score(&entries, "FOGLIE DI ORTICA TAGLIATA 1000G") >= AUTO_MATCH_SCORE);
+        assert!(score(&entries, "FOGLIE DI ORTICA TAGLIATE 500G") < MIN_MATCH_SCORE);
+    }
+
+    #[test]
+    fn seeding_keeps_pack_sizes_apart() {
+        let mut entries = Vec::new();
+        let names = [
+            ("ORTICA FOGLIE 1000G", 5),
+            ("ORTICA FOGLIE 2000G", 3),
+            ("ORTICA FOGLE 1000G", 1),
+        ]
+        .map(|(n, c)| (n.to_string(), c))
+        .to_vec();
+
+        let result = seed_entries(&mut entries, names);
+
+        assert_eq!(
+            (result.added, result.aliases_added, result.total),
+            (2, 1, 2)
+        );
+        assert_eq!(entries[0].name, "ORTICA FOGLIE 1000G");
+        assert_eq!(entries[0].aliases, vec!["ORTICA FOGLE 1000G"]);
+        assert_eq!(entries[1].name, "ORTICA FOGLIE 2000G");
+        assert!(entries[1].aliases.is_empty());
+    }
+}
@@ -1,132 +1,39 @@
-use keyring::Entry;
-use serde_json::json;
-use std::collections::HashMap;
+use crate::modules::credentials::{self, DEFAULT_NAME, DEFAULT_PROVIDER};
+use crate::modules::policy;
+
 use std::fs;
 use tauri::command;
-use tauri_plugin_store::StoreExt;
-
-const KEYRING_SERVICE: &str = "com.silas.raccolta-dati";
-const KEYRING_USER: &str = "mistral_api_key";
 
-#[command]
-pub async fn get_corrections(app: tauri::AppHandle) -> Result<HashMap<String, String>, String> {
-    let store = app
-        .store("corrections.json")
-        .map_err(|e| format!("Store errore: {}", e))?;
-
-    match store.get("product_corrections") {
-        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
-        None => Ok(HashMap::new()),
-    }
-}
-
-#[command]
-pub async fn remove_correction(app: tauri::AppHandle, wrong: String) -> Result<(), String> {
-    let store = app
-        .store("corrections.json")
-        .map_err(|e| format!("Store errore: {}", e))?;
-
-    let mut corrections: HashMap<String, String> = match store.get("product_corrections") {
-        Some(val) => serde_json::from_value(val).unwrap_or_default(),
-        None => HashMap::new(),
-    };
-
-    if corrections.remove(&wrong).is_some() {
-        store.set("product_corrections", json!(corrections));
-        store
-            .save()
-            .map_err(|e| format!("Errore di memoria: {}", e))?;
-    }
-
-    Ok(())
-}
+/// Secret name of the single API key of older versions.
+pub const KEYRING_USER: &str = "mistral_api_key";
 
+/// Sets the key of the active Mistral credential, also when its old key is missing,
+/// creating a "default" one if there is none. An empty key removes the active credential.
 #[command]
 pub async fn save_api_key(app: tauri::AppHandle, key: String) -> Result<(), String> {
-    let key_trimmed = key.trim();
-    let keyring_result = Entry::new(KEYRING_SERVICE, KEYRING_USER);
-
-    match keyring_result {
-        Ok(entry) => {
-            if key_trimmed.is_empty() {
-                let _ = entry.delete_credential();
-            } else {
-                if let Err(e) = entry.set_password(key_trimmed) {
-                    println!(
-                        "⚠️ Errore di scrittura del portachiavi: {}. Utilizza il fallback...",
-                        e
-                    );
-                    return save_to_json_fallback(&app, key_trimmed);
-                }
+    match credentials::active(&app, DEFAULT_PROVIDER) {
+        Some(active) if key.trim().is_empty() => {
+            credentials::remove_credential(app, active.provider, active.name).await
+        }
+        Some(active) => {
+            let current = credentials::active_credential(&app, DEFAULT_PROVIDER).map(|(_, k)| k);
+            if current.as_deref() == Some(key.trim()) {
+                return Ok(());
             }
+            credentials::store_credential(&app, DEFAULT_PROVIDER, &active.name, &key).map(|_| ())
         }
-        Err(e) => {
-            println!(
-                "⚠️ Portachiavi non disponibile: {}. Utilizza fallback...",
-                e
-            );
-            return save_to_json_fallback(&app, key_trimmed);
+        None if key.trim().is_empty() => Ok(()),
+        None => {
+            credentials::store_credential(&app, DEFAULT_PROVIDER, DEFAULT_NAME, &key).map(|_| ())
         }
     }
-
-    let _ = save_to_json_fallback(&app, "");
-
-    Ok(())
-}
-
-#[command]
-pub async fn get_api_key() -> Result<String, String> {
-    let entry = match Entry::new(KEYRING_SERVICE, KEYRING_USER) {
-        Ok(e) => e,
-        Err(_) => return Ok("".to_string()),
-    };
-
-    match entry.get_password() {
-        Ok(password) => Ok(password),
-        Err(_) => Ok("".to_string()),
-    }
-}
-
-fn save_to_json_fallback(app: &tauri::AppHandle, key: &str) -> Result<(), String> {
-    let store = app.store("settings.json").map_err(|e| e.to_string())?;
-
-    if key.is_empty() {
-        store.delete("apiKey");
-    } else {
-        store.set("apiKey", json!(key));
-    }
-
-    store.save().map_err(|e| e.to_string())?;
-    Ok(())
 }
 
 #[command]
-pub async fn learn_correction(
-    app: tauri::AppHandle,
-    wrong: String,
-    correct: String,
-) -> Result<(), String> {
-    if wrong.trim().is_empty() || correct.trim().is_empty() || wrong == correct {
-        return Ok(());
-    }
-
-    let store = app
-        .store("corrections.json")
-        .map_err(|e| format!("Errore di memoria: {}", e))?;
-
-    let mut corrections: HashMap<String, String> = match store.get("product_corrections") {
-        Some(val) => serde_json::from_value(val).unwrap_or_default(),
-        None => HashMap::new(),
-    };
-
-    corrections.insert(wrong.trim().to_string(), correct.trim().to_string());
-
-    store.set("product_corrections", json!(corrections));
-    store
-        .save()
-        .map_err(|e| format!("Errore di memoria: {}", e))?;
-
-    Ok(())
+pub async fn get_api_key(app: tauri::AppHandle) -> Result<String, String> {
+    Ok(credentials::active_credential(&app, DEFAULT_PROVIDER)
+        .map(|(_, key)| key)
+        .unwrap_or_default())
 }
 
 #[command]
@@ -136,6 +43,7 @@ pub async fn move_files(paths: Vec<String>, target_dir: String) -> Result<(), St
     }
 
     let dest_path = std::path::Path::new(&target_dir);
+    policy::check_export_path(dest_path)?;
     if !dest_path.exists() {
         fs::create_dir_all(dest_path)
             .map_err(|e| format!("Impossibile creare la cartella: {}", e))?;
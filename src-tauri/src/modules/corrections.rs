@@ -0,0 +1,1265 @@
+use crate::modules::policy;
+use crate::modules::settings;
+use crate::modules::utils::{read_csv_records, same_quantities, token_similarity};
+use crate::modules::workspace;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tauri::{command, AppHandle, Manager};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionEntry {
+    pub wrong: String,
+    pub correct: String,
+    #[serde(default)]
+    pub supplier: Option<String>,
+    #[serde(default)]
+    pub customer: Option<String>,
+    #[serde(default)]
+    pub added_by: Option<String>,
+    #[serde(default)]
+    pub added_at: Option<String>,
+}
+
+/// A change to the corrections. Changes made while the shared folder is unreachable are
+/// queued and replayed on the shared file later.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum CorrectionOp {
+    Upsert {
+        entry: CorrectionEntry,
+    },
+    Remove {
+        wrong: String,
+        supplier: Option<String>,
+        customer: Option<String>,
+    },
+}
+
+/// One change of a correction. `old_value` is `None` when the correction was added,
+/// `new_value` is `None` when it was removed.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionHistoryEntry {
+    pub id: u64,
+    pub wrong: String,
+    pub supplier: Option<String>,
+    pub customer: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub at: String,
+    pub user: String,
+    pub origin: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionUsage {
+    pub wrong: String,
+    pub supplier: Option<String>,
+    pub customer: Option<String>,
+    pub count: u64,
+    pub last_used: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionSyncStatus {
+    pub shared_path: Option<String>,
+    pub online: bool,
+    pub last_sync: Option<String>,
+    pub pending: usize,
+    pub error: Option<String>,
+}
+
+const SHARED_FILE_NAME: &str = "correzioni-condivise.json";
+const SHARED_LOCK_NAME: &str = "correzioni-condivise.lock";
+/// How long the local copy is used before the shared file is read again.
+const REFRESH_INTERVAL_SECS: i64 = 60;
+/// After failed syncs the wait doubles from the refresh interval up to this limit.
+const MAX_RETRY_SECS: i64 = 900;
+/// A lock older than this was left behind by a client that crashed while writing.
+const STALE_LOCK_SECS: u64 = 60;
+const LOCK_ATTEMPTS: u32 = 20;
+/// Older history entries are dropped beyond this count.
+const MAX_HISTORY: usize = 2000;
+
+/// Serialises the read-modify-write cycles on corrections.json within this process, so
+/// that concurrent extractions and edits do not overwrite each other's changes.
+#[derive(Default)]
+pub struct CorrectionsLock(Mutex<()>);
+
+fn lock_store(app: &AppHandle) -> MutexGuard<'_, ()> {
+    app.state::<CorrectionsLock>()
+        .inner()
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Minimum `token_similarity` for a correction to apply to a name that is not an exact match.
+const FUZZY_THRESHOLD: f64 = 0.9;
+
+fn normalize(value: &str) -> String {
+    value.trim().to_uppercase()
+}
+
+/// Uppercases and reduces punctuation and whitespace runs to single spaces, so that
+/// "CARDO MARIANO SEMI." and "Cardo  Mariano, Semi" compare equal.
+pub fn normalize_name(value: &str) -> String {
+    value
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergePolicy {
+    /// Existing mappings stay, imported ones for the same key are dropped.
+    Skip,
+    /// Imported mappings replace existing ones for the same key.
+    Overwrite,
+    /// Existing mappings stay active, differing imported ones are kept as conflicts to
+    /// resolve in the settings.
+    KeepBoth,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionChange {
+    pub action: String,
+    pub wrong: String,
+    pub supplier: Option<String>,
+    pub customer: Option<String>,
+    pub current: Option<String>,
+    pub incoming: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionImportReport {
+    pub added: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub unchanged: usize,
+    pub conflicts: usize,
+    pub changes: Vec<CorrectionChange>,
+    pub applied: bool,
+}
+
+pub struct CorrectionMatch<'a> {
+    pub entry: &'a CorrectionEntry,
+    pub score: f64,
+}
+
+fn normalize_scope(scope: Option<String>) -> Option<String> {
+    scope.map(|s| normalize(&s)).filter(|s| !s.is_empty())
+}
+
+fn same_scope(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => normalize(a) == normalize(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl CorrectionEntry {
+    fn same_key(&self, wrong: &str, supplier: &Option<String>, customer: &Option<String>) -> bool {
+        normalize(&self.wrong) == normalize(wrong)
+            && same_scope(&self.supplier, supplier)
+            && same_scope(&self.customer, customer)
+    }
+
+    /// How specific the entry is for the given document, or `None` if its scope does
+    /// not apply. Supplier scope weighs more than customer scope.
+    fn specificity(&self, supplier: Option<&str>, customer: Option<&str>) -> Option<u8> {
+        let matches = |scope: &Option<String>, value: Option<&str>| match (scope, value) {
+            (None, _) => Some(false),
+            (Some(s), Some(v)) if normalize(s) == normalize(v) => Some(true),
+            _ => None,
+        };
+        let supplier_hit = matches(&self.supplier, supplier)?;
+        let customer_hit = matches(&self.customer, customer)?;
+        Some(u8::from(supplier_hit) * 2 + u8::from(customer_hit))
+    }
+}
+
+fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "sconosciuto".to_string())
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Loads the local copy and converts the old global `product_corrections` map on the
+/// first access.
+fn load_local(app: &AppHandle) -> Result<Vec<CorrectionEntry>, String> {
+    let store = workspace::store(app, "corrections.json")?;
+
+    if let Some(val) = store.get("entries") {
+        return serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e));
+    }
+
+    let legacy: HashMap<String, String> = match store.get("product_corrections") {
+        Some(val) => serde_json::from_value(val).unwrap_or_default(),
+        None => return Ok(Vec::new()),
+    };
+    let mut entries: Vec<CorrectionEntry> = legacy
+        .into_iter()
+        .map(|(wrong, correct)| CorrectionEntry {
+            wrong,
+            correct,
+            supplier: None,
+            customer: None,
+            added_by: None,
+            added_at: None,
+        })
+        .collect();
+    entries.sort_by(|a, b| a.wrong.cmp(&b.wrong));
+
+    store.set("entries", json!(entries));
+    store.delete("product_corrections");
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))?;
+    Ok(entries)
+}
+
+fn save_local(app: &AppHandle, entries: &[CorrectionEntry]) -> Result<(), String> {
+    let store = workspace::store(app, "corrections.json")?;
+    store.set("entries", json!(entries));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+fn apply_op(entries: &mut Vec<CorrectionEntry>, op: CorrectionOp) {
+    match op {
+        CorrectionOp::Upsert { entry } => {
+            match entries
+                .iter_mut()
+                .find(|e| e.same_key(&entry.wrong, &entry.supplier, &entry.customer))
+            {
+                Some(existing) => *existing = entry,
+                None => entries.push(entry),
+            }
+        }
+        CorrectionOp::Remove {
+            wrong,
+            supplier,
+            customer,
+        } => entries.retain(|e| !e.same_key(&wrong, &supplier, &customer)),
+    }
+}
+
+fn shared_dir(app: &AppHandle) -> Option<PathBuf> {
+    settings::load(app)
+        .shared_corrections_path
+        .map(PathBuf::from)
+}
+
+fn load_pending(app: &AppHandle) -> Vec<CorrectionOp> {
+    load_list(app, "pending")
+}
+
+/// Saves the queued changes; `synced` is the shared folder that was just synced with.
+fn set_sync_state(
+    app: &AppHandle,
+    pending: &[CorrectionOp],
+    synced: Option<&Path>,
+) -> Result<(), String> {
+    let store = workspace::store(app, "corrections.json")?;
+    store.set("pending", json!(pending));
+    if let Some(dir) = synced {
+        store.set("lastSync", json!(now()));
+        store.set("syncedPath", json!(dir.display().to_string()));
+        store.set("failedSyncs", json!(0));
+    }
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+fn last_sync(app: &AppHandle) -> Option<String> {
+    workspace::store(app, "corrections.json")
+        .ok()
+        .and_then(|store| store.get("lastSync"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+}
+
+/// Whether `dir` has not been synced with yet. Versions that did not record the folder
+/// count as synced with the current one.
+fn is_new_share(last_sync: Option<&str>, synced_path: Option<&str>, dir: &Path) -> bool {
+    match (last_sync, synced_path) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(_), Some(path)) => Path::new(path) != dir,
+    }
+}
+
+fn first_sync(app: &AppHandle, dir: &Path) -> bool {
+    let synced_path = workspace::store(app, "corrections.json")
+        .ok()
+        .and_then(|store| store.get("syncedPath"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    is_new_share(last_sync(app).as_deref(), synced_path.as_deref(), dir)
+}
+
+/// Remembers a failed sync so that an unreachable share is not retried on every read.
+fn record_failed_sync(app: &AppHandle) {
+    let store = match workspace::store(app, "corrections.json") {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+    let failures = store
+        .get("failedSyncs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    store.set("failedSyncs", json!(failures + 1));
+    store.set("lastAttempt", json!(now()));
+    if let Err(e) = store.save() {
+        println!("Impossibile salvare lo stato della sincronizzazione: {}", e);
+    }
+}
+
+fn sync_due(app: &AppHandle, dir: &Path) -> bool {
+    if first_sync(app, dir) {
+        return true;
+    }
+    let store = match workspace::store(app, "corrections.json") {
+        Ok(store) => store,
+        Err(_) => return true,
+    };
+    let time = |key: &str| {
+        store
+            .get(key)
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .and_then(|s| chrono::NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").ok())
+    };
+    let failures = store
+        .get("failedSyncs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+
+    let (since, wait) = if failures > 0 {
+        let backoff = REFRESH_INTERVAL_SECS << failures.min(5);
+        (time("lastAttempt"), backoff.min(MAX_RETRY_SECS))
+    } else {
+        (time("lastSync"), REFRESH_INTERVAL_SECS)
+    };
+    match since {
+        Some(since) => chrono::Local::now().naive_local() - since > chrono::Duration::seconds(wait),
+        None => true,
+    }
+}
+
+/// Advisory lock on the shared folder, released when dropped.
+struct SharedLock(PathBuf);
+
+impl Drop for SharedLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn acquire_lock(dir: &Path) -> Result<SharedLock, String> {
+    let path = dir.join(SHARED_LOCK_NAME);
+    for _ in 0..LOCK_ATTEMPTS {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let _ = writeln!(file, "{} {}", current_user(), now());
+                return Ok(SharedLock(path));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let stale = std::fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .is_some_and(|age| age.as_secs() > STALE_LOCK_SECS);
+                if stale {
+                    let _ = std::fs::remove_file(&path);
+                } else {
+                    std::thread::sleep(Duration::from_millis(250));
+                }
+            }
+            Err(e) => return Err(format!("Cartella condivisa non raggiungibile: {}", e)),
+        }
+    }
+    Err("Il file delle correzioni condivise è bloccato da un altro utente.".to_string())
+}
+
+fn read_shared(file: &Path) -> Result<Vec<CorrectionEntry>, String> {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| format!("Impossibile leggere le correzioni condivise: {}", e))?;
+    let value: Value =
+        serde_json::from_str(&content).map_err(|e| format!("Parse errore: {}", e))?;
+    serde_json::from_value(value.get("entries").cloned().unwrap_or(json!([])))
+        .map_err(|e| format!("Parse errore: {}", e))
+}
+
+/// Writes through a temporary file so that readers never see a half-written file.
+fn write_shared(dir: &Path, entries: &[CorrectionEntry]) -> Result<(), String> {
+    let tmp = dir.join(format!("{}.{}.tmp", SHARED_FILE_NAME, std::process::id()));
+    let content =
+        serde_json::to_string_pretty(&json!({ "entries": entries })).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, content).map_err(|e| format!("Errore di scrittura: {}", e))?;
+    std::fs::rename(&tmp, dir.join(SHARED_FILE_NAME)).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("Errore di scrittura: {}", e)
+    })
+}
+
+/// Reads the shared file into the local copy. Queued changes are first merged into the
+/// current shared file under the lock. On the first sync with a folder, what this
+/// workstation learned before is added for keys the shared file does not have yet.
+/// Callers hold `lock_store`.
+fn sync_shared(app: &AppHandle, dir: &Path) -> Result<Vec<CorrectionEntry>, String> {
+    let result = try_sync_shared(app, dir);
+    if result.is_err() {
+        record_failed_sync(app);
+    }
+    result
+}
+
+fn try_sync_shared(app: &AppHandle, dir: &Path) -> Result<Vec<CorrectionEntry>, String> {
+    if !dir.is_dir() {
+        return Err(format!(
+            "Cartella condivisa non raggiungibile: {}",
+            dir.display()
+        ));
+    }
+    let file = dir.join(SHARED_FILE_NAME);
+    let pending = load_pending(app);
+    let first_sync = first_sync(app, dir);
+
+    let entries = if pending.is_empty() && !first_sync && file.exists() {
+        read_shared(&file)?
+    } else {
+        let _lock = acquire_lock(dir)?;
+        let mut entries = if file.exists() {
+            read_shared(&file)?
+        } else {
+            Vec::new()
+        };
+        if first_sync {
+            for local in load_local(app)? {
+                if !entries
+                    .iter()
+                    .any(|e| e.same_key(&local.wrong, &local.supplier, &local.customer))
+                {
+                    entries.push(local);
+                }
+            }
+        }
+        for op in pending {
+            apply_op(&mut entries, op);
+        }
+        write_shared(dir, &entries)?;
+        entries
+    };
+
+    save_local(app, &entries)?;
+    set_sync_state(app, &[], Some(dir))?;
+    Ok(entries)
+}
+
+/// Runs corrections work on a blocking thread. Syncing may wait a few seconds for the
+/// lock on the shared folder, which must not stall the async runtime.
+pub async fn blocking<T, F>(app: &AppHandle, work: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppHandle) -> Result<T, String> + Send + 'static,
+{
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || work(&app))
+        .await
+        .map_err(|e| format!("Errore interno: {}", e))?
+}
+
+/// Returns the corrections. With a shared folder configured, the shared file is read
+/// again once the refresh interval has passed; while it is unreachable the local copy is used.
+pub fn load_corrections(app: &AppHandle) -> Result<Vec<CorrectionEntry>, String> {
+    if let Some(dir) = shared_dir(app) {
+        let _lock = lock_store(app);
+        if sync_due(app, &dir) {
+            match sync_shared(app, &dir) {
+                Ok(entries) => return Ok(entries),
+                Err(e) => println!("Correzioni condivise non disponibili: {}", e),
+            }
+        }
+    }
+    load_local(app)
+}
+
+fn load_list<T: serde::de::DeserializeOwned>(app: &AppHandle, key: &str) -> Vec<T> {
+    workspace::store(app, "corrections.json")
+        .ok()
+        .and_then(|store| store.get(key))
+        .and_then(|val| serde_json::from_value(val).ok())
+        .unwrap_or_default()
+}
+
+fn save_list<T: serde::Serialize>(app: &AppHandle, key: &str, list: &[T]) -> Result<(), String> {
+    let store = workspace::store(app, "corrections.json")?;
+    store.set(key, json!(list));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+/// Applies changes to the local copy and records them in the history. If configured,
+/// the changes also go to the shared file; if the shared folder cannot be written they
+/// are queued for the next sync. The history is kept per workstation.
+fn commit_ops(app: &AppHandle, ops: Vec<CorrectionOp>, origin: &str) -> Result<(), String> {
+    if ops.is_empty() {
+        return Ok(());
+    }
+    let _lock = lock_store(app);
+    let mut entries = load_local(app)?;
+    let mut history: Vec<CorrectionHistoryEntry> = load_list(app, "history");
+    let mut next_id = history.last().map_or(1, |h| h.id + 1);
+
+    for op in ops.iter().cloned() {
+        let (wrong, supplier, customer, new_value) = match &op {
+            CorrectionOp::Upsert { entry } => (
+                entry.wrong.clone(),
+                entry.supplier.clone(),
+                entry.customer.clone(),
+                Some(entry.correct.clone()),
+            ),
+            CorrectionOp::Remove {
+                wrong,
+                supplier,
+                customer,
+            } => (wrong.clone(), supplier.clone(), customer.clone(), None),
+        };
+        let old_value = entries
+            .iter()
+            .find(|e| e.same_key(&wrong, &supplier, &customer))
+            .map(|e| e.correct.clone());
+
+        if old_value != new_value {
+            history.push(CorrectionHistoryEntry {
+                id: next_id,
+                wrong,
+                supplier,
+                customer,
+                old_value,
+                new_value,
+                at: now(),
+                user: current_user(),
+                origin: origin.to_string(),
+            });
+            next_id += 1;
+        }
+        apply_op(&mut entries, op);
+    }
+    save_local(app, &entries)?;
+
+    if history.len() > MAX_HISTORY {
+        let excess = history.len() - MAX_HISTORY;
+        history.drain(..excess);
+    }
+    save_list(app, "history", &history)?;
+
+    if let Some(dir) = shared_dir(app) {
+        let mut pending = load_pending(app);
+        pending.extend(ops);
+        set_sync_state(app, &pending, None)?;
+        if let Err(e) = sync_shared(app, &dir) {
+            println!("Correzioni salvate solo in locale: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn sync_status(app: &AppHandle, error: Option<String>) -> CorrectionSyncStatus {
+    CorrectionSyncStatus {
+        shared_path: shared_dir(app).map(|d| d.join(SHARED_FILE_NAME).display().to_string()),
+        online: shared_dir(app).is_some_and(|d| d.is_dir()) && error.is_none(),
+        last_sync: last_sync(app),
+        pending: load_pending(app).len(),
+        error,
+    }
+}
+
+/// Finds the correction for a product name. Exact (normalized) matches win over fuzzy
+/// ones; among those, entries scoped to the supplier and/or customer win over global ones.
+pub fn find_correction<'a>(
+    entries: &'a [CorrectionEntry],
+    name: &str,
+    supplier: Option<&str>,
+    customer: Option<&str>,
+) -> Option<CorrectionMatch<'a>> {
+    let name = normalize_name(name);
+    if name.is_empty() {
+        return None;
+    }
+
+    entries
+        .iter()
+        .filter_map(|e| {
+            let specificity = e.specificity(supplier, customer)?;
+            let wrong = normalize_name(&e.wrong);
+            let score = if wrong == name {
+                1.0
+            } else if !same_quantities(&wrong, &name) {
+                return None;
+            } else {
+                token_similarity(&name, &wrong).min(token_similarity(&wrong, &name))
+            };
+            (score >= FUZZY_THRESHOLD).then_some((score >= 1.0, specificity, score, e))
+        })
+        .max_by(|a, b| {
+            (a.0, a.1)
+                .cmp(&(b.0, b.1))
+                .then_with(|| a.2.total_cmp(&b.2))
+        })
+        .map(|(_, _, score, entry)| CorrectionMatch { entry, score })
+}
+
+fn record_usage(app: &AppHandle, used: &[&CorrectionEntry]) {
+    let _lock = lock_store(app);
+    let mut usage: Vec<CorrectionUsage> = load_list(app, "usage");
+    for entry in used {
+        match usage.iter_mut().find(|u| {
+            normalize(&u.wrong) == normalize(&entry.wrong)
+                && same_scope(&u.supplier, &entry.supplier)
+                && same_scope(&u.customer, &entry.customer)
+        }) {
+            Some(u) => {
+                u.count += 1;
+                u.last_used = now();
+            }
+            None => usage.push(CorrectionUsage {
+                wrong: entry.wrong.clone(),
+                supplier: entry.supplier.clone(),
+                customer: entry.customer.clone(),
+                count: 1,
+                last_used: now(),
+            }),
+        }
+    }
+    if let Err(e) = save_list(app, "usage", &usage) {
+        println!("Impossibile salvare l'uso delle correzioni: {}", e);
+    }
+}
+
+pub fn apply(app: &AppHandle, result: &mut Value, supplier: Option<&str>, customer: Option<&str>) {
+    let entries = match load_corrections(app) {
+        Ok(e) if !e.is_empty() => e,
+        _ => return,
+    };
+    let mut used = Vec::new();
+
+    if let Some(products) = result.get_mut("produkte").and_then(|p| p.as_array_mut()) {
+        for prod in products {
+            if let Some(name_val) = prod.get_mut("produkt") {
+                let name = match name_val.as_str() {
+                    Some(n) => n.to_string(),
+                    None => continue,
+                };
+                if let Some(found) = find_correction(&entries, &name, supplier, customer) {
+                    *name_val = json!(normalize(&found.entry.correct));
+                    prod["correction"] = json!({
+                        "from": name,
+                        "wrong": found.entry.wrong,
+                        "correct": found.entry.correct,
+                        "supplier": found.entry.supplier,
+                        "customer": found.entry.customer,
+                        "score": (found.score * 100.0).round() / 100.0,
+                    });
+                    used.push(found.entry);
+                }
+            }
+        }
+    }
+
+    if !used.is_empty() {
+        record_usage(app, &used);
+    }
+}
+
+#[command]
+pub async fn get_corrections(app: AppHandle) -> Result<Vec<CorrectionEntry>, String> {
+    blocking(&app, load_corrections).await
+}
+
+#[command]
+pub async fn learn_correction(
+    app: AppHandle,
+    wrong: String,
+    correct: String,
+    supplier: Option<String>,
+    customer: Option<String>,
+    origin: Option<String>,
+) -> Result<(), String> {
+    if wrong.trim().is_empty() || correct.trim().is_empty() || wrong == correct {
+        return Ok(());
+    }
+
+    let entry = CorrectionEntry {
+        wrong: wrong.trim().to_string(),
+        correct: correct.trim().to_string(),
+        supplier: normalize_scope(supplier),
+        customer: normalize_scope(customer),
+        added_by: Some(current_user()),
+        added_at: Some(now()),
+    };
+    let origin = origin.unwrap_or_else(|| "grid".to_string());
+    blocking(&app, move |app| {
+        commit_ops(app, vec![CorrectionOp::Upsert { entry }], &origin)
+    })
+    .await
+}
+
+#[command]
+pub async fn remove_correction(
+    app: AppHandle,
+    wrong: String,
+    supplier: Option<String>,
+    customer: Option<String>,
+    origin: Option<String>,
+) -> Result<(), String> {
+    let supplier = normalize_scope(supplier);
+    let customer = normalize_scope(customer);
+    let origin = origin.unwrap_or_else(|| "settings".to_string());
+
+    blocking(&app, move |app| {
+        let entries = load_corrections(app)?;
+        if !entries
+            .iter()
+            .any(|e| e.same_key(&wrong, &supplier, &customer))
+        {
+            return Ok(());
+        }
+        commit_ops(
+            app,
+            vec![CorrectionOp::Remove {
+                wrong,
+                supplier,
+                customer,
+            }],
+            &origin,
+        )
+    })
+    .await
+}
+
+fn is_json(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// Reads corrections from a CSV (`wrong;correct;supplier;customer`) or JSON file. JSON may
+/// be a list of entries or the old `{ "wrong": "correct" }` map.
+fn read_corrections_file(path: &str) -> Result<Vec<CorrectionEntry>, String> {
+    if is_json(path) {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Impossibile leggere il file: {}", e))?;
+        let value: Value = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+            .map_err(|e| format!("Parse errore: {}", e))?;
+        if value.is_object() {
+            let legacy: HashMap<String, String> =
+                serde_json::from_value(value).map_err(|e| format!("Parse errore: {}", e))?;
+            return Ok(legacy
+                .into_iter()
+                .map(|(wrong, correct)| CorrectionEntry {
+                    wrong,
+                    correct,
+                    supplier: None,
+                    customer: None,
+                    added_by: None,
+                    added_at: None,
+                })
+                .collect());
+        }
+        return serde_json::from_value(value).map_err(|e| format!("Parse errore: {}", e));
+    }
+
+    let mut entries = Vec::new();
+    for (i, record) in read_csv_records(path)?.iter().enumerate() {
+        let field = |n: usize| record.get(n).unwrap_or_default().trim().to_string();
+        if i == 0 && field(0).eq_ignore_ascii_case("wrong") {
+            continue;
+        }
+        entries.push(CorrectionEntry {
+            wrong: field(0),
+            correct: field(1),
+            supplier: Some(field(2)),
+            customer: Some(field(3)),
+            added_by: Some(field(4)).filter(|v| !v.is_empty()),
+            added_at: Some(field(5)).filter(|v| !v.is_empty()),
+        });
+    }
+    Ok(entries)
+}
+
+/// Works out how imported entries merge into the existing ones, returning the report, the
+/// changes to apply and the imported entries kept as conflicts. Entries without a wrong or
+/// correct name are ignored.
+fn merge_corrections(
+    existing: &[CorrectionEntry],
+    imported: Vec<CorrectionEntry>,
+    policy: MergePolicy,
+) -> (
+    CorrectionImportReport,
+    Vec<CorrectionOp>,
+    Vec<CorrectionEntry>,
+) {
+    let mut entries = existing.to_vec();
+    let mut ops = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut report = CorrectionImportReport {
+        added: 0,
+        overwritten: 0,
+        skipped: 0,
+        unchanged: 0,
+        conflicts: 0,
+        changes: Vec::new(),
+        applied: false,
+    };
+
+    for entry in imported {
+        let wrong = entry.wrong.trim().to_string();
+        let correct = entry.correct.trim().to_string();
+        if wrong.is_empty() || correct.is_empty() || wrong == correct {
+            continue;
+        }
+        let supplier = normalize_scope(entry.supplier);
+        let customer = normalize_scope(entry.customer);
+
+        let current = entries
+            .iter()
+            .find(|e| e.same_key(&wrong, &supplier, &customer))
+            .map(|e| e.correct.clone());
+        let upsert = CorrectionOp::Upsert {
+            entry: CorrectionEntry {
+                wrong: wrong.clone(),
+                correct: correct.clone(),
+                supplier: supplier.clone(),
+                customer: customer.clone(),
+                added_by: entry.added_by.or_else(|| Some(current_user())),
+                added_at: entry.added_at.or_else(|| Some(now())),
+            },
+        };
+
+        let action = match &current {
+            None => {
+                apply_op(&mut entries, upsert.clone());
+                ops.push(upsert);
+                report.added += 1;
+                "add"
+            }
+            Some(c) if normalize(c) == normalize(&correct) => {
+                report.unchanged += 1;
+                "unchanged"
+            }
+            Some(_) => match policy {
+                MergePolicy::Overwrite => {
+                    apply_op(&mut entries, upsert.clone());
+                    ops.push(upsert);
+                    report.overwritten += 1;
+                    "overwrite"
+                }
+                MergePolicy::Skip => {
+                    report.skipped += 1;
+                    "skip"
+                }
+                MergePolicy::KeepBoth => {
+                    if let CorrectionOp::Upsert { entry } = upsert {
+                        conflicts.push(entry);
+                    }
+                    report.conflicts += 1;
+                    "conflict"
+                }
+            },
+        };
+
+        if action != "unchanged" {
+            report.changes.push(CorrectionChange {
+                action: action.to_string(),
+                wrong,
+                supplier,
+                customer,
+                current,
+                incoming: correct,
+            });
+        }
+    }
+    (report, ops, conflicts)
+}
+
+/// Adds imported values to the open conflicts; a newer value for the same key replaces
+/// the older one.
+fn add_conflicts(open: &mut Vec<CorrectionEntry>, incoming: Vec<CorrectionEntry>) {
+    for entry in incoming {
+        match open
+            .iter_mut()
+            .find(|e| e.same_key(&entry.wrong, &entry.supplier, &entry.customer))
+        {
+            Some(existing) => *existing = entry,
+            None => open.push(entry),
+        }
+    }
+}
+
+#[command]
+pub async fn export_corrections(app: AppHandle, path: String) -> Result<usize, String> {
+    policy::check_export_path(Path::new(&path))?;
+    let entries = blocking(&app, load_corrections).await?;
+
+    if is_json(&path) {
+        let content = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+        std::fs::write(&path, content).map_err(|e| format!("Errore di scrittura: {}", e))?;
+        return Ok(entries.len());
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(&path)
+        .map_err(|e| format!("Impossibile creare il file: {}", e))?;
+    writer
+        .write_record([
+            "wrong", "correct", "supplier", "customer", "addedBy", "addedAt",
+        ])
+        .map_err(|e| e.to_string())?;
+    for entry in &entries {
+        writer
+            .write_record([
+                entry.wrong.as_str(),
+                entry.correct.as_str(),
+                entry.supplier.as_deref().unwrap_or_default(),
+                entry.customer.as_deref().unwrap_or_default(),
+                entry.added_by.as_deref().unwrap_or_default(),
+                entry.added_at.as_deref().unwrap_or_default(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Errore di scrittura: {}", e))?;
+    Ok(entries.len())
+}
+
+/// Imports corrections with the given merge policy. With `dry_run` nothing is saved and
+/// the report only shows what would change.
+#[command]
+pub async fn import_corrections(
+    app: AppHandle,
+    path: String,
+    policy: MergePolicy,
+    dry_run: bool,
+) -> Result<CorrectionImportReport, String> {
+    let imported = read_corrections_file(&path)?;
+    blocking(&app, move |app| merge_into(app, imported, policy, dry_run)).await
+}
+
+/// Merges corrections from another source into the current ones.
+pub fn merge_into(
+    app: &AppHandle,
+    imported: Vec<CorrectionEntry>,
+    policy: MergePolicy,
+    dry_run: bool,
+) -> Result<CorrectionImportReport, String> {
+    let entries = load_corrections(app)?;
+
+    let (mut report, ops, conflicts) = merge_corrections(&entries, imported, policy);
+    if dry_run {
+        return Ok(report);
+    }
+    commit_ops(app, ops, "import")?;
+    if !conflicts.is_empty() {
+        let _lock = lock_store(app);
+        let mut open: Vec<CorrectionEntry> = load_list(app, "conflicts");
+        add_conflicts(&mut open, conflicts);
+        save_list(app, "conflicts", &open)?;
+    }
+    report.applied = true;
+    Ok(report)
+}
+
+/// Imported values that differ from an existing correction, kept with "keep both".
+#[command]
+pub async fn get_correction_conflicts(app: AppHandle) -> Result<Vec<CorrectionEntry>, String> {
+    Ok(load_list(&app, "conflicts"))
+}
+
+/// Closes a conflict: with `accept` the imported value replaces the current correction,
+/// otherwise it is dropped.
+#[command]
+pub async fn resolve_correction_conflict(
+    app: AppHandle,
+    wrong: String,
+    supplier: Option<String>,
+    customer: Option<String>,
+    accept: bool,
+) -> Result<(), String> {
+    let supplier = normalize_scope(supplier);
+    let customer = normalize_scope(customer);
+
+    blocking(&app, move |app| {
+        let entry = {
+            let _lock = lock_store(app);
+            let mut open: Vec<CorrectionEntry> = load_list(app, "conflicts");
+            let index = open
+                .iter()
+                .position(|e| e.same_key(&wrong, &supplier, &customer))
+                .ok_or("Conflitto non trovato.".to_string())?;
+            let entry = open.remove(index);
+            save_list(app, "conflicts", &open)?;
+            entry
+        };
+        if !accept {
+            return Ok(());
+        }
+        commit_ops(app, vec![CorrectionOp::Upsert { entry }], "conflict")
+    })
+    .await
+}
+
+/// Reads the shared corrections file now, replaying changes queued while offline.
+#[command]
+pub async fn sync_corrections(app: AppHandle) -> Result<CorrectionSyncStatus, String> {
+    blocking(&app, |app| {
+        let _lock = lock_store(app);
+        let error = match shared_dir(app) {
+            Some(dir) => sync_shared(app, &dir).err(),
+            None => None,
+        };
+        Ok(sync_status(app, error))
+    })
+    .await
+}
+
+/// Returns the history of one correction key, or of all corrections if no key is given,
+/// newest first.
+#[command]
+pub async fn get_correction_history(
+    app: AppHandle,
+    wrong: Option<String>,
+    supplier: Option<String>,
+    customer: Option<String>,
+) -> Result<Vec<CorrectionHistoryEntry>, String> {
+    let supplier = normalize_scope(supplier);
+    let customer = normalize_scope(customer);
+    let history: Vec<CorrectionHistoryEntry> = load_list(&app, "history");
+
+    Ok(history
+        .into_iter()
+        .rev()
+        .filter(|h| match &wrong {
+            Some(w) => {
+                normalize(&h.wrong) == normalize(w)
+                    && same_scope(&h.supplier, &supplier)
+                    && same_scope(&h.customer, &customer)
+            }
+            None => true,
+        })
+        .collect())
+}
+
+/// Sets a correction back to the value it had before the given history entry. Undoing
+/// an addition removes the correction again.
+#[command]
+pub async fn restore_correction(app: AppHandle, id: u64) -> Result<(), String> {
+    let history: Vec<CorrectionHistoryEntry> = load_list(&app, "history");
+    let change = history
+        .into_iter()
+        .find(|h| h.id == id)
+        .ok_or("Voce della cronologia non trovata.".to_string())?;
+
+    let op = match change.old_value {
+        Some(correct) => CorrectionOp::Upsert {
+            entry: CorrectionEntry {
+                wrong: change.wrong,
+                correct,
+                supplier: change.supplier,
+                customer: change.customer,
+                added_by: Some(current_user()),
+                added_at: Some(now()),
+            },
+        },
+        None => CorrectionOp::Remove {
+            wrong: change.wrong,
+            supplier: change.supplier,
+            customer: change.customer,
+        },
+    };
+    blocking(&app, move |app| commit_ops(app, vec![op], "restore")).await
+}
+
+#[command]
+pub async fn get_correction_usage(app: AppHandle) -> Result<Vec<CorrectionUsage>, String> {
+    Ok(load_list(&app, "usage"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(wrong: &str, correct: &str, supplier: Option<&str>) -> CorrectionEntry {
+        CorrectionEntry {
+            wrong: wrong.to_string(),
+            correct: correct.to_string(),
+            supplier: supplier.map(str::to_string),
+            customer: None,
+            added_by: None,
+            added_at: None,
+        }
+    }
+
+    fn corrected(
+        entries: &[CorrectionEntry],
+        name: &str,
+        supplier: Option<&str>,
+    ) -> Option<String> {
+        find_correction(entries, name, supplier, None).map(|m| m.entry.correct.clone())
+    }
+
+    #[test]
+    fn exact_match_wins_over_fuzzy() {
+        let entries = [
+            entry("CARDO MARIANO SEMEN", "CARDO MARIANO SEMI", None),
+            entry("CARDO MARIANO SEME", "CARDO MARIANO (SEME)", None),
+        ];
+        let found = find_correction(&entries, "Cardo  Mariano, Semen", None, None).unwrap();
+        assert_eq!(found.entry.correct, "CARDO MARIANO SEMI");
+        assert_eq!(found.score, 1.0);
+    }
+
+    #[test]
+    fn fuzzy_match_applies_to_typos() {
+        let entries = [entry(
+            "ORTICA FOGLIE TAGLIATE",
+            "ORTICA FOGLIE TAGLIO TISANA",
+            None,
+        )];
+        let found = find_correction(&entries, "ORTICA FOGLIE TAGLATE", None, None).unwrap();
+        assert_eq!(found.entry.correct, "ORTICA FOGLIE TAGLIO TISANA");
+        assert!(found.score >= FUZZY_THRESHOLD && found.score < 1.0);
+    }
+
+    #[test]
+    fn unrelated_names_do_not_match() {
+        let entries = [entry("CARDO MARIANO SEMEN", "CARDO MARIANO SEMI", None)];
+        assert!(find_correction(&entries, "MELISSA FOGLIE", None, None).is_none());
+        assert!(find_correction(&entries, "  ", None, None).is_none());
+    }
+
+    #[test]
+    fn supplier_scoped_entry_wins_over_global() {
+        let entries = [
+            entry("SALVIA FOGLIE", "SALVIA FOGLIE TAGLIATE", None),
+            entry("SALVIA FOGLIE", "SALVIA FOGLIE INTERE", Some("Rühle")),
+        ];
+        assert_eq!(
+            corrected(&entries, "SALVIA FOGLIE", Some("rühle")).as_deref(),
+            Some("SALVIA FOGLIE INTERE")
+        );
+        assert_eq!(
+            corrected(&entries, "SALVIA FOGLIE", Some("Altro")).as_deref(),
+            Some("SALVIA FOGLIE TAGLIATE")
+        );
+        assert_eq!(
+            corrected(&entries, "SALVIA FOGLIE", None).as_deref(),
+            Some("SALVIA FOGLIE TAGLIATE")
+        );
+    }
+
+    #[test]
+    fn exact_global_entry_wins_over_fuzzy_scoped_one() {
+        let entries = [
+            entry("SALVIA FOGLIE", "SALVIA FOGLIE TAGLIATE", None),
+            entry("SALVIA FOGLIA", "SALVIA FOGLIE INTERE", Some("Rühle")),
+        ];
+        assert_eq!(
+            corrected(&entries, "SALVIA FOGLIE", Some("Rühle")).as_deref(),
+            Some("SALVIA FOGLIE TAGLIATE")
+        );
+    }
+
+    #[test]
+    fn different_numbers_or_units_never_match_fuzzily() {
+        let entries = [entry(
+            "ORTICA DIOICA FOGLIE 1000G",
+            "ORTICA FOGLIE 1 KG",
+            None,
+        )];
+        assert!(find_correction(&entries, "ORTICA DIOICA FOGLIE 2000G", None, None).is_none());
+        assert!(find_correction(&entries, "ORTICA DIOICA FOGLIE 1000 G", None, None).is_none());
+        assert!(find_correction(&entries, "ORTICA DIOICA FOGLIE 1000", None, None).is_none());
+        assert_eq!(
+            corrected(&entries, "ORTICA DIOICA FOGLE 1000G", None).as_deref(),
+            Some("ORTICA FOGLIE 1 KG")
+        );
+
+        let entries = [entry(
+            "ORTICA DIOICA FOGLIE 500 G",
+            "ORTICA FOGLIE 500G",
+            None,
+        )];
+        assert!(find_correction(&entries, "ORTICA DIOICA FOGLIE 500 KG", None, None).is_none());
+    }
+
+    #[test]
+    fn keep_both_keeps_the_incoming_value() {
+        let existing = [entry("ORTICA FOGLE", "ORTICA FOGLIE", None)];
+        let imported = vec![
+            entry("ortica fogle", "ORTICA FOGLIE TAGLIATE", None),
+            entry("MENTA PIPERTIA", "MENTA PIPERITA", None),
+        ];
+
+        let (report, ops, conflicts) =
+            merge_corrections(&existing, imported, MergePolicy::KeepBoth);
+
+        assert_eq!((report.added, report.conflicts), (1, 1));
+        assert_eq!(ops.len(), 1);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].wrong, "ortica fogle");
+        assert_eq!(conflicts[0].correct, "ORTICA FOGLIE TAGLIATE");
+
+        let mut open = vec![entry("ORTICA FOGLE", "ORTICA (FOGLIE)", None)];
+        add_conflicts(&mut open, conflicts);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].correct, "ORTICA FOGLIE TAGLIATE");
+
+        let (_, _, skipped) = merge_corrections(
+            &existing,
+            vec![entry("ORTICA FOGLE", "ORTICA FOGLIE TAGLIATE", None)],
+            MergePolicy::Skip,
+        );
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn a_new_shared_folder_counts_as_first_sync() {
+        let dir = Path::new("/srv/condivise/correzioni");
+        let synced = "2026-01-10 08:00:00";
+
+        assert!(is_new_share(None, None, dir));
+        assert!(!is_new_share(
+            Some(synced),
+            Some("/srv/condivise/correzioni"),
+            dir
+        ));
+        assert!(is_new_share(Some(synced), Some("/srv/vecchia"), dir));
+        assert!(!is_new_share(Some(synced), None, dir));
+    }
+}
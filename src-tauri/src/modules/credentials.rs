@@ -0,0 +1,379 @@
+use crate::modules::config::KEYRING_USER;
+use crate::modules::policy;
+use crate::modules::secrets;
+use crate::modules::workspace;
+
+use serde_json::json;
+use std::time::Duration;
+use tauri::{command, AppHandle};
+
+pub const DEFAULT_PROVIDER: &str = "mistral";
+const PROVIDERS: [&str; 1] = [DEFAULT_PROVIDER];
+pub const DEFAULT_NAME: &str = "default";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Credential {
+    pub name: String,
+    pub provider: String,
+    /// Name of the secret in the keyring or the encrypted file.
+    pub secret_id: String,
+    pub active: bool,
+    pub created_at: String,
+    #[serde(default)]
+    pub last_tested: Option<String>,
+    #[serde(default)]
+    pub last_status: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialTestResult {
+    pub name: String,
+    pub provider: String,
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn check_provider(provider: &str) -> Result<(), String> {
+    if PROVIDERS.contains(&provider) {
+        Ok(())
+    } else {
+        Err(format!("Provider sconosciuto: {}", provider))
+    }
+}
+
+fn save_credentials(app: &AppHandle, credentials: &[Credential]) -> Result<(), String> {
+    let store = workspace::store(app, "credentials.json")?;
+    store.set("credentials", json!(credentials));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+/// Loads the credentials of the active workspace. In the default workspace the single key
+/// of older versions becomes the active "default" credential of Mistral, keeping its
+/// keyring entry.
+fn load_credentials(app: &AppHandle) -> Result<Vec<Credential>, String> {
+    let store = workspace::store(app, "credentials.json")?;
+
+    if let Some(val) = store.get("credentials") {
+        return serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e));
+    }
+
+    if workspace::active_id(app) != workspace::DEFAULT_WORKSPACE {
+        return Ok(Vec::new());
+    }
+    if let Err(e) = secrets::migrate_plaintext(app, "apiKey", KEYRING_USER) {
+        println!("⚠️ Migrazione della chiave API non riuscita: {}", e);
+    }
+    let credentials = match secrets::read_secret(app, KEYRING_USER).0 {
+        Some(_) => vec![Credential {
+            name: DEFAULT_NAME.to_string(),
+            provider: DEFAULT_PROVIDER.to_string(),
+            secret_id: KEYRING_USER.to_string(),
+            active: true,
+            created_at: now(),
+            last_tested: None,
+            last_status: None,
+        }],
+        None => Vec::new(),
+    };
+    save_credentials(app, &credentials)?;
+    Ok(credentials)
+}
+
+fn secret_id(app: &AppHandle, provider: &str, name: &str) -> String {
+    workspace::secret_name(app, &format!("{}_api_key:{}", provider, name))
+}
+
+/// Returns the active credential of a provider together with its key.
+/// The active credential of a provider, whether or not its key can be read.
+pub fn active(app: &AppHandle, provider: &str) -> Option<Credential> {
+    load_credentials(app)
+        .ok()?
+        .into_iter()
+        .find(|c| c.provider == provider && c.active)
+}
+
+pub fn active_credential(app: &AppHandle, provider: &str) -> Option<(Credential, String)> {
+    let credential = active(app, provider)?;
+    let key = secrets::read_secret(app, &credential.secret_id).0?;
+    Some((credential, key))
+}
+
+pub fn active_secret_id(app: &AppHandle, provider: &str) -> Option<String> {
+    active(app, provider).map(|c| c.secret_id)
+}
+
+/// Adds or replaces a credential. The first credential of a provider becomes active.
+pub fn store_credential(
+    app: &AppHandle,
+    provider: &str,
+    name: &str,
+    key: &str,
+) -> Result<Credential, String> {
+    check_provider(provider)?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Il nome della credenziale è vuoto.".to_string());
+    }
+    if key.trim().is_empty() {
+        return Err("La chiave API è vuota.".to_string());
+    }
+
+    let mut credentials = load_credentials(app)?;
+    let has_active = credentials
+        .iter()
+        .any(|c| c.provider == provider && c.active && c.name != name);
+
+    let credential = match credentials
+        .iter_mut()
+        .find(|c| c.provider == provider && c.name == name)
+    {
+        Some(existing) => {
+            existing.last_tested = None;
+            existing.last_status = None;
+            existing.clone()
+        }
+        None => {
+            let credential = Credential {
+                name: name.to_string(),
+                provider: provider.to_string(),
+                secret_id: secret_id(app, provider, name),
+                active: !has_active,
+                created_at: now(),
+                last_tested: None,
+                last_status: None,
+            };
+            credentials.push(credential.clone());
+            credential
+        }
+    };
+
+    secrets::store_secret(app, &credential.secret_id, key)?;
+    save_credentials(app, &credentials)?;
+    Ok(credential)
+}
+
+fn set_active(credentials: &mut [Credential], provider: &str, name: &str) -> Result<(), String> {
+    if !credentials
+        .iter()
+        .any(|c| c.provider == provider && c.name == name)
+    {
+        return Err(format!("Credenziale non trovata: {}", name));
+    }
+    for c in credentials.iter_mut().filter(|c| c.provider == provider) {
+        c.active = c.name == name;
+    }
+    Ok(())
+}
+
+/// Makes an authenticated request that costs nothing (listing the models) and turns the
+/// answer into a message the user can act on.
+async fn test_key(provider: &str, key: &str) -> (bool, Option<u16>, String) {
+    let url = match provider {
+        DEFAULT_PROVIDER => "https://api.mistral.ai/v1/models",
+        _ => return (false, None, format!("Provider sconosciuto: {}", provider)),
+    };
+    if let Err(e) = policy::check_endpoint(url) {
+        return (false, None, e);
+    }
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(url)
+        .header("Authorization", format!("Bearer {}", key))
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await;
+
+    match res {
+        Ok(res) => {
+            let status = res.status().as_u16();
+            let message = match status {
+                200..=299 => "Chiave valida.".to_string(),
+                401 => "Chiave non valida o revocata.".to_string(),
+                402 => "Credito esaurito: verificare il piano di pagamento.".to_string(),
+                403 => "Chiave senza permessi per questa API.".to_string(),
+                429 => "Limite di richieste o quota esaurita.".to_string(),
+                500..=599 => format!("Il servizio non è disponibile (stato {}).", status),
+                _ => format!("Risposta inattesa dal servizio (stato {}).", status),
+            };
+            ((200..300).contains(&status), Some(status), message)
+        }
+        Err(e) if e.is_timeout() => (false, None, "Il servizio non risponde.".to_string()),
+        Err(e) => (false, None, format!("Connessione non riuscita: {}", e)),
+    }
+}
+
+async fn test_one(app: &AppHandle, credential: &Credential) -> CredentialTestResult {
+    let (ok, status, message) = match secrets::read_secret(app, &credential.secret_id).0 {
+        Some(key) => test_key(&credential.provider, &key).await,
+        None => (false, None, "Chiave non trovata.".to_string()),
+    };
+    CredentialTestResult {
+        name: credential.name.clone(),
+        provider: credential.provider.clone(),
+        ok,
+        status,
+        message,
+    }
+}
+
+fn record_results(app: &AppHandle, results: &[CredentialTestResult]) -> Result<(), String> {
+    let mut credentials = load_credentials(app)?;
+    for result in results {
+        if let Some(c) = credentials
+            .iter_mut()
+            .find(|c| c.provider == result.provider && c.name == result.name)
+        {
+            c.last_tested = Some(now());
+            c.last_status = Some(result.message.clone());
+        }
+    }
+    save_credentials(app, &credentials)
+}
+
+#[command]
+pub async fn get_credentials(app: AppHandle) -> Result<Vec<Credential>, String> {
+    load_credentials(&app)
+}
+
+#[command]
+pub async fn save_credential(
+    app: AppHandle,
+    provider: String,
+    name: String,
+    key: String,
+) -> Result<Credential, String> {
+    store_credential(&app, &provider, &name, &key)
+}
+
+#[command]
+pub async fn set_active_credential(
+    app: AppHandle,
+    provider: String,
+    name: String,
+) -> Result<(), String> {
+    let mut credentials = load_credentials(&app)?;
+    set_active(&mut credentials, &provider, &name)?;
+    save_credentials(&app, &credentials)
+}
+
+#[command]
+pub async fn remove_credential(
+    app: AppHandle,
+    provider: String,
+    name: String,
+) -> Result<(), String> {
+    let mut credentials = load_credentials(&app)?;
+    let idx = credentials
+        .iter()
+        .position(|c| c.provider == provider && c.name == name)
+        .ok_or(format!("Credenziale non trovata: {}", name))?;
+    if credentials[idx].active
+        && credentials
+            .iter()
+            .any(|c| c.provider == provider && c.name != name)
+    {
+        return Err("Attivare prima un'altra credenziale.".to_string());
+    }
+
+    let removed = credentials.remove(idx);
+    save_credentials(&app, &credentials)?;
+    wipe_secret(&app, &removed);
+    Ok(())
+}
+
+/// Deletes the key of a credential that is no longer listed. The list is saved first,
+/// so that a failure here leaves an unused secret rather than a credential without key.
+fn wipe_secret(app: &AppHandle, credential: &Credential) {
+    if let Err(e) = secrets::store_secret(app, &credential.secret_id, "") {
+        println!(
+            "⚠️ Chiave della credenziale {} non eliminata: {}",
+            credential.name, e
+        );
+    }
+}
+
+/// Tests the credentials of a provider, or one of them if a name is given.
+#[command]
+pub async fn test_credentials(
+    app: AppHandle,
+    provider: Option<String>,
+    name: Option<String>,
+) -> Result<Vec<CredentialTestResult>, String> {
+    let credentials: Vec<Credential> = load_credentials(&app)?
+        .into_iter()
+        .filter(|c| provider.as_ref().is_none_or(|p| &c.provider == p))
+        .filter(|c| name.as_ref().is_none_or(|n| &c.name == n))
+        .collect();
+    if credentials.is_empty() {
+        return Err("Nessuna credenziale da verificare.".to_string());
+    }
+
+    let mut results = Vec::with_capacity(credentials.len());
+    for credential in &credentials {
+        results.push(test_one(&app, credential).await);
+    }
+    record_results(&app, &results)?;
+    Ok(results)
+}
+
+/// Adds a new key under `name`, verifies it and only then makes it active and retires
+/// the previously active credential. A key that fails the test is discarded.
+#[command]
+pub async fn rotate_credential(
+    app: AppHandle,
+    provider: String,
+    name: String,
+    key: String,
+) -> Result<CredentialTestResult, String> {
+    check_provider(&provider)?;
+    let previous = load_credentials(&app)?
+        .into_iter()
+        .find(|c| c.provider == provider && c.active && c.name != name.trim());
+    if load_credentials(&app)?
+        .iter()
+        .any(|c| c.provider == provider && c.name == name.trim())
+    {
+        return Err(format!("La credenziale {} esiste già.", name.trim()));
+    }
+
+    let (ok, status, message) = test_key(&provider, key.trim()).await;
+    if !ok {
+        return Err(format!("Nuova chiave non attivata: {}", message));
+    }
+
+    let credential = store_credential(&app, &provider, &name, &key)?;
+    let mut credentials = load_credentials(&app)?;
+    set_active(&mut credentials, &provider, &credential.name)?;
+    if let Some(c) = credentials
+        .iter_mut()
+        .find(|c| c.provider == provider && c.name == credential.name)
+    {
+        c.last_tested = Some(now());
+        c.last_status = Some(message.clone());
+    }
+    if let Some(previous) = &previous {
+        credentials.retain(|c| !(c.provider == provider && c.name == previous.name));
+    }
+    save_credentials(&app, &credentials)?;
+    if let Some(previous) = &previous {
+        wipe_secret(&app, previous);
+    }
+
+    Ok(CredentialTestResult {
+        name: credential.name,
+        provider,
+        ok,
+        status,
+        message,
+    })
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Pdf,
+    Ubl,
+    Image,
+    Email,
+    Archive,
+}
+
+impl DocumentKind {
+    pub fn from_path(path: &str) -> Option<DocumentKind> {
+        let ext = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "pdf" => Some(DocumentKind::Pdf),
+            "xml" => Some(DocumentKind::Ubl),
+            "jpg" | "jpeg" | "png" | "tif" | "tiff" => Some(DocumentKind::Image),
+            "eml" => Some(DocumentKind::Email),
+            "zip" => Some(DocumentKind::Archive),
+            _ => None,
+        }
+    }
+}
+
+/// A single analysable document, possibly extracted from a container such as an email.
+#[derive(serde::Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDocument {
+    pub path: String,
+    pub source_path: Option<String>,
+    pub entry_name: Option<String>,
+    pub hints: DocumentHints,
+}
+
+#[derive(serde::Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentHints {
+    pub lieferant: Option<String>,
+    pub doc_type: Option<String>,
+    pub datum: Option<String>,
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+}
+
+pub fn guess_doc_type(text: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    let invoice_words = [
+        "fattura",
+        "invoice",
+        "rechnung",
+        "nota di credito",
+        "credit note",
+    ];
+    let order_words = ["ordine", "order", "auftrag", "bestellung", "conferma"];
+
+    if invoice_words.iter().any(|w| lower.contains(w)) {
+        Some("rechnung".to_string())
+    } else if order_words.iter().any(|w| lower.contains(w)) {
+        Some("auftrag".to_string())
+    } else {
+        None
+    }
+}
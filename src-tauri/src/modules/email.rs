@@ -0,0 +1,191 @@
+use crate::modules::archive;
+use crate::modules::document::{guess_doc_type, DocumentHints, DocumentKind, InputDocument};
+use crate::modules::utils::{sanitize_file_name, unique_path};
+
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+use std::fs;
+use std::path::Path;
+
+fn extension_for(content_type: &str) -> Option<&'static str> {
+    match content_type {
+        "application/pdf" => Some("pdf"),
+        "application/xml" | "text/xml" => Some("xml"),
+        "image/jpeg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/tiff" => Some("tif"),
+        "application/zip" | "application/x-zip-compressed" => Some("zip"),
+        _ => None,
+    }
+}
+
+fn supplier_from_sender(message: &Message) -> (Option<String>, Option<String>) {
+    let addr = match message.from().and_then(|f| f.first()) {
+        Some(a) => a,
+        None => return (None, None),
+    };
+
+    let sender = match (addr.name(), addr.address()) {
+        (Some(n), Some(a)) => Some(format!("{} <{}>", n, a)),
+        (None, Some(a)) => Some(a.to_string()),
+        (Some(n), None) => Some(n.to_string()),
+        (None, None) => None,
+    };
+
+    let supplier = addr
+        .name()
+        .map(|n| n.trim().trim_matches('"').to_string())
+        .filter(|n| !n.is_empty() && !n.contains('@'))
+        .or_else(|| {
+            let domain = addr.address()?.rsplit('@').next()?;
+            let labels: Vec<&str> = domain.split('.').collect();
+            let label = if labels.len() >= 2 {
+                labels[labels.len() - 2]
+            } else {
+                labels[0]
+            };
+            Some(label.to_string())
+        })
+        .map(|s| s.to_uppercase());
+
+    (supplier, sender)
+}
+
+fn collect_attachments(
+    message: &Message,
+    target_dir: &Path,
+    hints: &DocumentHints,
+    source_path: &str,
+    depth: usize,
+    out: &mut Vec<InputDocument>,
+) -> Result<(), String> {
+    for part in message.attachments() {
+        if let PartType::Message(nested) = &part.body {
+            if depth >= archive::MAX_NESTING {
+                println!("Email annidata troppo profonda, ignorata: {}", source_path);
+                continue;
+            }
+            collect_attachments(nested, target_dir, hints, source_path, depth + 1, out)?;
+            continue;
+        }
+
+        let content_type = part
+            .content_type()
+            .map(|ct| {
+                format!(
+                    "{}/{}",
+                    ct.ctype().to_lowercase(),
+                    ct.subtype().unwrap_or_default().to_lowercase()
+                )
+            })
+            .unwrap_or_default();
+
+        let file_name = match part.attachment_name() {
+            Some(name) => sanitize_file_name(name),
+            None => match extension_for(&content_type) {
+                Some(ext) => format!("allegato_{}.{}", out.len() + 1, ext),
+                None => continue,
+            },
+        };
+
+        let kind = match DocumentKind::from_path(&file_name) {
+            Some(DocumentKind::Email) | None => continue,
+            Some(k) => k,
+        };
+
+        // Images referenced from the HTML body are signature logos, not documents.
+        if kind == DocumentKind::Image && part.content_id().is_some() {
+            continue;
+        }
+
+        let target = unique_path(target_dir, &file_name);
+        fs::write(&target, part.contents())
+            .map_err(|e| format!("Impossibile salvare l'allegato {}: {}", file_name, e))?;
+
+        if kind == DocumentKind::Archive {
+            let extracted = match archive::extract(&target.to_string_lossy(), target_dir, depth + 1)
+            {
+                Ok(docs) => docs,
+                Err(e) => {
+                    println!("Archivio nell'email ignorato: {}", e);
+                    continue;
+                }
+            };
+            for mut doc in extracted {
+                doc.entry_name = Some(format!(
+                    "{}/{}",
+                    file_name,
+                    doc.entry_name.unwrap_or_default()
+                ));
+                doc.source_path = Some(source_path.to_string());
+                doc.hints.lieferant = hints.lieferant.clone();
+                doc.hints.datum = hints.datum.clone();
+                doc.hints.sender = hints.sender.clone();
+                doc.hints.subject = hints.subject.clone();
+                out.push(doc);
+            }
+            continue;
+        }
+
+        let mut doc_hints = hints.clone();
+        if let Some(doc_type) = guess_doc_type(&file_name) {
+            doc_hints.doc_type = Some(doc_type);
+        }
+
+        out.push(InputDocument {
+            path: target.to_string_lossy().to_string(),
+            source_path: Some(source_path.to_string()),
+            entry_name: Some(file_name),
+            hints: doc_hints,
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses an `.eml` file and writes its PDF/XML/image/ZIP attachments into `work_dir`.
+/// `depth` counts the containers around it.
+pub fn extract(path: &str, work_dir: &Path, depth: usize) -> Result<Vec<InputDocument>, String> {
+    if depth > archive::MAX_NESTING {
+        return Err(format!("Email annidata troppo profonda: {}", path));
+    }
+    let raw = fs::read(path).map_err(|e| format!("Impossibile leggere l'email: {}", e))?;
+    let message = MessageParser::default()
+        .parse(&raw)
+        .ok_or_else(|| format!("Email non valida: {}", path))?;
+
+    let (lieferant, sender) = supplier_from_sender(&message);
+    let subject = message.subject().map(|s| s.to_string());
+    let hints = DocumentHints {
+        lieferant,
+        doc_type: subject.as_deref().and_then(guess_doc_type),
+        datum: message
+            .date()
+            .map(|d| format!("{:02}/{:02}/{:04}", d.day, d.month, d.year)),
+        sender,
+        subject,
+    };
+
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| sanitize_file_name(&s.to_string_lossy()))
+        .unwrap_or_else(|| "email".to_string());
+    let target_dir = unique_path(
+        work_dir,
+        &format!("{}_{}", stem, chrono::Local::now().format("%Y%m%d_%H%M%S")),
+    );
+    fs::create_dir_all(&target_dir)
+        .map_err(|e| format!("Impossibile creare la cartella: {}", e))?;
+
+    let mut documents = Vec::new();
+    collect_attachments(&message, &target_dir, &hints, path, depth, &mut documents)?;
+
+    if documents.is_empty() {
+        let _ = fs::remove_dir(&target_dir);
+        return Err(format!(
+            "Nessun allegato PDF, XML, immagine o ZIP trovato nell'email {}",
+            path
+        ));
+    }
+
+    Ok(documents)
+}
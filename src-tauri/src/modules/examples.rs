@@ -0,0 +1,210 @@
+use crate::modules::ai::run_sidecar;
+use crate::modules::document::DocumentKind;
+use crate::modules::images;
+use crate::modules::workspace;
+
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{command, AppHandle};
+
+const MAX_EXCERPT_CHARS: usize = 1500;
+const MAX_EXAMPLE_PRODUCTS: usize = 8;
+const MAX_STORED_PER_SUPPLIER: usize = 5;
+const EXAMPLES_IN_PROMPT: usize = 2;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FewShotExample {
+    pub doc_type: String,
+    pub file_name: String,
+    pub excerpt: String,
+    pub result: Value,
+    pub confirmed_at: String,
+}
+
+fn supplier_key(supplier: &str) -> String {
+    supplier
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn file_name_of(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn load_examples(app: &AppHandle) -> Result<HashMap<String, Vec<FewShotExample>>, String> {
+    let store = workspace::store(app, "examples.json")?;
+
+    match store.get("examples") {
+        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Cuts the document text down to the part around the first confirmed product,
+/// so the example shows the table layout rather than the letterhead.
+fn build_excerpt(text: &str, first_product: Option<&str>) -> String {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    let anchor = first_product
+        .and_then(|p| p.split_whitespace().find(|w| w.chars().count() >= 3))
+        .and_then(|word| Regex::new(&format!("(?i){}", regex::escape(word))).ok())
+        .and_then(|re| lines.iter().position(|l| re.is_match(l)));
+    let start = anchor.map(|i| i.saturating_sub(3)).unwrap_or(0);
+
+    let mut excerpt = String::new();
+    for line in &lines[start..] {
+        if excerpt.chars().count() + line.chars().count() + 1 > MAX_EXCERPT_CHARS {
+            break;
+        }
+        excerpt.push_str(line.trim_end());
+        excerpt.push('\n');
+    }
+
+    if excerpt.is_empty() {
+        excerpt = text.chars().take(MAX_EXCERPT_CHARS).collect();
+    }
+    excerpt
+}
+
+fn compact_result(result: &Value, doc_type: &str) -> Value {
+    let fields: &[&str] = if doc_type == "rechnung" {
+        &[
+            "produkt",
+            "produktOriginal",
+            "gelieferteMenge",
+            "preis",
+            "waehrung",
+        ]
+    } else {
+        &["produkt", "produktOriginal", "menge", "waehrung", "preis"]
+    };
+
+    let produkte: Vec<Value> = result
+        .get("produkte")
+        .and_then(|p| p.as_array())
+        .map(|a| a.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .take(MAX_EXAMPLE_PRODUCTS)
+        .map(|p| {
+            let mut product = serde_json::Map::new();
+            for field in fields {
+                product.insert(
+                    field.to_string(),
+                    p.get(*field).cloned().unwrap_or(Value::Null),
+                );
+            }
+            Value::Object(product)
+        })
+        .collect();
+
+    let mut compact = json!({ "produkte": produkte });
+    if doc_type == "rechnung" {
+        compact["nummerRechnung"] = result.get("nummerRechnung").cloned().unwrap_or(Value::Null);
+    }
+    compact
+}
+
+/// Returns the prompt section with up to two confirmed examples of the same supplier,
+/// or an empty string if there are none.
+pub fn prompt_examples(
+    app: &AppHandle,
+    supplier: Option<&str>,
+    doc_type: &str,
+    path: &str,
+) -> String {
+    let key = match supplier.map(supplier_key).filter(|k| !k.is_empty()) {
+        Some(k) => k,
+        None => return String::new(),
+    };
+    let examples = match load_examples(app) {
+        Ok(mut all) => all.remove(&key).unwrap_or_default(),
+        Err(_) => return String::new(),
+    };
+
+    let current_file = file_name_of(path);
+    let selected: Vec<&FewShotExample> = examples
+        .iter()
+        .rev()
+        .filter(|e| e.doc_type == doc_type && e.file_name != current_file)
+        .take(EXAMPLES_IN_PROMPT)
+        .collect();
+
+    if selected.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "CONFIRMED EXAMPLES FROM THE SAME SUPPLIER (use them only to understand the column layout; never copy their values):",
+    );
+    for (i, example) in selected.iter().enumerate() {
+        section.push_str(&format!(
+            "\n\nExample {} - document excerpt:\n{}\nExample {} - correct JSON:\n{}",
+            i + 1,
+            example.excerpt,
+            i + 1,
+            example.result
+        ));
+    }
+    section
+}
+
+#[command]
+pub async fn confirm_extraction(
+    app: AppHandle,
+    path: String,
+    doc_type: String,
+    supplier: String,
+    result: Value,
+) -> Result<(), String> {
+    let key = supplier_key(&supplier);
+    if key.is_empty() {
+        return Ok(());
+    }
+
+    let text = match DocumentKind::from_path(&path) {
+        Some(DocumentKind::Pdf) => run_sidecar(&app, &path, false).await?,
+        Some(DocumentKind::Image) => images::run_local_ocr(&app, &path).await?,
+        _ => return Ok(()),
+    };
+    if text.trim().len() < 50 {
+        return Ok(());
+    }
+
+    let compact = compact_result(&result, &doc_type);
+    let first_product = compact["produkte"][0]["produkt"].as_str();
+    if first_product.is_none() {
+        return Ok(());
+    }
+
+    let example = FewShotExample {
+        excerpt: build_excerpt(&text, first_product),
+        file_name: file_name_of(&path),
+        doc_type,
+        result: compact,
+        confirmed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+
+    let mut all = load_examples(&app)?;
+    let entries = all.entry(key).or_default();
+    entries.retain(|e| e.file_name != example.file_name || e.doc_type != example.doc_type);
+    entries.push(example);
+    if entries.len() > MAX_STORED_PER_SUPPLIER {
+        let excess = entries.len() - MAX_STORED_PER_SUPPLIER;
+        entries.drain(..excess);
+    }
+
+    let store = workspace::store(&app, "examples.json")?;
+    store.set("examples", json!(all));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
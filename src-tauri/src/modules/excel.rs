@@ -1,6 +1,11 @@
-use crate::modules::utils::{adjust_formula, close_excel_if_open, parse_date, token_similarity};
-
-use chrono::NaiveDate;
+use crate::modules::excel_columns::{ColumnLayout, ExcelColumns};
+use crate::modules::policy;
+use crate::modules::settings::{self, Settings, SheetRules};
+use crate::modules::utils::{
+    adjust_formula, close_excel_if_open, column_index, column_letters, parse_date, token_similarity,
+};
+
+use chrono::{Datelike, NaiveDate};
 use serde_json::json;
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
@@ -10,6 +15,7 @@ use std::path::PathBuf;
 use tauri::command;
 use tauri::Emitter;
 use tauri_plugin_dialog::DialogExt;
+use umya_spreadsheet::{Spreadsheet, Worksheet};
 use zip::ZipArchive;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
@@ -20,6 +26,8 @@ pub struct ExportRow {
     kunde: Option<String>,
     lieferant: Option<String>,
     produkt: Option<String>,
+    #[serde(default)]
+    produkt_original: Option<String>,
     menge: Option<f64>,
     waehrung: Option<String>,
     preis: Option<f64>,
@@ -28,6 +36,28 @@ pub struct ExportRow {
     gelieferte_menge: Option<f64>,
     anmerkungen: Option<String>,
 }
+
+fn append_original_to_notes(row: &mut ExportRow) {
+    let original = match row.produkt_original.as_deref().map(str::trim) {
+        Some(o) if !o.is_empty() => o,
+        _ => return,
+    };
+    if row
+        .produkt
+        .as_deref()
+        .is_some_and(|p| p.trim().eq_ignore_ascii_case(original))
+    {
+        return;
+    }
+
+    let note = format!("Orig.: {}", original);
+    row.anmerkungen = match row.anmerkungen.take() {
+        Some(n) if n.contains(&note) => Some(n),
+        Some(n) if !n.trim().is_empty() => Some(format!("{}; {}", n.trim(), note)),
+        _ => Some(note),
+    };
+}
+
 struct SheetRow {
     row_idx: u32,
     supplier: String,
@@ -52,6 +82,18 @@ pub async fn check_excel_access(path: String) -> Result<bool, String> {
     }
 }
 
+/// The sheet names of a workbook, in order, for choosing the export target.
+#[command]
+pub async fn get_excel_sheets(path: String) -> Result<Vec<String>, String> {
+    let book = umya_spreadsheet::reader::xlsx::lazy_read(std::path::Path::new(&path))
+        .map_err(|e| format!("Errore di lettura: {}", e))?;
+    Ok(book
+        .get_sheet_collection_no_check()
+        .iter()
+        .map(|s| s.get_name().to_string())
+        .collect())
+}
+
 fn is_1904_system(path: &std::path::Path) -> bool {
     if let Ok(file) = std::fs::File::open(path) {
         if let Ok(mut archive) = ZipArchive::new(file) {
@@ -67,120 +109,173 @@ fn is_1904_system(path: &std::path::Path) -> bool {
     false
 }
 
-#[command]
-pub async fn export_to_excel(
-    app: tauri::AppHandle,
-    mut data: Vec<ExportRow>,
-    file_path: Option<String>,
-) -> Result<String, String> {
-    if data.is_empty() {
-        return Err("Nessun dato selezionato.".to_string());
-    }
-
-    data.sort_by(|a, b| {
-        let a_is_invoice = a.gelieferte_menge.is_some();
-        let b_is_invoice = b.gelieferte_menge.is_some();
-        a_is_invoice.cmp(&b_is_invoice)
-    });
-
-    let path_buf = if let Some(p) = file_path {
-        PathBuf::from(p)
-    } else {
-        let file_path_opt = app
-            .dialog()
-            .file()
-            .add_filter("Excel", &["xlsx", "xlsm"])
-            .blocking_pick_file();
-
-        match file_path_opt {
-            Some(p) => p.into_path().map_err(|e| e.to_string())?,
-            None => return Ok("Interruzione da parte dell'utente".to_string()),
+/// Whether a number format shows a date: it has day or year codes outside of quoted
+/// text, escaped characters and `[...]` sections.
+fn is_date_format(code: &str) -> bool {
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    let mut escaped = false;
+    for c in code.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if !in_quotes => escaped = true,
+            '[' if !in_quotes => in_brackets = true,
+            ']' if !in_quotes => in_brackets = false,
+            'd' | 'D' | 'y' | 'Y' if !in_quotes && !in_brackets => return true,
+            _ => {}
         }
-    };
-    if let Some(path_str) = path_buf.to_str() {
-        close_excel_if_open(path_str);
-        std::thread::sleep(std::time::Duration::from_millis(500));
     }
+    false
+}
 
-    let path = path_buf.as_path();
-
-    if let Err(_) = OpenOptions::new().write(true).open(path) {
-        return Err("Accesso negato! Il file è aperto.".to_string());
+/// umya-spreadsheet saves every workbook in the 1900 date system, so the dates of a
+/// 1904 workbook are moved to it on every sheet, also on those the export neither
+/// reads nor writes: cells with a date format, and the date columns of sheets whose
+/// header is recognised. Formulas are left alone, Excel computes them again.
+fn convert_1904_dates(book: &mut Spreadsheet, columns: &ExcelColumns) {
+    for sheet in book.get_sheet_collection_mut().iter_mut() {
+        let date_columns = ColumnLayout::resolve(sheet, columns)
+            .ok()
+            .map(|layout| (layout.header_row, layout.date_columns()));
+
+        for cell in sheet.get_cell_collection_mut() {
+            if cell.is_formula() {
+                continue;
+            }
+            let value = match cell.get_value().parse::<f64>() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let col = *cell.get_coordinate().get_col_num();
+            let row = *cell.get_coordinate().get_row_num();
+
+            let in_date_column = date_columns
+                .as_ref()
+                .is_some_and(|(header_row, cols)| row > *header_row && cols.contains(&col));
+            let date_format = cell
+                .get_style()
+                .get_number_format()
+                .is_some_and(|f| is_date_format(f.get_format_code()));
+
+            if (in_date_column && value > 30000.0) || (date_format && value > 0.0) {
+                cell.set_value_number(value + 1462.0);
+            }
+        }
     }
+}
 
-    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-    let backup_path = path.with_file_name(format!("{}.bak", file_name));
+fn date_to_excel(d: &str) -> Option<f64> {
+    parse_date(d).map(|parsed| {
+        let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap();
+        (parsed - epoch).num_days() as f64
+    })
+}
 
-    if let Err(e) = fs::copy(path, &backup_path) {
-        println!("Avviso: impossibile creare il backup: {}", e);
-    }
+/// A value the export writes into a cell.
+enum CellValue {
+    Text(String),
+    Number(f64),
+}
 
-    let is_1904 = is_1904_system(path);
-    if is_1904 {
-        println!("INFO: Datei verwendet 1904-Datumssystem. Daten werden migriert.");
+fn date_value(value: &str) -> CellValue {
+    match date_to_excel(value) {
+        Some(excel_date) => CellValue::Number(excel_date),
+        None => CellValue::Text(value.to_string()),
     }
+}
 
-    let date_to_excel = |d: &str| -> Option<f64> {
-        parse_date(d).map(|parsed| {
-            let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap();
-            (parsed - epoch).num_days() as f64
-        })
+fn write_cell(sheet: &mut Worksheet, col: u32, row: u32, value: &CellValue) {
+    match value {
+        CellValue::Text(v) => sheet.get_cell_mut((col, row)).set_value(v),
+        CellValue::Number(v) => sheet.get_cell_mut((col, row)).set_value_number(*v),
     };
+}
 
-    let mut book = umya_spreadsheet::reader::xlsx::read(path)
-        .map_err(|e| format!("Errore di lettura: {}", e))?;
+fn order_key(row: &ExportRow) -> String {
+    row.nummer_auftrag
+        .clone()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
 
-    let sheet = book
-        .get_sheet_mut(&0)
-        .ok_or("Nessun foglio di lavoro trovato.".to_string())?;
+struct ExcelCandidate {
+    row_idx: u32,
+    product_norm: String,
+    qty: f64,
+    has_invoice: bool,
+}
 
-    let highest_row = sheet.get_highest_row();
+struct PlannedUpdate {
+    /// Position of the row in the exported data.
+    source: usize,
+    row_idx: u32,
+    row: ExportRow,
+}
 
-    let mut header_row = 1;
-    let search_limit = if highest_row < 100 { highest_row } else { 100 };
-    for r in 1..=search_limit {
-        let cell_val = sheet.get_value((4, r));
-        if cell_val.trim().eq_ignore_ascii_case("Casa Estera") {
-            header_row = r;
-            break;
-        }
-    }
-    let start_data_row = header_row + 1;
+/// A new row of the sheet with the exported rows it was built from: an order and the
+/// invoice that completes it.
+struct PendingRow {
+    sources: Vec<usize>,
+    row: ExportRow,
+    /// Row before which it is inserted, numbered as before the export.
+    position: u32,
+    supplier_block: bool,
+}
 
-    if is_1904 && highest_row >= start_data_row {
-        for r in start_data_row..=highest_row {
-            for col in [1, 10, 17] {
-                let cell = sheet.get_cell_mut((col, r));
-                let val_str = cell.get_value().to_string();
-                if let Ok(val_num) = val_str.parse::<f64>() {
-                    if val_num > 30000.0 {
-                        cell.set_value_number(val_num + 1462.0);
-                    }
-                }
-            }
-        }
-    }
+/// A worksheet the export reads candidates from or writes to.
+struct SheetContext {
+    index: usize,
+    name: String,
+    layout: ColumnLayout,
+    highest_row: u32,
+    order_map: HashMap<String, Vec<ExcelCandidate>>,
+    existing_rows: Vec<SheetRow>,
+    updates: Vec<PlannedUpdate>,
+    rows_to_insert: Vec<PendingRow>,
+}
+
+fn read_sheet(
+    sheet: &Worksheet,
+    index: usize,
+    columns: &ExcelColumns,
+    original_column: Option<u32>,
+) -> Result<SheetContext, String> {
+    let highest_row = sheet.get_highest_row();
 
-    struct ExcelCandidate {
-        row_idx: u32,
-        product_norm: String,
-        qty: f64,
-        has_invoice: bool,
+    let layout = ColumnLayout::resolve(sheet, columns)?;
+    if let Some(col) = original_column.filter(|c| layout.data_columns().contains(c)) {
+        return Err(format!(
+            "La colonna {} è già usata dall'esportazione e non può contenere il nome originale.",
+            column_letters(col)
+        ));
     }
+    let start_data_row = layout.header_row + 1;
 
     let mut order_map: HashMap<String, Vec<ExcelCandidate>> = HashMap::new();
-    let mut existing_rows_for_sorting: Vec<SheetRow> = Vec::with_capacity(highest_row as usize);
+    let mut existing_rows: Vec<SheetRow> = Vec::with_capacity(highest_row as usize);
 
     if highest_row >= start_data_row {
         for r in start_data_row..=highest_row {
-            let s_val = sheet.get_value((4, r)).to_lowercase();
-            let d_val = sheet.get_value((1, r));
-            let o_val = sheet.get_value((2, r)).to_string().trim().to_lowercase();
-            let p_name = sheet.get_value((5, r)).to_string();
+            let s_val = sheet.get_value((layout.supplier, r)).to_lowercase();
+            let d_val = sheet.get_value((layout.order_date, r));
+            let o_val = sheet
+                .get_value((layout.order_number, r))
+                .to_string()
+                .trim()
+                .to_lowercase();
+            let p_name = sheet.get_value((layout.product, r)).to_string();
 
-            let qty_val = sheet.get_value((6, r)).parse::<f64>().unwrap_or(0.0);
+            let qty_val = sheet
+                .get_value((layout.quantity, r))
+                .parse::<f64>()
+                .unwrap_or(0.0);
 
-            existing_rows_for_sorting.push(SheetRow {
+            existing_rows.push(SheetRow {
                 row_idx: r,
                 supplier: s_val,
                 order_number: o_val.clone(),
@@ -188,7 +283,7 @@ pub async fn export_to_excel(
             });
 
             if !o_val.is_empty() {
-                let inv_qty_str = sheet.get_value((12, r));
+                let inv_qty_str = sheet.get_value((layout.delivered_quantity, r));
                 let has_invoice = !inv_qty_str.trim().is_empty();
 
                 order_map.entry(o_val).or_default().push(ExcelCandidate {
@@ -201,408 +296,863 @@ pub async fn export_to_excel(
         }
     }
 
-    let mut rows_to_insert: Vec<ExportRow> = Vec::new();
-    let mut updated_count = 0;
-
-    let total_ops = data.len();
-    let mut current_progress = 0;
+    Ok(SheetContext {
+        index,
+        name: sheet.get_name().to_string(),
+        layout,
+        highest_row,
+        order_map,
+        existing_rows,
+        updates: Vec::new(),
+        rows_to_insert: Vec::new(),
+    })
+}
 
-    for row in data {
-        let order_nr = row
-            .nummer_auftrag
-            .clone()
-            .unwrap_or_default()
-            .trim()
-            .to_lowercase();
-        let prod_name = row.produkt.clone().unwrap_or_default();
-
-        let mut best_match_excel: Option<u32> = None;
-        let mut best_score_excel: f64 = -1.0;
-        let mut best_cand_idx: Option<usize> = None;
-
-        if let (Some(_inv_price), Some(inv_qty)) = (row.preis, row.gelieferte_menge) {
-            if let Some(candidates) = order_map.get(&order_nr) {
-                for (i, cand) in candidates.iter().enumerate() {
-                    if cand.has_invoice {
-                        continue;
-                    }
+/// The sheet a row belongs to: the one named in the call, otherwise the one chosen by
+/// the sheet rules of the settings.
+fn target_sheet(
+    rules: &SheetRules,
+    requested: Option<&str>,
+    names: &[String],
+    row: &ExportRow,
+) -> Result<usize, String> {
+    let find = |name: &str| {
+        names
+            .iter()
+            .position(|n| n.trim().eq_ignore_ascii_case(name.trim()))
+            .ok_or(format!(
+                "Il foglio \"{}\" non esiste nel file Excel.",
+                name.trim()
+            ))
+    };
+    if let Some(name) = requested.filter(|n| !n.trim().is_empty()) {
+        return find(name);
+    }
 
-                    let qty_diff_abs = (cand.qty - inv_qty).abs();
-                    let qty_diff_rel = if cand.qty > 0.0 {
-                        qty_diff_abs / cand.qty
-                    } else {
-                        1.0
-                    };
+    match rules.mode.as_str() {
+        "name" => find(&rules.name),
+        "orderYear" => {
+            let date = row
+                .datum_auftrag
+                .as_deref()
+                .or(row.datum_rechnung.as_deref())
+                .and_then(parse_date)
+                .ok_or(format!(
+                    "Manca la data dell'ordine {}: impossibile scegliere il foglio dell'anno.",
+                    row.nummer_auftrag.as_deref().unwrap_or("senza numero")
+                ))?;
+            find(
+                &rules
+                    .year_pattern
+                    .replace("{year}", &date.year().to_string()),
+            )
+        }
+        _ if names.is_empty() => Err("Nessun foglio di lavoro trovato.".to_string()),
+        _ => Ok(0),
+    }
+}
 
-                    if qty_diff_rel > 0.5 {
-                        continue;
-                    }
+/// The best row of the sheet for `row`, with its score. Invoices go to an order row
+/// without invoice and a similar quantity, order rows to the most similar product.
+fn best_candidate(row: &ExportRow, candidates: &[ExcelCandidate]) -> Option<(usize, f64)> {
+    let prod_name = row.produkt.clone().unwrap_or_default();
+    let mut best: Option<(usize, f64)> = None;
+
+    if let (Some(_inv_price), Some(inv_qty)) = (row.preis, row.gelieferte_menge) {
+        let mut best_score = -1.0;
+        for (i, cand) in candidates.iter().enumerate() {
+            if cand.has_invoice {
+                continue;
+            }
 
-                    let name_sim = token_similarity(&prod_name, &cand.product_norm);
-                    let qty_score = (1.0 - qty_diff_rel).max(0.0);
-                    let total_score = (qty_score * 10.0) + (name_sim * 5.0);
+            let qty_diff_abs = (cand.qty - inv_qty).abs();
+            let qty_diff_rel = if cand.qty > 0.0 {
+                qty_diff_abs / cand.qty
+            } else {
+                1.0
+            };
 
-                    if total_score > best_score_excel {
-                        best_score_excel = total_score;
-                        best_match_excel = Some(cand.row_idx);
-                        best_cand_idx = Some(i);
-                    }
-                }
-            }
-        } else {
-            if let Some(candidates) = order_map.get(&order_nr) {
-                let mut best_score_order = 0.5;
-                for (i, cand) in candidates.iter().enumerate() {
-                    let name_sim = token_similarity(&prod_name, &cand.product_norm);
-                    if name_sim > best_score_order {
-                        best_score_order = name_sim;
-                        best_match_excel = Some(cand.row_idx);
-                        best_cand_idx = Some(i);
-                    }
-                }
+            if qty_diff_rel > 0.5 {
+                continue;
             }
-        }
 
-        if let Some(idx) = best_cand_idx {
-            if let Some(candidates) = order_map.get_mut(&order_nr) {
-                candidates[idx].has_invoice = true;
-            }
-        }
+            let name_sim = token_similarity(&prod_name, &cand.product_norm);
+            let qty_score = (1.0 - qty_diff_rel).max(0.0);
+            let total_score = (qty_score * 10.0) + (name_sim * 5.0);
 
-        if let Some(row_idx) = best_match_excel {
-            if let Some(v) = row.menge {
-                sheet.get_cell_mut((6, row_idx)).set_value_number(v);
-            }
-            if let Some(v) = &row.waehrung {
-                sheet.get_cell_mut((7, row_idx)).set_value(v);
-            }
-            if let Some(v) = row.preis {
-                sheet.get_cell_mut((8, row_idx)).set_value_number(v);
-            }
-            if let Some(v) = &row.datum_rechnung {
-                if let Some(excel_date) = date_to_excel(v) {
-                    sheet
-                        .get_cell_mut((10, row_idx))
-                        .set_value_number(excel_date);
-                } else {
-                    sheet.get_cell_mut((10, row_idx)).set_value(v);
-                }
+            if total_score > best_score {
+                best_score = total_score;
+                best = Some((i, total_score));
             }
-            if let Some(v) = &row.nummer_rechnung {
-                sheet.get_cell_mut((11, row_idx)).set_value(v);
-            }
-            if let Some(v) = row.gelieferte_menge {
-                sheet.get_cell_mut((12, row_idx)).set_value_number(v);
+        }
+    } else {
+        let mut best_score = 0.5;
+        for (i, cand) in candidates.iter().enumerate() {
+            let name_sim = token_similarity(&prod_name, &cand.product_norm);
+            if name_sim > best_score {
+                best_score = name_sim;
+                best = Some((i, name_sim));
             }
+        }
+    }
+    best
+}
 
-            if let Some(v) = &row.anmerkungen {
-                sheet.get_cell_mut((18, row_idx)).set_value(v);
-            }
+/// An order waiting for insertion that the invoice `row` completes.
+fn best_pending(row: &ExportRow, pending_rows: &[PendingRow]) -> Option<(usize, f64)> {
+    let inv_qty = match (row.preis, row.gelieferte_menge) {
+        (Some(_), Some(q)) => q,
+        _ => return None,
+    };
+    let order_nr = order_key(row);
+    let prod_name = row.produkt.clone().unwrap_or_default();
+    let mut best: Option<(usize, f64)> = None;
+    let mut best_score_pending = -1.0;
+
+    for (idx, pending) in pending_rows.iter().map(|p| &p.row).enumerate() {
+        if pending.gelieferte_menge.is_some() || order_key(pending) != order_nr {
+            continue;
+        }
 
-            updated_count += 1;
+        let pending_qty = pending.menge.unwrap_or(0.0);
+        let qty_diff_abs = (pending_qty - inv_qty).abs();
+        let qty_diff_rel = if pending_qty > 0.0 {
+            qty_diff_abs / pending_qty
         } else {
-            let mut found_in_pending = false;
-
-            if let (Some(_inv_price), Some(inv_qty)) = (row.preis, row.gelieferte_menge) {
-                let mut best_idx_pending = None;
-                let mut best_score_pending = -1.0;
+            1.0
+        };
 
-                for (idx, pending) in rows_to_insert.iter().enumerate() {
-                    if pending.gelieferte_menge.is_some() {
-                        continue;
-                    }
+        if qty_diff_rel > 0.5 {
+            continue;
+        }
 
-                    let pending_nr = pending
-                        .nummer_auftrag
-                        .as_deref()
-                        .unwrap_or("")
-                        .trim()
-                        .to_lowercase();
-                    if pending_nr != order_nr {
-                        continue;
-                    }
+        let pending_name = pending.produkt.as_deref().unwrap_or("");
+        let name_sim = token_similarity(&prod_name, pending_name);
 
-                    let pending_qty = pending.menge.unwrap_or(0.0);
-                    let qty_diff_abs = (pending_qty - inv_qty).abs();
-                    let qty_diff_rel = if pending_qty > 0.0 {
-                        qty_diff_abs / pending_qty
-                    } else {
-                        1.0
-                    };
+        let qty_score = (1.0 - qty_diff_rel).max(0.0);
+        let total_score = (qty_score * 10.0) + (name_sim * 5.0);
 
-                    if qty_diff_rel > 0.5 {
-                        continue;
-                    }
+        if total_score > best_score_pending {
+            best_score_pending = total_score;
+            best = Some((idx, total_score));
+        }
+    }
+    best
+}
 
-                    let pending_name = pending.produkt.as_deref().unwrap_or("");
-                    let name_sim = token_similarity(&prod_name, pending_name);
+fn merge_invoice(target: &mut ExportRow, row: &ExportRow) {
+    target.datum_rechnung = row.datum_rechnung.clone();
+    target.nummer_rechnung = row.nummer_rechnung.clone();
+    target.gelieferte_menge = row.gelieferte_menge;
 
-                    let qty_score = (1.0 - qty_diff_rel).max(0.0);
-                    let total_score = (qty_score * 10.0) + (name_sim * 5.0);
+    if let Some(inv_p) = row.preis {
+        target.preis = Some(inv_p);
+    }
 
-                    if total_score > best_score_pending {
-                        best_score_pending = total_score;
-                        best_idx_pending = Some(idx);
-                    }
-                }
+    if let Some(note) = &row.anmerkungen {
+        if target.anmerkungen.is_none() {
+            target.anmerkungen = Some(note.clone());
+        }
+    }
+    if target.produkt_original.is_none() {
+        target.produkt_original = row.produkt_original.clone();
+    }
+}
 
-                if let Some(idx) = best_idx_pending {
-                    let target = &mut rows_to_insert[idx];
-                    target.datum_rechnung = row.datum_rechnung.clone();
-                    target.nummer_rechnung = row.nummer_rechnung.clone();
-                    target.gelieferte_menge = Some(inv_qty);
+/// The invoice values, which an existing order row and a new row have in common.
+fn invoice_cells(layout: &ColumnLayout, row: &ExportRow) -> Vec<(u32, CellValue)> {
+    let mut cells = Vec::new();
+    if let Some(v) = row.menge {
+        cells.push((layout.quantity, CellValue::Number(v)));
+    }
+    if let Some(v) = &row.waehrung {
+        cells.push((layout.currency, CellValue::Text(v.clone())));
+    }
+    if let Some(v) = row.preis {
+        cells.push((layout.price, CellValue::Number(v)));
+    }
+    if let Some(v) = &row.datum_rechnung {
+        cells.push((layout.invoice_date, date_value(v)));
+    }
+    if let Some(v) = &row.nummer_rechnung {
+        cells.push((layout.invoice_number, CellValue::Text(v.clone())));
+    }
+    if let Some(v) = row.gelieferte_menge {
+        cells.push((layout.delivered_quantity, CellValue::Number(v)));
+    }
+    if let (Some(col), Some(v)) = (layout.notes, &row.anmerkungen) {
+        cells.push((col, CellValue::Text(v.clone())));
+    }
+    cells
+}
 
-                    if let Some(inv_p) = row.preis {
-                        target.preis = Some(inv_p);
-                    }
+/// The cells of an existing row. The original name only fills an empty cell.
+fn update_cells(
+    sheet: &Worksheet,
+    layout: &ColumnLayout,
+    row_idx: u32,
+    row: &ExportRow,
+    original_column: Option<u32>,
+) -> Vec<(u32, CellValue)> {
+    let mut cells = invoice_cells(layout, row);
+    if let (Some(col), Some(v)) = (original_column, &row.produkt_original) {
+        if sheet.get_value((col, row_idx)).trim().is_empty() {
+            cells.push((col, CellValue::Text(v.clone())));
+        }
+    }
+    cells
+}
 
-                    if let Some(note) = &row.anmerkungen {
-                        if target.anmerkungen.is_none() {
-                            target.anmerkungen = Some(note.clone());
-                        }
-                    }
-                    found_in_pending = true;
-                    updated_count += 1;
-                }
-            }
+fn insert_cells(
+    layout: &ColumnLayout,
+    row: &ExportRow,
+    original_column: Option<u32>,
+) -> Vec<(u32, CellValue)> {
+    let mut cells = Vec::new();
+    if let Some(v) = &row.datum_auftrag {
+        cells.push((layout.order_date, date_value(v)));
+    }
+    let texts = [
+        (layout.order_number, &row.nummer_auftrag),
+        (layout.customer, &row.kunde),
+        (layout.supplier, &row.lieferant),
+        (layout.product, &row.produkt),
+    ];
+    for (col, value) in texts {
+        if let Some(v) = value {
+            cells.push((col, CellValue::Text(v.clone())));
+        }
+    }
+    cells.extend(invoice_cells(layout, row));
+    if let (Some(col), Some(v)) = (original_column, &row.produkt_original) {
+        cells.push((col, CellValue::Text(v.clone())));
+    }
+    cells
+}
 
-            if !found_in_pending {
-                rows_to_insert.push(row);
-            }
+fn sort_for_insertion(rows: &mut [PendingRow]) {
+    rows.sort_by(|a, b| {
+        let (a, b) = (&a.row, &b.row);
+        let res_l = a
+            .lieferant
+            .as_deref()
+            .unwrap_or("")
+            .to_lowercase()
+            .cmp(&b.lieferant.as_deref().unwrap_or("").to_lowercase());
+        if res_l != std::cmp::Ordering::Equal {
+            return res_l;
         }
 
-        current_progress += 1;
-        if current_progress % 10 == 0 {
-            let _ = app.emit(
-                "excel-progress",
-                json!({ "current": current_progress, "total": total_ops }),
-            );
+        let date_a = parse_date(a.datum_auftrag.as_deref().unwrap_or_default());
+        let date_b = parse_date(b.datum_auftrag.as_deref().unwrap_or_default());
+        let res_d = date_a.cmp(&date_b);
+        if res_d != std::cmp::Ordering::Equal {
+            return res_d;
         }
+
+        a.nummer_auftrag
+            .as_deref()
+            .unwrap_or("")
+            .to_lowercase()
+            .cmp(&b.nummer_auftrag.as_deref().unwrap_or("").to_lowercase())
+    });
+}
+
+/// The row before which `new_row` goes: inside its supplier block, by order date and
+/// number, or after the last row. The flag tells whether the supplier already has rows.
+fn insert_position(ctx: &SheetContext, new_row: &ExportRow) -> (u32, bool) {
+    let start_data_row = ctx.layout.header_row + 1;
+    let target_supplier = new_row
+        .lieferant
+        .as_deref()
+        .unwrap_or_default()
+        .to_lowercase();
+    let target_order = new_row
+        .nummer_auftrag
+        .as_deref()
+        .unwrap_or_default()
+        .to_lowercase();
+    let target_date = parse_date(new_row.datum_auftrag.as_deref().unwrap_or_default())
+        .unwrap_or(NaiveDate::from_ymd_opt(1900, 1, 1).unwrap());
+
+    let mut insert_at = ctx.highest_row + 1;
+    if insert_at < start_data_row {
+        insert_at = start_data_row;
     }
 
-    if !rows_to_insert.is_empty() {
-        rows_to_insert.sort_by(|a, b| {
-            let res_l = a
-                .lieferant
-                .as_deref()
-                .unwrap_or("")
-                .to_lowercase()
-                .cmp(&b.lieferant.as_deref().unwrap_or("").to_lowercase());
-            if res_l != std::cmp::Ordering::Equal {
-                return res_l;
+    let mut found_supplier_block = false;
+    for ex in &ctx.existing_rows {
+        if ex.supplier == target_supplier {
+            found_supplier_block = true;
+            if ex.date > target_date || (ex.date == target_date && ex.order_number > target_order) {
+                insert_at = ex.row_idx;
+                break;
             }
+        } else if found_supplier_block || ex.supplier > target_supplier {
+            insert_at = ex.row_idx;
+            break;
+        }
+    }
+    (insert_at, found_supplier_block)
+}
 
-            let date_a = parse_date(a.datum_auftrag.as_deref().unwrap_or_default());
-            let date_b = parse_date(b.datum_auftrag.as_deref().unwrap_or_default());
-            let res_d = date_a.cmp(&date_b);
-            if res_d != std::cmp::Ordering::Equal {
-                return res_d;
-            }
+fn write_insertions(sheet: &mut Worksheet, ctx: &SheetContext, original_column: Option<u32>) {
+    let layout = &ctx.layout;
+    let start_data_row = layout.header_row + 1;
 
-            a.nummer_auftrag
-                .as_deref()
-                .unwrap_or("")
-                .to_lowercase()
-                .cmp(&b.nummer_auftrag.as_deref().unwrap_or("").to_lowercase())
-        });
+    let mut insertions: BTreeMap<u32, Vec<&ExportRow>> = BTreeMap::new();
+    for pending in &ctx.rows_to_insert {
+        insertions
+            .entry(pending.position)
+            .or_default()
+            .push(&pending.row);
+    }
 
-        let mut insertions: BTreeMap<u32, Vec<ExportRow>> = BTreeMap::new();
+    for (row_idx, batch) in insertions.iter().rev() {
+        let start_row = *row_idx;
+        let count = batch.len() as u32;
 
-        for new_row in rows_to_insert.iter() {
-            let target_supplier = new_row
-                .lieferant
-                .as_deref()
-                .unwrap_or_default()
-                .to_lowercase();
-            let target_order = new_row
-                .nummer_auftrag
-                .as_deref()
-                .unwrap_or_default()
-                .to_lowercase();
-            let target_date = parse_date(new_row.datum_auftrag.as_deref().unwrap_or_default())
-                .unwrap_or(NaiveDate::from_ymd_opt(1900, 1, 1).unwrap());
+        sheet.insert_new_row(&start_row, &count);
 
-            let mut insert_at = sheet.get_highest_row() + 1;
-            if insert_at < start_data_row {
-                insert_at = start_data_row;
-            }
+        let current_highest = sheet.get_highest_row();
 
-            let mut found_supplier_block = false;
-            for ex in &existing_rows_for_sorting {
-                if ex.supplier == target_supplier {
-                    found_supplier_block = true;
-                    if ex.date > target_date {
-                        insert_at = ex.row_idx;
-                        break;
-                    } else if ex.date == target_date && ex.order_number > target_order {
-                        insert_at = ex.row_idx;
-                        break;
-                    }
-                } else if found_supplier_block {
-                    insert_at = ex.row_idx;
+        let mut template_row_idx = if start_row > start_data_row {
+            start_row - 1
+        } else {
+            start_row + count
+        };
+
+        let mut found_ref = false;
+        if start_row > start_data_row {
+            for r in (start_data_row..start_row).rev() {
+                if !sheet.get_value((layout.order_number, r)).trim().is_empty() {
+                    template_row_idx = r;
+                    found_ref = true;
                     break;
-                } else if ex.supplier > target_supplier {
-                    insert_at = ex.row_idx;
+                }
+            }
+        }
+        if !found_ref {
+            for r in (start_row + count)..=current_highest {
+                if !sheet.get_value((layout.order_number, r)).trim().is_empty() {
+                    template_row_idx = r;
                     break;
                 }
             }
-
-            insertions
-                .entry(insert_at)
-                .or_default()
-                .push((*new_row).clone());
         }
 
-        for (row_idx, batch) in insertions.iter().rev() {
-            let start_row = *row_idx;
-            let count = batch.len() as u32;
-
-            sheet.insert_new_row(&start_row, &count);
+        let (template_height, template_custom_height) =
+            match sheet.get_row_dimension(&template_row_idx) {
+                Some(row_dim) => (*row_dim.get_height(), *row_dim.get_custom_height()),
+                None => (0.0, false),
+            };
 
-            let current_highest = sheet.get_highest_row();
+        let template_formula = match layout
+            .formula
+            .and_then(|col| sheet.get_cell((col, template_row_idx)))
+        {
+            Some(c) => c.get_formula().to_string(),
+            None => String::new(),
+        };
+        let formula_source_row = template_row_idx;
 
-            let mut template_row_idx = if start_row > start_data_row {
-                start_row - 1
-            } else {
-                start_row + count
-            };
+        let last_column = layout.last_column().max(original_column.unwrap_or(0));
+        let mut column_styles = Vec::with_capacity(last_column as usize);
+        for col in 1..=last_column {
+            let mut style_found = None;
 
-            let mut found_ref = false;
             if start_row > start_data_row {
                 for r in (start_data_row..start_row).rev() {
-                    if !sheet.get_value((2, r)).trim().is_empty() {
-                        template_row_idx = r;
-                        found_ref = true;
+                    if !sheet.get_value((col, r)).trim().is_empty() {
+                        style_found = Some(sheet.get_style((col, r)).clone());
                         break;
                     }
                 }
             }
-            if !found_ref {
+
+            if style_found.is_none() {
                 for r in (start_row + count)..=current_highest {
-                    if !sheet.get_value((2, r)).trim().is_empty() {
-                        template_row_idx = r;
+                    if !sheet.get_value((col, r)).trim().is_empty() {
+                        style_found = Some(sheet.get_style((col, r)).clone());
                         break;
                     }
                 }
             }
 
-            let (template_height, template_custom_height) =
-                match sheet.get_row_dimension(&template_row_idx) {
-                    Some(row_dim) => (*row_dim.get_height(), *row_dim.get_custom_height()),
-                    None => (0.0, false),
-                };
+            if style_found.is_none() {
+                style_found = Some(sheet.get_style((col, template_row_idx)).clone());
+            }
 
-            let template_formula = match sheet.get_cell((13, template_row_idx)) {
-                Some(c) => c.get_formula().to_string(),
-                None => String::new(),
-            };
-            let formula_source_row = template_row_idx;
-
-            let mut column_styles = Vec::with_capacity(18);
-            for col in 1..=18 {
-                let mut style_found = None;
-
-                if start_row > start_data_row {
-                    for r in (start_data_row..start_row).rev() {
-                        if !sheet.get_value((col, r)).trim().is_empty() {
-                            style_found = Some(sheet.get_style((col, r)).clone());
-                            break;
-                        }
-                    }
-                }
+            column_styles.push(style_found.unwrap());
+        }
 
-                if style_found.is_none() {
-                    for r in (start_row + count)..=current_highest {
-                        if !sheet.get_value((col, r)).trim().is_empty() {
-                            style_found = Some(sheet.get_style((col, r)).clone());
-                            break;
-                        }
-                    }
+        for (i, row_data) in batch.iter().enumerate() {
+            let r = start_row + i as u32;
+
+            if template_height > 0.0 {
+                let row_dim = sheet.get_row_dimension_mut(&r);
+                row_dim.set_height(template_height);
+                if template_custom_height {
+                    row_dim.set_custom_height(true);
                 }
+            }
 
-                if style_found.is_none() {
-                    style_found = Some(sheet.get_style((col, template_row_idx)).clone());
+            for (col, value) in insert_cells(layout, row_data, original_column) {
+                write_cell(sheet, col, r, &value);
+            }
+
+            for col in 1..=last_column {
+                if let Some(style) = column_styles.get((col - 1) as usize) {
+                    sheet.set_style((col, r), style.clone());
                 }
+            }
 
-                column_styles.push(style_found.unwrap());
+            if let (Some(col), false) = (layout.formula, template_formula.is_empty()) {
+                let new_formula = adjust_formula(&template_formula, formula_source_row, r);
+                sheet.get_cell_mut((col, r)).set_formula(new_formula);
             }
+        }
+    }
+}
 
-            for (i, row_data) in batch.iter().enumerate() {
-                let r = start_row + i as u32;
+/// What the export decided for every sheet, before anything is written.
+struct ExportPlan {
+    contexts: BTreeMap<usize, SheetContext>,
+    original_column: Option<u32>,
+    updated_count: usize,
+}
 
-                if template_height > 0.0 {
-                    let row_dim = sheet.get_row_dimension_mut(&r);
-                    row_dim.set_height(template_height);
-                    if template_custom_height {
-                        row_dim.set_custom_height(true);
-                    }
-                }
+impl ExportPlan {
+    fn inserted_count(&self) -> usize {
+        self.contexts.values().map(|c| c.rows_to_insert.len()).sum()
+    }
+}
 
-                if let Some(v) = &row_data.datum_auftrag {
-                    if let Some(excel_date) = date_to_excel(v) {
-                        sheet.get_cell_mut((1, r)).set_value_number(excel_date);
-                    } else {
-                        sheet.get_cell_mut((1, r)).set_value(v);
-                    }
-                }
-                if let Some(v) = &row_data.nummer_auftrag {
-                    sheet.get_cell_mut((2, r)).set_value(v);
-                }
-                if let Some(v) = &row_data.kunde {
-                    sheet.get_cell_mut((3, r)).set_value(v);
-                }
-                if let Some(v) = &row_data.lieferant {
-                    sheet.get_cell_mut((4, r)).set_value(v);
-                }
-                if let Some(v) = &row_data.produkt {
-                    sheet.get_cell_mut((5, r)).set_value(v);
-                }
-                if let Some(v) = row_data.menge {
-                    sheet.get_cell_mut((6, r)).set_value_number(v);
-                }
-                if let Some(v) = &row_data.waehrung {
-                    sheet.get_cell_mut((7, r)).set_value(v);
-                }
-                if let Some(v) = row_data.preis {
-                    sheet.get_cell_mut((8, r)).set_value_number(v);
-                }
-                if let Some(v) = &row_data.datum_rechnung {
-                    if let Some(excel_date) = date_to_excel(v) {
-                        sheet.get_cell_mut((10, r)).set_value_number(excel_date);
-                    } else {
-                        sheet.get_cell_mut((10, r)).set_value(v);
-                    }
-                }
-                if let Some(v) = &row_data.nummer_rechnung {
-                    sheet.get_cell_mut((11, r)).set_value(v);
-                }
-                if let Some(v) = row_data.gelieferte_menge {
-                    sheet.get_cell_mut((12, r)).set_value_number(v);
-                }
-                if let Some(v) = &row_data.anmerkungen {
-                    sheet.get_cell_mut((18, r)).set_value(v);
+/// Matches every row against the workbook and decides which cells it updates or where
+/// it is inserted. Only the 1904 date conversion touches the book.
+fn plan_export(
+    book: &mut Spreadsheet,
+    settings: &Settings,
+    data: Vec<ExportRow>,
+    requested_sheet: Option<&str>,
+    is_1904: bool,
+    progress: &dyn Fn(usize, usize),
+) -> Result<ExportPlan, String> {
+    let rules = &settings.sheet_rules;
+    let original_column = match &settings.original_name_column {
+        Some(letters) => Some(column_index(letters).ok_or(format!(
+            "Colonna non valida per il nome originale: {}",
+            letters
+        ))?),
+        None => None,
+    };
+
+    if is_1904 {
+        convert_1904_dates(book, &settings.excel_columns);
+    }
+
+    let mut data: Vec<(usize, ExportRow)> = data.into_iter().enumerate().collect();
+    if settings.original_name_in_notes {
+        data.iter_mut()
+            .for_each(|(_, row)| append_original_to_notes(row));
+    }
+
+    data.sort_by(|(_, a), (_, b)| {
+        let a_is_invoice = a.gelieferte_menge.is_some();
+        let b_is_invoice = b.gelieferte_menge.is_some();
+        a_is_invoice.cmp(&b_is_invoice)
+    });
+
+    let names: Vec<String> = book
+        .get_sheet_collection()
+        .iter()
+        .map(|s| s.get_name().to_string())
+        .collect();
+    let targets = data
+        .iter()
+        .map(|(_, row)| target_sheet(rules, requested_sheet, &names, row))
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    // Target sheets must be readable; with the search across sheets, every other sheet
+    // whose header is recognised also supplies candidates, the rest (e.g. an overview)
+    // is skipped.
+    let mut contexts: BTreeMap<usize, SheetContext> = BTreeMap::new();
+    for (index, name) in names.iter().enumerate() {
+        let is_target = targets.contains(&index);
+        if !is_target && !rules.search_all_sheets {
+            continue;
+        }
+        let worksheet = book
+            .get_sheet(&index)
+            .ok_or("Nessun foglio di lavoro trovato.".to_string())?;
+        match read_sheet(worksheet, index, &settings.excel_columns, original_column) {
+            Ok(ctx) => {
+                contexts.insert(index, ctx);
+            }
+            Err(e) if is_target => return Err(format!("Foglio \"{}\": {}", name, e)),
+            Err(_) => {}
+        }
+    }
+
+    let mut updated_count = 0;
+
+    let total_ops = data.len();
+    let mut current_progress = 0;
+
+    for ((source, row), target) in data.into_iter().zip(targets) {
+        let order_nr = order_key(&row);
+        let is_invoice = row.preis.is_some() && row.gelieferte_menge.is_some();
+        // The target sheet comes first, so that it wins a tie.
+        let mut search = vec![target];
+        if is_invoice && rules.search_all_sheets {
+            search.extend(contexts.keys().copied().filter(|i| *i != target));
+        }
+
+        let mut best_excel: Option<(usize, usize, f64)> = None;
+        for index in &search {
+            let candidates = match contexts[index].order_map.get(&order_nr) {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some((i, score)) = best_candidate(&row, candidates) {
+                if best_excel.is_none_or(|(_, _, best)| score > best) {
+                    best_excel = Some((*index, i, score));
                 }
+            }
+        }
 
-                for col in 1..=18 {
-                    if let Some(style) = column_styles.get((col - 1) as usize) {
-                        sheet.set_style((col, r), style.clone());
+        if let Some((index, cand_idx, _)) = best_excel {
+            let ctx = contexts.get_mut(&index).unwrap();
+            let candidates = ctx.order_map.get_mut(&order_nr).unwrap();
+            candidates[cand_idx].has_invoice = true;
+            let row_idx = candidates[cand_idx].row_idx;
+            ctx.updates.push(PlannedUpdate {
+                source,
+                row_idx,
+                row,
+            });
+            updated_count += 1;
+        } else {
+            let mut best_pending_row: Option<(usize, usize, f64)> = None;
+            for index in &search {
+                if let Some((i, score)) = best_pending(&row, &contexts[index].rows_to_insert) {
+                    if best_pending_row.is_none_or(|(_, _, best)| score > best) {
+                        best_pending_row = Some((*index, i, score));
                     }
                 }
+            }
 
-                if !template_formula.is_empty() {
-                    let new_formula = adjust_formula(&template_formula, formula_source_row, r);
-                    sheet.get_cell_mut((13, r)).set_formula(new_formula);
+            match best_pending_row {
+                Some((index, i, _)) => {
+                    let pending = &mut contexts.get_mut(&index).unwrap().rows_to_insert[i];
+                    merge_invoice(&mut pending.row, &row);
+                    pending.sources.push(source);
+                    updated_count += 1;
                 }
+                None => contexts
+                    .get_mut(&target)
+                    .unwrap()
+                    .rows_to_insert
+                    .push(PendingRow {
+                        sources: vec![source],
+                        row,
+                        position: 0,
+                        supplier_block: false,
+                    }),
             }
         }
+
+        current_progress += 1;
+        if current_progress % 10 == 0 {
+            progress(current_progress, total_ops);
+        }
+    }
+
+    for ctx in contexts.values_mut() {
+        let mut pending = std::mem::take(&mut ctx.rows_to_insert);
+        sort_for_insertion(&mut pending);
+        for p in pending.iter_mut() {
+            (p.position, p.supplier_block) = insert_position(ctx, &p.row);
+        }
+        ctx.rows_to_insert = pending;
     }
 
+    Ok(ExportPlan {
+        contexts,
+        original_column,
+        updated_count,
+    })
+}
+
+fn apply_plan(book: &mut Spreadsheet, plan: &ExportPlan) -> Result<(), String> {
+    for ctx in plan.contexts.values() {
+        if ctx.updates.is_empty() && ctx.rows_to_insert.is_empty() {
+            continue;
+        }
+        let worksheet = book
+            .get_sheet_mut(&ctx.index)
+            .ok_or(format!("Il foglio \"{}\" non esiste più.", ctx.name))?;
+        for update in &ctx.updates {
+            let cells = update_cells(
+                worksheet,
+                &ctx.layout,
+                update.row_idx,
+                &update.row,
+                plan.original_column,
+            );
+            for (col, value) in cells {
+                write_cell(worksheet, col, update.row_idx, &value);
+            }
+        }
+        write_insertions(worksheet, ctx, plan.original_column);
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CellChange {
+    pub column: String,
+    pub header: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// What the export does with one row of the data. Row numbers are those of the sheet
+/// after the export, `insert_at` is the row before which a new row goes today.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowPreview {
+    pub index: usize,
+    /// "update" or "insert".
+    pub action: String,
+    pub sheet: String,
+    pub row: u32,
+    pub insert_at: Option<u32>,
+    /// Whether the new row joins the existing rows of its supplier.
+    pub supplier_block: Option<bool>,
+    /// The order row of the data that an invoice completes before both are inserted.
+    pub merged_into: Option<usize>,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPreview {
+    pub rows: Vec<RowPreview>,
+    pub updated: usize,
+    pub inserted: usize,
+}
+
+/// Shows date serials as dates, so that the old and new value read the same way.
+fn display_value(layout: &ColumnLayout, col: u32, value: &str) -> String {
+    if layout.date_columns().contains(&col) {
+        if let Ok(serial) = value.parse::<f64>() {
+            let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap();
+            return (epoch + chrono::Duration::days(serial as i64))
+                .format("%d/%m/%Y")
+                .to_string();
+        }
+    }
+    value.to_string()
+}
+
+fn cell_changes(
+    sheet: &Worksheet,
+    layout: &ColumnLayout,
+    row_idx: Option<u32>,
+    cells: Vec<(u32, CellValue)>,
+) -> Vec<CellChange> {
+    cells
+        .into_iter()
+        .filter_map(|(col, value)| {
+            let old = row_idx
+                .map(|r| display_value(layout, col, sheet.get_value((col, r)).trim()))
+                .unwrap_or_default();
+            let new = match value {
+                CellValue::Text(v) => v,
+                CellValue::Number(v) => display_value(layout, col, &v.to_string()),
+            };
+            (old != new).then(|| CellChange {
+                column: column_letters(col),
+                header: sheet.get_value((col, layout.header_row)).trim().to_string(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+fn describe_plan(book: &Spreadsheet, plan: &ExportPlan) -> ExportPreview {
+    let mut rows = Vec::new();
+    for ctx in plan.contexts.values() {
+        let sheet = match book.get_sheet(&ctx.index) {
+            Some(s) => s,
+            None => continue,
+        };
+        let positions: Vec<u32> = ctx.rows_to_insert.iter().map(|p| p.position).collect();
+
+        for update in &ctx.updates {
+            let cells = update_cells(
+                sheet,
+                &ctx.layout,
+                update.row_idx,
+                &update.row,
+                plan.original_column,
+            );
+            let shift = positions.iter().filter(|p| **p <= update.row_idx).count() as u32;
+            rows.push(RowPreview {
+                index: update.source,
+                action: "update".to_string(),
+                sheet: ctx.name.clone(),
+                row: update.row_idx + shift,
+                insert_at: None,
+                supplier_block: None,
+                merged_into: None,
+                changes: cell_changes(sheet, &ctx.layout, Some(update.row_idx), cells),
+            });
+        }
+
+        // Rows with the same position go in one batch, in the order of the plan.
+        let mut seen: HashMap<u32, u32> = HashMap::new();
+        for pending in &ctx.rows_to_insert {
+            let before = positions.iter().filter(|p| **p < pending.position).count() as u32;
+            let offset = seen.entry(pending.position).or_insert(0);
+            let row = pending.position + before + *offset;
+            *offset += 1;
+
+            let cells = insert_cells(&ctx.layout, &pending.row, plan.original_column);
+            let changes = cell_changes(sheet, &ctx.layout, None, cells);
+            for (i, source) in pending.sources.iter().enumerate() {
+                rows.push(RowPreview {
+                    index: *source,
+                    action: "insert".to_string(),
+                    sheet: ctx.name.clone(),
+                    row,
+                    insert_at: Some(pending.position),
+                    supplier_block: Some(pending.supplier_block),
+                    merged_into: (i > 0).then(|| pending.sources[0]),
+                    changes: changes.clone(),
+                });
+            }
+        }
+    }
+    rows.sort_by_key(|r| r.index);
+
+    ExportPreview {
+        rows,
+        updated: plan.updated_count,
+        inserted: plan.inserted_count(),
+    }
+}
+
+/// Runs the matching of `export_to_excel` on the workbook without saving it.
+#[command]
+pub async fn preview_export(
+    app: tauri::AppHandle,
+    data: Vec<ExportRow>,
+    file_path: Option<String>,
+    sheet: Option<String>,
+) -> Result<ExportPreview, String> {
+    if data.is_empty() {
+        return Err("Nessun dato selezionato.".to_string());
+    }
+
+    let settings = settings::load(&app);
+    let path = match file_path.or(settings.default_excel_path.clone()) {
+        Some(p) if !p.trim().is_empty() => PathBuf::from(p),
+        _ => return Err("Nessun file Excel selezionato.".to_string()),
+    };
+
+    let mut book = umya_spreadsheet::reader::xlsx::read(&path)
+        .map_err(|e| format!("Errore di lettura: {}", e))?;
+    let plan = plan_export(
+        &mut book,
+        &settings,
+        data,
+        sheet.as_deref(),
+        is_1904_system(&path),
+        &|_, _| {},
+    )?;
+    Ok(describe_plan(&book, &plan))
+}
+
+#[command]
+pub async fn export_to_excel(
+    app: tauri::AppHandle,
+    data: Vec<ExportRow>,
+    file_path: Option<String>,
+    sheet: Option<String>,
+) -> Result<String, String> {
+    if data.is_empty() {
+        return Err("Nessun dato selezionato.".to_string());
+    }
+
+    let settings = settings::load(&app);
+
+    let path_buf = if let Some(p) = file_path {
+        PathBuf::from(p)
+    } else {
+        let file_path_opt = app
+            .dialog()
+            .file()
+            .add_filter("Excel", &["xlsx", "xlsm"])
+            .blocking_pick_file();
+
+        match file_path_opt {
+            Some(p) => p.into_path().map_err(|e| e.to_string())?,
+            None => return Ok("Interruzione da parte dell'utente".to_string()),
+        }
+    };
+    policy::check_export_path(&path_buf)?;
+    if let Some(path_str) = path_buf.to_str() {
+        close_excel_if_open(path_str);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+
+    let path = path_buf.as_path();
+
+    if OpenOptions::new().write(true).open(path).is_err() {
+        return Err("Accesso negato! Il file è aperto.".to_string());
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let backup_path = path.with_file_name(format!("{}.bak", file_name));
+
+    if let Err(e) = fs::copy(path, &backup_path) {
+        println!("Avviso: impossibile creare il backup: {}", e);
+    }
+
+    let is_1904 = is_1904_system(path);
+    if is_1904 {
+        println!("INFO: Datei verwendet 1904-Datumssystem. Daten werden migriert.");
+    }
+
+    let mut book = umya_spreadsheet::reader::xlsx::read(path)
+        .map_err(|e| format!("Errore di lettura: {}", e))?;
+
+    let total_ops = data.len();
+    let plan = plan_export(
+        &mut book,
+        &settings,
+        data,
+        sheet.as_deref(),
+        is_1904,
+        &|current, total| {
+            let _ = app.emit(
+                "excel-progress",
+                json!({ "current": current, "total": total }),
+            );
+        },
+    )?;
+    apply_plan(&mut book, &plan)?;
+
     let _ = app.emit(
         "excel-progress",
         json!({ "current": total_ops, "total": total_ops }),
     );
 
-    let _ = umya_spreadsheet::writer::xlsx::write(&book, path)
+    umya_spreadsheet::writer::xlsx::write(&book, path)
         .map_err(|e| format!("Errore di memoria: {}", e))?;
 
     if backup_path.exists() {
@@ -611,9 +1161,213 @@ pub async fn export_to_excel(
         }
     }
 
-    let inserted_count = rows_to_insert.len();
     Ok(format!(
         "Finito: {} aggiornati, {} nuovi inseriti.",
-        updated_count, inserted_count
+        plan.updated_count,
+        plan.inserted_count()
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use umya_spreadsheet::NumberingFormat;
+
+    const HEADERS: [&str; 12] = [
+        "Data ordine",
+        "Numero ordine",
+        "Cliente",
+        "Casa Estera",
+        "Prodotto",
+        "Quantità",
+        "Valuta",
+        "Prezzo",
+        "",
+        "Data fattura",
+        "Numero fattura",
+        "Quantità consegnata",
+    ];
+
+    /// A sheet with the export header in row 1 and one order row per
+    /// `(order date serial, order number, supplier, product)`.
+    fn order_sheet(book: &mut Spreadsheet, name: &str, rows: &[(f64, &str, &str, &str)]) {
+        let sheet = book.new_sheet(name).unwrap();
+        for (i, header) in HEADERS.iter().enumerate() {
+            sheet.get_cell_mut((i as u32 + 1, 1)).set_value(*header);
+        }
+        for (i, (date, number, supplier, product)) in rows.iter().enumerate() {
+            let r = i as u32 + 2;
+            sheet.get_cell_mut((1, r)).set_value_number(*date);
+            sheet.get_cell_mut((2, r)).set_value(*number);
+            sheet.get_cell_mut((3, r)).set_value("CLIENTE");
+            sheet.get_cell_mut((4, r)).set_value(*supplier);
+            sheet.get_cell_mut((5, r)).set_value(*product);
+            sheet.get_cell_mut((6, r)).set_value_number(10.0);
+        }
+    }
+
+    fn row(value: serde_json::Value) -> ExportRow {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn number(book: &Spreadsheet, sheet: &str, col: u32, r: u32) -> Option<f64> {
+        book.get_sheet_by_name(sheet)
+            .unwrap()
+            .get_cell((col, r))
+            .and_then(|c| c.get_value_number())
+    }
+
+    #[test]
+    fn date_formats_are_recognised() {
+        assert!(is_date_format("dd/mm/yyyy"));
+        assert!(is_date_format("[$-410]d mmmm yyyy;@"));
+        assert!(!is_date_format("General"));
+        assert!(!is_date_format("#,##0.00 \"days\""));
+        assert!(!is_date_format("[Red]0.00"));
+        assert!(!is_date_format("h:mm"));
+    }
+
+    #[test]
+    fn converts_1904_dates_on_every_sheet() {
+        let mut book = umya_spreadsheet::new_file_empty_worksheet();
+        order_sheet(&mut book, "2025", &[(44000.0, "100", "RÜHLE", "ORTICA")]);
+        order_sheet(&mut book, "2024", &[(43000.0, "90", "RÜHLE", "SALVIA")]);
+
+        let overview = book.new_sheet("Riepilogo").unwrap();
+        overview.get_cell_mut("A1").set_value("Ultimo ordine");
+        overview.get_cell_mut("B1").set_value_number(44100.0);
+        overview
+            .get_style_mut("B1")
+            .get_number_format_mut()
+            .set_format_code(NumberingFormat::FORMAT_DATE_DDMMYYYYSLASH);
+        overview.get_cell_mut("C1").set_value_number(50000.0);
+        overview.get_cell_mut("D1").set_formula("B1+1");
+
+        let data = vec![row(json!({
+            "datumAuftrag": "03/02/2025",
+            "nummerAuftrag": "200",
+            "kunde": "CLIENTE",
+            "lieferant": "RÜHLE",
+            "produkt": "MELISSA",
+            "menge": 5.0,
+        }))];
+        let plan = plan_export(
+            &mut book,
+            &Settings::default(),
+            data,
+            None,
+            true,
+            &|_, _| {},
+        )
+        .unwrap();
+
+        // Only the first sheet is a target, yet every sheet moves to the 1900 system.
+        assert_eq!(plan.contexts.keys().copied().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(number(&book, "2025", 1, 2), Some(45462.0));
+        assert_eq!(number(&book, "2024", 1, 2), Some(44462.0));
+        assert_eq!(number(&book, "Riepilogo", 2, 1), Some(45562.0));
+        assert_eq!(number(&book, "Riepilogo", 3, 1), Some(50000.0));
+        let formula = book
+            .get_sheet_by_name("Riepilogo")
+            .unwrap()
+            .get_cell("D1")
+            .unwrap();
+        assert!(formula.is_formula());
+        assert_eq!(formula.get_formula(), "B1+1");
+    }
+
+    #[test]
+    fn previewed_rows_match_the_written_rows() {
+        let mut book = umya_spreadsheet::new_file_empty_worksheet();
+        order_sheet(
+            &mut book,
+            "2025",
+            &[
+                (45660.0, "100", "ALPHA", "ORTICA"),
+                (45670.0, "101", "ALPHA", "SALVIA"),
+                (45680.0, "102", "GAMMA", "MENTA"),
+            ],
+        );
+
+        let order = |number: &str, supplier: &str, product: &str| {
+            row(json!({
+                "datumAuftrag": "03/02/2025",
+                "nummerAuftrag": number,
+                "kunde": "CLIENTE",
+                "lieferant": supplier,
+                "produkt": product,
+                "menge": 5.0,
+            }))
+        };
+        let invoice = |number: &str, supplier: &str, product: &str, qty: f64, invoice: &str| {
+            row(json!({
+                "nummerAuftrag": number,
+                "lieferant": supplier,
+                "produkt": product,
+                "preis": 2.5,
+                "datumRechnung": "10/02/2025",
+                "nummerRechnung": invoice,
+                "gelieferteMenge": qty,
+            }))
+        };
+        let data = vec![
+            order("200", "ALPHA", "MELISSA"),
+            order("201", "BETA", "TIMO"),
+            invoice("102", "GAMMA", "MENTA", 10.0, "R-1"),
+            invoice("100", "ALPHA", "ORTICA", 10.0, "R-2"),
+            order("202", "ZETA", "ROSMARINO"),
+            invoice("202", "ZETA", "ROSMARINO", 5.0, "R-3"),
+        ];
+        let products = [
+            "MELISSA",
+            "TIMO",
+            "MENTA",
+            "ORTICA",
+            "ROSMARINO",
+            "ROSMARINO",
+        ];
+
+        let plan = plan_export(
+            &mut book,
+            &Settings::default(),
+            data,
+            None,
+            false,
+            &|_, _| {},
+        )
+        .unwrap();
+        let preview = describe_plan(&book, &plan);
+        assert_eq!((preview.updated, preview.inserted), (3, 3));
+        apply_plan(&mut book, &plan).unwrap();
+
+        let sheet = book.get_sheet_by_name("2025").unwrap();
+        let actions: Vec<(usize, &str, u32)> = preview
+            .rows
+            .iter()
+            .map(|r| (r.index, r.action.as_str(), r.row))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (0, "insert", 4),
+                (1, "insert", 5),
+                (2, "update", 6),
+                (3, "update", 2),
+                (4, "insert", 7),
+                (5, "insert", 7),
+            ]
+        );
+        for r in &preview.rows {
+            assert_eq!(
+                sheet.get_value((5, r.row)),
+                products[r.index],
+                "row {}",
+                r.index
+            );
+        }
+        assert_eq!(sheet.get_value((11, 6)), "R-1");
+        assert_eq!(sheet.get_value((11, 2)), "R-2");
+        assert_eq!(sheet.get_value((11, 7)), "R-3");
+        assert_eq!(preview.rows[5].merged_into, Some(4));
+    }
+}
@@ -0,0 +1,587 @@
+use crate::modules::utils::{column_index, column_letters};
+
+use std::collections::{BTreeMap, HashMap};
+use umya_spreadsheet::Worksheet;
+
+/// Rows searched for the header of the table.
+const HEADER_SEARCH_ROWS: u32 = 100;
+/// Key of `header_synonyms` for headers of further date columns.
+const EXTRA_DATES_KEY: &str = "extraDates";
+
+/// Where each exported field lives in the workbook. A reference of up to three letters
+/// is a column letter, anything longer is the text of its header cell.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExcelColumns {
+    pub datum_auftrag: String,
+    pub nummer_auftrag: String,
+    pub kunde: String,
+    pub lieferant: String,
+    pub produkt: String,
+    pub menge: String,
+    pub waehrung: String,
+    pub preis: String,
+    pub datum_rechnung: String,
+    pub nummer_rechnung: String,
+    pub gelieferte_menge: String,
+    /// Column whose formula is copied into inserted rows.
+    pub formula: Option<String>,
+    pub anmerkungen: Option<String>,
+    /// Date columns that are not exported but still need the 1904 date conversion, as
+    /// letters or header names. With `detect_headers`, columns whose header is among the
+    /// `extraDates` synonyms are converted as well.
+    pub extra_date_columns: Vec<String>,
+    /// Takes the position of each field from its header instead of the letter, which
+    /// then only serves the fields whose header is not recognised.
+    pub detect_headers: bool,
+    /// Header texts recognised for each field, keyed like the references above.
+    pub header_synonyms: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for ExcelColumns {
+    fn default() -> Self {
+        ExcelColumns {
+            datum_auftrag: "A".to_string(),
+            nummer_auftrag: "B".to_string(),
+            kunde: "C".to_string(),
+            lieferant: "D".to_string(),
+            produkt: "E".to_string(),
+            menge: "F".to_string(),
+            waehrung: "G".to_string(),
+            preis: "H".to_string(),
+            datum_rechnung: "J".to_string(),
+            nummer_rechnung: "K".to_string(),
+            gelieferte_menge: "L".to_string(),
+            formula: Some("M".to_string()),
+            anmerkungen: Some("R".to_string()),
+            extra_date_columns: vec!["Q".to_string()],
+            detect_headers: true,
+            header_synonyms: default_synonyms(),
+        }
+    }
+}
+
+pub fn default_synonyms() -> BTreeMap<String, Vec<String>> {
+    let synonyms: [(&str, &[&str]); 14] = [
+        (
+            "datumAuftrag",
+            &["Data ordine", "Data ord.", "Datum Auftrag", "Auftragsdatum"],
+        ),
+        (
+            "nummerAuftrag",
+            &[
+                "Numero ordine",
+                "N. ordine",
+                "Nr. ordine",
+                "Ordine",
+                "Nummer Auftrag",
+                "Auftragsnummer",
+            ],
+        ),
+        ("kunde", &["Cliente", "Kunde"]),
+        (
+            "lieferant",
+            &["Casa Estera", "Fornitore", "Lieferant", "Hersteller"],
+        ),
+        (
+            "produkt",
+            &["Prodotto", "Articolo", "Descrizione", "Produkt", "Artikel"],
+        ),
+        ("menge", &["Quantità", "Quantita", "Qtà", "Q.tà", "Menge"]),
+        ("waehrung", &["Valuta", "Divisa", "Währung"]),
+        ("preis", &["Prezzo", "Prezzo unitario", "Preis"]),
+        (
+            "datumRechnung",
+            &[
+                "Data fattura",
+                "Data fatt.",
+                "Datum Rechnung",
+                "Rechnungsdatum",
+            ],
+        ),
+        (
+            "nummerRechnung",
+            &[
+                "Numero fattura",
+                "N. fattura",
+                "Nr. fattura",
+                "Fattura",
+                "Nummer Rechnung",
+                "Rechnungsnummer",
+            ],
+        ),
+        (
+            "gelieferteMenge",
+            &[
+                "Quantità consegnata",
+                "Qtà consegnata",
+                "Consegnato",
+                "Gelieferte Menge",
+                "Liefermenge",
+            ],
+        ),
+        ("formula", &[]),
+        (
+            "anmerkungen",
+            &["Note", "Annotazioni", "Osservazioni", "Anmerkungen"],
+        ),
+        (
+            EXTRA_DATES_KEY,
+            &[
+                "Data consegna",
+                "Data pagamento",
+                "Lieferdatum",
+                "Zahlungsdatum",
+            ],
+        ),
+    ];
+    synonyms
+        .iter()
+        .map(|(key, names)| {
+            (
+                key.to_string(),
+                names.iter().map(|n| n.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// A field of the export with the label used in error messages.
+struct Entry<'a> {
+    key: &'static str,
+    label: &'static str,
+    reference: Option<&'a str>,
+    required: bool,
+}
+
+fn required<'a>(key: &'static str, label: &'static str, reference: &'a str) -> Entry<'a> {
+    Entry {
+        key,
+        label,
+        reference: Some(reference),
+        required: true,
+    }
+}
+
+fn optional<'a>(key: &'static str, label: &'static str, reference: Option<&'a str>) -> Entry<'a> {
+    Entry {
+        key,
+        label,
+        reference,
+        required: false,
+    }
+}
+
+impl ExcelColumns {
+    fn entries(&self) -> Vec<Entry<'_>> {
+        vec![
+            required("datumAuftrag", "data ordine", &self.datum_auftrag),
+            required("nummerAuftrag", "numero ordine", &self.nummer_auftrag),
+            required("kunde", "cliente", &self.kunde),
+            required("lieferant", "fornitore", &self.lieferant),
+            required("produkt", "prodotto", &self.produkt),
+            required("menge", "quantità", &self.menge),
+            required("waehrung", "valuta", &self.waehrung),
+            required("preis", "prezzo", &self.preis),
+            required("datumRechnung", "data fattura", &self.datum_rechnung),
+            required("nummerRechnung", "numero fattura", &self.nummer_rechnung),
+            required(
+                "gelieferteMenge",
+                "quantità consegnata",
+                &self.gelieferte_menge,
+            ),
+            optional("formula", "formula", self.formula.as_deref()),
+            optional("anmerkungen", "note", self.anmerkungen.as_deref()),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let entries = self.entries();
+        let mut letters: Vec<(u32, &str)> = Vec::new();
+        for entry in &entries {
+            let reference = match entry.reference.map(str::trim) {
+                Some(r) if !r.is_empty() => r,
+                Some(_) => return Err(format!("Manca la colonna Excel per: {}", entry.label)),
+                None => continue,
+            };
+            if let Some(col) = column_index(reference) {
+                if let Some((_, other)) = letters.iter().find(|(c, _)| *c == col) {
+                    return Err(format!(
+                        "La colonna {} è assegnata sia a {} sia a {}.",
+                        reference.to_uppercase(),
+                        other,
+                        entry.label
+                    ));
+                }
+                letters.push((col, entry.label));
+            }
+        }
+        for reference in &self.extra_date_columns {
+            if header_key(reference).is_empty() {
+                return Err(format!("Colonna di data non valida: {}", reference));
+            }
+        }
+        for key in self.header_synonyms.keys() {
+            if key != EXTRA_DATES_KEY && !entries.iter().any(|e| e.key == key) {
+                return Err(format!("Sinonimi per un campo sconosciuto: {}", key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Columns of further dates: letters as given, header names and, with header
+    /// detection, the `extraDates` synonyms looked up in the header row. Columns the
+    /// export writes to are left out, they are converted as part of the data.
+    fn extra_dates(&self, headers: &[(u32, String)], taken: &[u32]) -> Vec<u32> {
+        let mut texts = Vec::new();
+        let mut columns = Vec::new();
+        for reference in &self.extra_date_columns {
+            match column_index(reference.trim()) {
+                Some(col) => columns.push(col),
+                None => texts.push(header_key(reference)),
+            }
+        }
+        if self.detect_headers {
+            if let Some(synonyms) = self.header_synonyms.get(EXTRA_DATES_KEY) {
+                texts.extend(synonyms.iter().map(|s| header_key(s)));
+            }
+        }
+        columns.extend(
+            headers
+                .iter()
+                .filter(|(_, h)| texts.contains(h))
+                .map(|(c, _)| *c),
+        );
+
+        columns.retain(|c| !taken.contains(c));
+        columns.sort_unstable();
+        columns.dedup();
+        columns
+    }
+
+    /// Header texts that identify a field: the reference when it is a header name, then
+    /// the synonyms if headers are detected.
+    fn header_texts(&self, entry: &Entry) -> Vec<String> {
+        self.texts(entry, self.detect_headers)
+    }
+
+    fn texts(&self, entry: &Entry, with_synonyms: bool) -> Vec<String> {
+        let mut texts: Vec<String> = entry
+            .reference
+            .filter(|r| column_index(r).is_none())
+            .map(header_key)
+            .into_iter()
+            .collect();
+        if with_synonyms {
+            if let Some(synonyms) = self.header_synonyms.get(entry.key) {
+                texts.extend(synonyms.iter().map(|s| header_key(s)));
+            }
+        }
+        texts.retain(|t| !t.is_empty());
+        texts
+    }
+}
+
+/// The columns of the workbook at hand, with header names resolved to positions.
+#[derive(Clone)]
+pub struct ColumnLayout {
+    pub header_row: u32,
+    pub order_date: u32,
+    pub order_number: u32,
+    pub customer: u32,
+    pub supplier: u32,
+    pub product: u32,
+    pub quantity: u32,
+    pub currency: u32,
+    pub price: u32,
+    pub invoice_date: u32,
+    pub invoice_number: u32,
+    pub delivered_quantity: u32,
+    pub formula: Option<u32>,
+    pub notes: Option<u32>,
+    pub extra_dates: Vec<u32>,
+}
+
+fn cell_text(sheet: &Worksheet, col: u32, row: u32) -> String {
+    sheet.get_value((col, row)).trim().to_string()
+}
+
+/// Compares headers regardless of case, punctuation and line breaks inside the cell.
+fn header_key(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn row_headers(sheet: &Worksheet, row: u32) -> Vec<(u32, String)> {
+    (1..=sheet.get_highest_column())
+        .map(|c| (c, header_key(&cell_text(sheet, c, row))))
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
+
+/// The header row is the one, among the first rows, where the most fields recognise
+/// their header. The synonyms count even with fixed letters, so that the data still
+/// starts below the header.
+pub fn find_header_row(sheet: &Worksheet, columns: &ExcelColumns) -> Result<u32, String> {
+    let search_limit = sheet.get_highest_row().min(HEADER_SEARCH_ROWS);
+    let texts: Vec<Vec<String>> = columns
+        .entries()
+        .iter()
+        .map(|e| columns.texts(e, true))
+        .collect();
+
+    let mut best = (0, 0);
+    for r in 1..=search_limit {
+        let headers = row_headers(sheet, r);
+        let found = texts
+            .iter()
+            .filter(|t| headers.iter().any(|(_, h)| t.contains(h)))
+            .count();
+        if found > best.1 {
+            best = (r, found);
+        }
+    }
+    if best.1 == 0 {
+        return Err(format!(
+            "Riga di intestazione non trovata nelle prime {} righe del foglio: nessuna cella corrisponde ai nomi delle colonne configurati nelle impostazioni.",
+            search_limit
+        ));
+    }
+    Ok(best.0)
+}
+
+impl ColumnLayout {
+    pub fn resolve(sheet: &Worksheet, columns: &ExcelColumns) -> Result<ColumnLayout, String> {
+        let header_row = find_header_row(sheet, columns)?;
+        let headers = row_headers(sheet, header_row);
+
+        let entries = columns.entries();
+        let texts_by_key: Vec<(&str, Vec<String>)> = entries
+            .iter()
+            .map(|e| (e.key, columns.header_texts(e)))
+            .collect();
+        // A column whose header names another field is never that field's fallback.
+        let claimed_by_other = |col: u32, key: &str| {
+            headers.iter().any(|(c, h)| {
+                *c == col
+                    && texts_by_key
+                        .iter()
+                        .any(|(k, texts)| *k != key && texts.contains(h))
+            })
+        };
+
+        let mut taken: Vec<u32> = Vec::new();
+        let mut found: HashMap<&str, u32> = HashMap::new();
+        let mut missing = Vec::new();
+        for entry in entries {
+            let reference = entry.reference.map(str::trim).filter(|r| !r.is_empty());
+            let letter = reference.and_then(column_index);
+            let texts = columns.header_texts(&entry);
+            let by_header = headers
+                .iter()
+                .find(|(c, h)| !taken.contains(c) && texts.contains(h))
+                .map(|(c, _)| *c);
+
+            let col = match (by_header, letter) {
+                (Some(col), _) => Some(col),
+                // A header name that is not in the row is never replaced by a guess.
+                (None, None) => None,
+                // A header worded differently from the synonyms keeps the configured letter.
+                (None, Some(col)) if !taken.contains(&col) && !claimed_by_other(col, entry.key) => {
+                    Some(col)
+                }
+                (None, Some(_)) => None,
+            };
+            match col {
+                Some(col) => {
+                    taken.push(col);
+                    found.insert(entry.key, col);
+                }
+                None if entry.required || (reference.is_some() && letter.is_none()) => {
+                    missing.push(entry.label)
+                }
+                None => {}
+            }
+        }
+
+        if !missing.is_empty() {
+            let present: Vec<String> = (1..=sheet.get_highest_column())
+                .map(|c| cell_text(sheet, c, header_row))
+                .filter(|t| !t.is_empty())
+                .map(|t| format!("\"{}\"", t))
+                .collect();
+            return Err(format!(
+                "Colonne non trovate nella riga di intestazione {}: {}. Intestazioni presenti: {}. Aggiungere i nomi usati nel file ai sinonimi delle colonne nelle impostazioni.",
+                header_row,
+                missing.join(", "),
+                present.join(", ")
+            ));
+        }
+
+        let layout = ColumnLayout {
+            header_row,
+            order_date: found["datumAuftrag"],
+            order_number: found["nummerAuftrag"],
+            customer: found["kunde"],
+            supplier: found["lieferant"],
+            product: found["produkt"],
+            quantity: found["menge"],
+            currency: found["waehrung"],
+            price: found["preis"],
+            invoice_date: found["datumRechnung"],
+            invoice_number: found["nummerRechnung"],
+            delivered_quantity: found["gelieferteMenge"],
+            formula: found.get("formula").copied(),
+            notes: found.get("anmerkungen").copied(),
+            extra_dates: columns.extra_dates(&headers, &taken),
+        };
+
+        let used = layout.data_columns();
+        if let Some(col) = used
+            .iter()
+            .find(|c| used.iter().filter(|o| o == c).count() > 1)
+        {
+            return Err(format!(
+                "La colonna {} è assegnata a più campi dell'esportazione.",
+                column_letters(*col)
+            ));
+        }
+        Ok(layout)
+    }
+
+    /// Every column the export writes to.
+    pub fn data_columns(&self) -> Vec<u32> {
+        let mut columns = vec![
+            self.order_date,
+            self.order_number,
+            self.customer,
+            self.supplier,
+            self.product,
+            self.quantity,
+            self.currency,
+            self.price,
+            self.invoice_date,
+            self.invoice_number,
+            self.delivered_quantity,
+        ];
+        columns.extend(self.formula);
+        columns.extend(self.notes);
+        columns
+    }
+
+    pub fn date_columns(&self) -> Vec<u32> {
+        let mut columns = vec![self.order_date, self.invoice_date];
+        columns.extend(self.extra_dates.iter().copied());
+        columns
+    }
+
+    /// The last column of the table, up to which inserted rows take over the styles.
+    pub fn last_column(&self) -> u32 {
+        self.data_columns()
+            .into_iter()
+            .chain(self.extra_dates.iter().copied())
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use umya_spreadsheet::Spreadsheet;
+
+    const HEADERS: [&str; 12] = [
+        "Data ordine",
+        "Numero ordine",
+        "Cliente",
+        "Casa Estera",
+        "Prodotto",
+        "Quantità",
+        "Valuta",
+        "Prezzo",
+        "",
+        "Data fattura",
+        "Numero fattura",
+        "Quantità consegnata",
+    ];
+
+    /// A workbook whose only sheet has a title in row 1 and `headers` in row 3.
+    fn book(headers: &[&str]) -> Spreadsheet {
+        let mut book = umya_spreadsheet::new_file_empty_worksheet();
+        let sheet = book.new_sheet("Ordini").unwrap();
+        sheet.get_cell_mut("A1").set_value("Ordini 2025");
+        for (i, header) in headers.iter().enumerate() {
+            sheet.get_cell_mut((i as u32 + 1, 3)).set_value(*header);
+        }
+        book
+    }
+
+    fn resolve(book: &Spreadsheet, columns: &ExcelColumns) -> Result<ColumnLayout, String> {
+        ColumnLayout::resolve(book.get_sheet(&0).unwrap(), columns)
+    }
+
+    #[test]
+    fn finds_columns_by_header() {
+        let mut headers = HEADERS;
+        headers.swap(2, 3);
+        let layout = resolve(&book(&headers), &ExcelColumns::default()).unwrap();
+
+        assert_eq!(layout.header_row, 3);
+        assert_eq!((layout.customer, layout.supplier), (4, 3));
+        assert_eq!(layout.delivered_quantity, 12);
+    }
+
+    #[test]
+    fn unknown_headers_keep_the_configured_letter() {
+        let mut headers = HEADERS;
+        headers[5] = "Q.tà ordinata";
+        headers[7] = "Prezzo listino";
+        let layout = resolve(&book(&headers), &ExcelColumns::default()).unwrap();
+
+        assert_eq!(layout.header_row, 3);
+        assert_eq!((layout.quantity, layout.price), (6, 8));
+    }
+
+    #[test]
+    fn letter_of_another_fields_header_is_not_used() {
+        let mut headers = HEADERS;
+        headers[5] = "Prezzo";
+        headers[7] = "Q.tà ordinata";
+        let error = resolve(&book(&headers), &ExcelColumns::default())
+            .err()
+            .unwrap();
+
+        assert!(error.contains("quantità"), "{}", error);
+    }
+
+    #[test]
+    fn header_name_references_must_be_present() {
+        let mut headers = HEADERS;
+        headers[4] = "Articolo fornitore";
+        let mut columns = ExcelColumns {
+            produkt: "Articolo fornitore".to_string(),
+            ..ExcelColumns::default()
+        };
+        assert_eq!(resolve(&book(&headers), &columns).unwrap().product, 5);
+
+        columns.produkt = "Descrizione articolo".to_string();
+        let error = resolve(&book(&headers), &columns).err().unwrap();
+        assert!(error.contains("prodotto"), "{}", error);
+    }
+
+    #[test]
+    fn letters_only_without_header_detection() {
+        let mut headers = HEADERS;
+        headers.swap(2, 3);
+        let columns = ExcelColumns {
+            detect_headers: false,
+            ..ExcelColumns::default()
+        };
+        let layout = resolve(&book(&headers), &columns).unwrap();
+
+        assert_eq!((layout.customer, layout.supplier), (3, 4));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::modules::policy;
+use crate::modules::utils::read_csv_records;
+use crate::modules::workspace;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{command, AppHandle};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GlossaryTerm {
+    pub source: String,
+    pub target: String,
+    pub language: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlossaryImportResult {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+const SEED_TERMS: [(&str, &str); 34] = [
+    ("SEMEN", "SEMI"),
+    ("SEMINA", "SEMI"),
+    ("RADIX", "RADICE"),
+    ("RADICES", "RADICI"),
+    ("FOLIUM", "FOGLIE"),
+    ("FOLIA", "FOGLIE"),
+    ("HERBA", "ERBA"),
+    ("FLOS", "FIORI"),
+    ("FLORES", "FIORI"),
+    ("CORTEX", "CORTECCIA"),
+    ("FRUCTUS", "FRUTTI"),
+    ("RHIZOMA", "RIZOMA"),
+    ("BULBUS", "BULBO"),
+    ("PERICARPIUM", "PERICARPO"),
+    ("SAMEN", "SEMI"),
+    ("WURZEL", "RADICE"),
+    ("BLÄTTER", "FOGLIE"),
+    ("BLATT", "FOGLIE"),
+    ("KRAUT", "ERBA"),
+    ("BLÜTEN", "FIORI"),
+    ("RINDE", "CORTECCIA"),
+    ("FRÜCHTE", "FRUTTI"),
+    ("SCHALEN", "BUCCE"),
+    ("SEED", "SEMI"),
+    ("SEEDS", "SEMI"),
+    ("ROOT", "RADICE"),
+    ("ROOTS", "RADICI"),
+    ("LEAF", "FOGLIE"),
+    ("LEAVES", "FOGLIE"),
+    ("HERB", "ERBA"),
+    ("FLOWER", "FIORI"),
+    ("FLOWERS", "FIORI"),
+    ("BARK", "CORTECCIA"),
+    ("FRUIT", "FRUTTI"),
+];
+
+fn seed_terms() -> Vec<GlossaryTerm> {
+    SEED_TERMS
+        .iter()
+        .map(|(source, target)| GlossaryTerm {
+            source: source.to_string(),
+            target: target.to_string(),
+            language: "it".to_string(),
+        })
+        .collect()
+}
+
+pub fn normalize(term: &str) -> String {
+    term.trim().to_uppercase()
+}
+
+pub fn load_terms(app: &AppHandle) -> Result<Vec<GlossaryTerm>, String> {
+    let store = workspace::store(app, "glossary.json")?;
+
+    match store.get("terms") {
+        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
+        None => Ok(seed_terms()),
+    }
+}
+
+pub fn save_terms(app: &AppHandle, terms: &[GlossaryTerm]) -> Result<(), String> {
+    let store = workspace::store(app, "glossary.json")?;
+    store.set("terms", json!(terms));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+fn lookup_for(terms: &[GlossaryTerm], language: &str) -> HashMap<String, String> {
+    terms
+        .iter()
+        .filter(|t| t.language.eq_ignore_ascii_case(language))
+        .map(|t| (normalize(&t.source), normalize(&t.target)))
+        .collect()
+}
+
+/// Replaces every word of a product name that has a glossary entry. Punctuation around
+/// the word ("SEMEN," or "(RADIX)") is kept.
+pub fn translate_name(name: &str, lookup: &HashMap<String, String>) -> String {
+    name.split_whitespace()
+        .map(|token| {
+            let start = token
+                .find(|c: char| c.is_alphanumeric())
+                .unwrap_or(token.len());
+            let end = token
+                .rfind(|c: char| c.is_alphanumeric())
+                .map(|i| i + token[i..].chars().next().map_or(1, |c| c.len_utf8()))
+                .unwrap_or(start);
+            if start >= end {
+                return token.to_string();
+            }
+            match lookup.get(&token[start..end].to_uppercase()) {
+                Some(target) => format!("{}{}{}", &token[..start], target, &token[end..]),
+                None => token.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Applies the glossary of the target language to every `produkt` of an extraction result.
+pub fn apply(app: &AppHandle, result: &mut Value, language: &str) {
+    let terms = match load_terms(app) {
+        Ok(t) => t,
+        Err(e) => {
+            println!("Glossario non disponibile: {}", e);
+            return;
+        }
+    };
+    let lookup = lookup_for(&terms, language);
+    if lookup.is_empty() {
+        return;
+    }
+
+    if let Some(products) = result.get_mut("produkte").and_then(|p| p.as_array_mut()) {
+        for prod in products {
+            if let Some(name_val) = prod.get_mut("produkt") {
+                if let Some(name) = name_val.as_str() {
+                    *name_val = json!(translate_name(name, &lookup));
+                }
+            }
+        }
+    }
+}
+
+/// The glossary section for the `{{GLOSSARY}}` prompt placeholder.
+pub fn prompt_terms(app: &AppHandle, language: &str) -> String {
+    let terms = load_terms(app).unwrap_or_default();
+    let mut pairs: Vec<(String, String)> = lookup_for(&terms, language).into_iter().collect();
+    if pairs.is_empty() {
+        return "none".to_string();
+    }
+    pairs.sort();
+
+    let list = pairs
+        .iter()
+        .map(|(source, target)| format!("{} = {}", source, target))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "Always translate these single words exactly like this, wherever they appear in a product name: {}",
+        list
+    )
+}
+
+fn read_csv(path: &str) -> Result<Vec<GlossaryTerm>, String> {
+    let mut terms = Vec::new();
+    for (i, record) in read_csv_records(path)?.iter().enumerate() {
+        let source = record.get(0).unwrap_or_default().trim();
+        let target = record.get(1).unwrap_or_default().trim();
+        let language = record.get(2).map(|l| l.trim()).filter(|l| !l.is_empty());
+
+        if i == 0 && source.eq_ignore_ascii_case("source") {
+            continue;
+        }
+        if source.is_empty() || target.is_empty() {
+            continue;
+        }
+        terms.push(GlossaryTerm {
+            source: normalize(source),
+            target: normalize(target),
+            language: language.unwrap_or("it").to_lowercase(),
+        });
+    }
+    Ok(terms)
+}
+
+#[command]
+pub async fn get_glossary(app: AppHandle) -> Result<Vec<GlossaryTerm>, String> {
+    load_terms(&app)
+}
+
+#[command]
+pub async fn save_glossary(app: AppHandle, terms: Vec<GlossaryTerm>) -> Result<(), String> {
+    let mut cleaned: Vec<GlossaryTerm> = Vec::with_capacity(terms.len());
+    for term in terms {
+        let source = normalize(&term.source);
+        let target = normalize(&term.target);
+        if source.is_empty() || target.is_empty() {
+            continue;
+        }
+        if source.contains(char::is_whitespace) {
+            return Err(format!(
+                "Il glossario accetta solo singole parole: '{}'",
+                term.source
+            ));
+        }
+        let language = term.language.trim().to_lowercase();
+        cleaned.retain(|t| !(t.source == source && t.language == language));
+        cleaned.push(GlossaryTerm {
+            source,
+            target,
+            language,
+        });
+    }
+    save_terms(&app, &cleaned)
+}
+
+#[command]
+pub async fn import_glossary(
+    app: AppHandle,
+    path: String,
+    replace: bool,
+) -> Result<GlossaryImportResult, String> {
+    let imported = read_csv(&path)?;
+    let mut terms = if replace {
+        Vec::new()
+    } else {
+        load_terms(&app)?
+    };
+
+    let mut result = GlossaryImportResult {
+        added: 0,
+        updated: 0,
+        skipped: 0,
+    };
+    for term in imported {
+        if term.source.contains(char::is_whitespace) {
+            result.skipped += 1;
+            continue;
+        }
+        match terms
+            .iter_mut()
+            .find(|t| normalize(&t.source) == term.source && t.language == term.language)
+        {
+            Some(existing) if normalize(&existing.target) == term.target => result.skipped += 1,
+            Some(existing) => {
+                existing.target = term.target;
+                result.updated += 1;
+            }
+            None => {
+                terms.push(term);
+                result.added += 1;
+            }
+        }
+    }
+
+    save_terms(&app, &terms)?;
+    Ok(result)
+}
+
+#[command]
+pub async fn export_glossary(app: AppHandle, path: String) -> Result<usize, String> {
+    policy::check_export_path(Path::new(&path))?;
+    let terms = load_terms(&app)?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(&path)
+        .map_err(|e| format!("Impossibile creare il file: {}", e))?;
+
+    writer
+        .write_record(["source", "target", "language"])
+        .map_err(|e| e.to_string())?;
+    for term in &terms {
+        writer
+            .write_record([&term.source, &term.target, &term.language])
+            .map_err(|e| e.to_string())?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Errore di scrittura: {}", e))?;
+    Ok(terms.len())
+}
@@ -0,0 +1,240 @@
+use image::metadata::Orientation;
+use image::{
+    DynamicImage, GrayAlphaImage, GrayImage, ImageDecoder, ImageFormat, ImageReader, RgbImage,
+    RgbaImage,
+};
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+use tauri::AppHandle;
+use tauri_plugin_shell::ShellExt;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+const MAX_IMAGE_SIDE: u32 = 3000;
+
+pub struct ImagePage {
+    pub mime: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+fn is_tiff(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.ends_with(".tif") || lower.ends_with(".tiff")
+}
+
+fn limit_size(img: DynamicImage) -> DynamicImage {
+    if img.width().max(img.height()) > MAX_IMAGE_SIDE {
+        img.resize(
+            MAX_IMAGE_SIDE,
+            MAX_IMAGE_SIDE,
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        img
+    }
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| format!("Impossibile codificare l'immagine: {}", e))?;
+    Ok(bytes)
+}
+
+fn expand_bilevel(buf: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let stride = width.div_ceil(8) as usize;
+    let mut out = Vec::with_capacity((width * height) as usize);
+    for row in buf.chunks(stride).take(height as usize) {
+        for x in 0..width as usize {
+            let bit = (row[x / 8] >> (7 - (x % 8))) & 1;
+            out.push(if bit == 1 { 255 } else { 0 });
+        }
+    }
+    out
+}
+
+fn tiff_page_to_image(
+    color: ColorType,
+    data: DecodingResult,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, String> {
+    let buf = match data {
+        DecodingResult::U8(buf) => buf,
+        _ => return Err("Profondità colore TIFF non supportata".to_string()),
+    };
+
+    let img = match color {
+        ColorType::Gray(1) => {
+            GrayImage::from_raw(width, height, expand_bilevel(&buf, width, height))
+                .map(DynamicImage::ImageLuma8)
+        }
+        ColorType::Gray(8) => GrayImage::from_raw(width, height, buf).map(DynamicImage::ImageLuma8),
+        ColorType::GrayA(8) => {
+            GrayAlphaImage::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        ColorType::RGB(8) => RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8),
+        ColorType::RGBA(8) => RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8),
+        other => return Err(format!("Tipo colore TIFF non supportato: {:?}", other)),
+    };
+
+    img.ok_or_else(|| "Dati TIFF incompleti".to_string())
+}
+
+fn load_tiff_pages(path: &str) -> Result<Vec<DynamicImage>, String> {
+    let file = File::open(path).map_err(|e| format!("Impossibile leggere il file: {}", e))?;
+    let mut decoder =
+        Decoder::new(BufReader::new(file)).map_err(|e| format!("TIFF non valido: {}", e))?;
+
+    let mut pages = Vec::new();
+    loop {
+        let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+        let color = decoder.colortype().map_err(|e| e.to_string())?;
+        let orientation = decoder
+            .find_tag_unsigned::<u8>(Tag::Orientation)
+            .ok()
+            .flatten()
+            .and_then(Orientation::from_exif);
+        let data = decoder
+            .read_image()
+            .map_err(|e| format!("Pagina TIFF {} illeggibile: {}", pages.len() + 1, e))?;
+
+        let mut img = tiff_page_to_image(color, data, width, height)?;
+        if let Some(o) = orientation {
+            img.apply_orientation(o);
+        }
+        pages.push(img);
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(|e| e.to_string())?;
+    }
+
+    Ok(pages)
+}
+
+fn load_single_image(path: &str) -> Result<DynamicImage, String> {
+    let mut decoder = ImageReader::open(path)
+        .map_err(|e| format!("Impossibile leggere il file: {}", e))?
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| format!("Formato immagine non supportato: {}", e))?;
+
+    let orientation = decoder.orientation().ok();
+    let mut img =
+        DynamicImage::from_decoder(decoder).map_err(|e| format!("Immagine illeggibile: {}", e))?;
+    if let Some(o) = orientation {
+        img.apply_orientation(o);
+    }
+    Ok(img)
+}
+
+/// Loads every page of an image document with the EXIF/TIFF orientation already applied.
+pub fn load_pages(path: &str) -> Result<Vec<DynamicImage>, String> {
+    let pages = if is_tiff(path) {
+        load_tiff_pages(path)?
+    } else {
+        vec![load_single_image(path)?]
+    };
+    Ok(pages.into_iter().map(limit_size).collect())
+}
+
+fn original_if_upright(path: &str) -> Option<ImagePage> {
+    let mime = match ImageFormat::from_path(path).ok()? {
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Png => "image/png",
+        _ => return None,
+    };
+
+    let mut decoder = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    let (width, height) = decoder.dimensions();
+    if width.max(height) > MAX_IMAGE_SIDE
+        || decoder.orientation().ok()? != Orientation::NoTransforms
+    {
+        return None;
+    }
+
+    Some(ImagePage {
+        mime,
+        bytes: std::fs::read(path).ok()?,
+    })
+}
+
+/// Encodes every page for the OCR upload. Upright JPEG/PNG files are sent as they are,
+/// everything else is re-encoded as PNG so that the orientation correction survives and
+/// multi-page TIFFs become single images.
+pub fn encode_pages(path: &str) -> Result<Vec<ImagePage>, String> {
+    if !is_tiff(path) {
+        if let Some(page) = original_if_upright(path) {
+            return Ok(vec![page]);
+        }
+    }
+
+    load_pages(path)?
+        .iter()
+        .map(|img| {
+            Ok(ImagePage {
+                mime: "image/png",
+                bytes: encode_png(img)?,
+            })
+        })
+        .collect()
+}
+
+async fn run_tesseract(app: &AppHandle, image_path: &Path) -> Result<String, String> {
+    let image_arg = image_path.to_string_lossy().to_string();
+    let attempts: [&[&str]; 2] = [&["stdout", "-l", "ita+deu+eng"], &["stdout"]];
+    let mut last_error = String::new();
+
+    for extra in attempts {
+        let mut args = vec![image_arg.as_str()];
+        args.extend_from_slice(extra);
+
+        let output = app
+            .shell()
+            .command("tesseract")
+            .args(&args)
+            .output()
+            .await
+            .map_err(|e| format!("Tesseract non disponibile: {}", e))?;
+
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+        last_error = String::from_utf8_lossy(&output.stderr).to_string();
+    }
+
+    Err(format!("Tesseract non riuscito: {}", last_error.trim()))
+}
+
+/// Runs the local Tesseract installation over every page of an image document.
+pub async fn run_local_ocr(app: &AppHandle, path: &str) -> Result<String, String> {
+    let pages = load_pages(path)?;
+    let mut texts = Vec::with_capacity(pages.len());
+
+    for (i, page) in pages.iter().enumerate() {
+        let tmp_path = std::env::temp_dir().join(format!(
+            "raccolta_ocr_{}_{}_{}.png",
+            std::process::id(),
+            chrono::Local::now().format("%H%M%S%f"),
+            i
+        ));
+        page.save_with_format(&tmp_path, ImageFormat::Png)
+            .map_err(|e| format!("Impossibile salvare l'immagine temporanea: {}", e))?;
+
+        let result = run_tesseract(app, &tmp_path).await;
+        let _ = std::fs::remove_file(&tmp_path);
+        texts.push(result?);
+    }
+
+    Ok(texts.join("\n\n"))
+}
@@ -0,0 +1,149 @@
+use crate::modules::document::{DocumentKind, InputDocument};
+use crate::modules::utils::{sanitize_file_name, unique_path};
+use crate::modules::{archive, email};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::{command, Manager};
+
+/// An expanded email or archive. Its folder holds the extracted files and is deleted
+/// when the container leaves the selection or changes on disk.
+struct CachedInput {
+    modified: Option<SystemTime>,
+    dir: PathBuf,
+    documents: Vec<InputDocument>,
+}
+
+/// Containers already expanded in this session, by source path.
+#[derive(Default)]
+pub struct InputCache(Mutex<HashMap<String, CachedInput>>);
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputError {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedInputs {
+    pub documents: Vec<InputDocument>,
+    /// Containers that could not be expanded; the other files are still returned.
+    pub errors: Vec<InputError>,
+}
+
+fn work_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Cartella di lavoro non disponibile: {}", e))?
+        .join("inputs");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Impossibile creare la cartella di lavoro: {}", e))?;
+    Ok(dir)
+}
+
+/// Removes the files extracted in earlier sessions.
+pub fn clear_work_dir(app: &tauri::AppHandle) {
+    if let Ok(dir) = work_dir(app) {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            println!("Impossibile pulire la cartella di lavoro: {}", e);
+        }
+    }
+}
+
+fn expand_container(
+    app: &tauri::AppHandle,
+    path: &str,
+    kind: DocumentKind,
+) -> Result<(PathBuf, Vec<InputDocument>), String> {
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| sanitize_file_name(&s.to_string_lossy()))
+        .unwrap_or_default();
+    let dir = unique_path(&work_dir(app)?, &stem);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Impossibile creare la cartella: {}", e))?;
+
+    let result = match kind {
+        DocumentKind::Email => email::extract(path, &dir, 0),
+        _ => archive::extract(path, &dir, 0),
+    };
+    match result {
+        Ok(documents) => Ok((dir, documents)),
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&dir);
+            Err(e)
+        }
+    }
+}
+
+#[command]
+pub async fn expand_inputs(
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, InputCache>,
+    paths: Vec<String>,
+) -> Result<ExpandedInputs, String> {
+    let mut cache = cache
+        .0
+        .lock()
+        .map_err(|_| "Archivio dei file estratti non disponibile.".to_string())?;
+
+    cache.retain(|path, entry| {
+        let keep = paths.contains(path);
+        if !keep {
+            let _ = std::fs::remove_dir_all(&entry.dir);
+        }
+        keep
+    });
+
+    let mut documents = Vec::with_capacity(paths.len());
+    let mut errors = Vec::new();
+
+    for path in paths {
+        let kind = match DocumentKind::from_path(&path) {
+            Some(k @ (DocumentKind::Email | DocumentKind::Archive)) => k,
+            Some(_) => {
+                documents.push(InputDocument {
+                    path,
+                    ..Default::default()
+                });
+                continue;
+            }
+            None => {
+                println!("File ignorato (formato non supportato): {}", path);
+                continue;
+            }
+        };
+
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if let Some(entry) = cache.get(&path) {
+            if entry.modified == modified {
+                documents.extend(entry.documents.iter().cloned());
+                continue;
+            }
+        }
+        if let Some(outdated) = cache.remove(&path) {
+            let _ = std::fs::remove_dir_all(&outdated.dir);
+        }
+
+        match expand_container(&app, &path, kind) {
+            Ok((dir, extracted)) => {
+                documents.extend(extracted.iter().cloned());
+                cache.insert(
+                    path,
+                    CachedInput {
+                        modified,
+                        dir,
+                        documents: extracted,
+                    },
+                );
+            }
+            Err(error) => errors.push(InputError { path, error }),
+        }
+    }
+
+    Ok(ExpandedInputs { documents, errors })
+}
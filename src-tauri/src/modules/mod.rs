@@ -1,5 +1,24 @@
 pub mod ai;
+pub mod archive;
+pub mod bundle;
+pub mod catalog;
 pub mod config;
+pub mod corrections;
+pub mod credentials;
+pub mod document;
+pub mod email;
+pub mod examples;
 pub mod excel;
+pub mod excel_columns;
+pub mod glossary;
+pub mod images;
+pub mod inputs;
+pub mod policy;
+pub mod prompts;
+pub mod secrets;
+pub mod settings;
+pub mod templates;
+pub mod ubl;
 pub mod ui;
 pub mod utils;
+pub mod workspace;
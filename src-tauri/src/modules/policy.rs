@@ -0,0 +1,310 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::command;
+
+/// Overrides the location of the policy file, mainly for testing a policy before
+/// rolling it out.
+const POLICY_ENV: &str = "RACCOLTA_DATI_POLICY";
+const POLICY_FILE: &str = "policy.json";
+
+/// Options IT locks on shared workstations. A missing field leaves the option to the
+/// user.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Policy {
+    /// URL prefixes the AI requests may go to.
+    #[serde(default)]
+    pub allowed_endpoints: Option<Vec<String>>,
+    #[serde(default)]
+    pub max_concurrency: Option<u32>,
+    /// `false` keeps documents on the workstation: no upload to the cloud OCR.
+    #[serde(default)]
+    pub cloud_ocr: Option<bool>,
+    /// Folders that exported workbooks and files may be written to, subfolders included.
+    #[serde(default)]
+    pub allowed_export_folders: Option<Vec<String>>,
+}
+
+#[derive(serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyStatus {
+    pub path: String,
+    pub active: bool,
+    pub error: Option<String>,
+    pub policy: Policy,
+}
+
+static POLICY: OnceLock<PolicyStatus> = OnceLock::new();
+
+fn system_path() -> PathBuf {
+    if let Ok(path) = std::env::var(POLICY_ENV) {
+        if !path.trim().is_empty() {
+            return PathBuf::from(path.trim());
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    let dir = PathBuf::from(
+        std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string()),
+    )
+    .join("raccolta-dati");
+    #[cfg(target_os = "macos")]
+    let dir = PathBuf::from("/Library/Application Support/raccolta-dati");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let dir = PathBuf::from("/etc/raccolta-dati");
+
+    dir.join(POLICY_FILE)
+}
+
+fn read_policy(path: &Path) -> PolicyStatus {
+    let mut status = PolicyStatus {
+        path: path.display().to_string(),
+        active: false,
+        error: None,
+        policy: Policy::default(),
+    };
+    let content = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return status,
+        Err(e) => {
+            status.error = Some(format!("Impossibile leggere {}: {}", status.path, e));
+            return status;
+        }
+    };
+
+    status.active = true;
+    match serde_json::from_str(&content) {
+        Ok(policy) => status.policy = policy,
+        Err(e) => {
+            status.error = Some(format!(
+                "File dei criteri {} non valido: {}",
+                status.path, e
+            ))
+        }
+    }
+    status
+}
+
+/// Reads the policy file once; the policy stays the same until the app restarts.
+pub fn status() -> &'static PolicyStatus {
+    POLICY.get_or_init(|| {
+        let status = read_policy(&system_path());
+        match (&status.error, status.active) {
+            (Some(e), _) => println!("⚠️ {}", e),
+            (None, true) => println!("Criteri amministrativi caricati da {}", status.path),
+            (None, false) => {}
+        }
+        status
+    })
+}
+
+/// The active policy. A policy file that exists but cannot be read blocks every
+/// restricted action instead of silently allowing it.
+fn policy() -> Result<&'static Policy, String> {
+    let status = status();
+    match &status.error {
+        Some(e) => Err(format!(
+            "{}. Le operazioni soggette ai criteri sono bloccate, contattare l'amministratore.",
+            e
+        )),
+        None => Ok(&status.policy),
+    }
+}
+
+pub fn check_endpoint(url: &str) -> Result<(), String> {
+    let allowed = match &policy()?.allowed_endpoints {
+        Some(list) => list,
+        None => return Ok(()),
+    };
+    let permits = |prefix: &String| {
+        let prefix = prefix.trim().trim_end_matches('/');
+        url == prefix || url.starts_with(&format!("{}/", prefix))
+    };
+    if allowed.iter().any(permits) {
+        Ok(())
+    } else {
+        Err(format!(
+            "L'indirizzo {} non è tra i servizi consentiti dai criteri dell'amministratore.",
+            url
+        ))
+    }
+}
+
+pub fn check_cloud_ocr() -> Result<(), String> {
+    if policy()?.cloud_ocr == Some(false) {
+        return Err(
+            "L'OCR nel cloud è disattivato dai criteri dell'amministratore: il documento non viene caricato."
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// 0 processes documents one at a time and is always allowed.
+pub fn check_concurrency(limit: u32) -> Result<(), String> {
+    match policy()?.max_concurrency {
+        Some(max) if limit > max => Err(format!(
+            "I criteri dell'amministratore consentono al massimo {} processi paralleli.",
+            max
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Lowers a stored concurrency that exceeds the policy.
+pub fn clamp_concurrency(limit: u32) -> u32 {
+    match status().policy.max_concurrency {
+        Some(max) => limit.min(max),
+        None => limit,
+    }
+}
+
+/// Resolves `..`, links and letter case as far as the path exists, so that a target
+/// cannot leave an allowed folder through a detour. The part that does not exist yet
+/// is resolved by its text, `..` included.
+fn resolve(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(last)) => {
+                rest.push(last);
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    let mut resolved = canonical(existing);
+    for component in rest.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    // Windows adds a `\\?\` prefix that the configured folders do not have.
+    let text = canonical.display().to_string();
+    match text.strip_prefix(r"\\?\") {
+        Some(stripped) => PathBuf::from(stripped),
+        None => canonical,
+    }
+}
+
+fn comparable(path: &Path) -> String {
+    let text = resolve(path).display().to_string();
+    if cfg!(target_os = "windows") {
+        text.to_lowercase()
+    } else {
+        text
+    }
+}
+
+fn is_within(path: &Path, folders: &[String]) -> bool {
+    let target = Path::new(&comparable(path)).to_path_buf();
+    folders
+        .iter()
+        .any(|folder| target.starts_with(comparable(Path::new(folder.trim()))))
+}
+
+/// Checks that a workbook or file may be written to `path`.
+pub fn check_export_path(path: &Path) -> Result<(), String> {
+    let allowed = match &policy()?.allowed_export_folders {
+        Some(list) => list,
+        None => return Ok(()),
+    };
+    if is_within(path, allowed) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} è fuori dalle cartelle di esportazione consentite dai criteri dell'amministratore ({}).",
+            path.display(),
+            allowed.join(", ")
+        ))
+    }
+}
+
+#[command]
+pub async fn get_policy() -> Result<PolicyStatus, String> {
+    Ok(status().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An allowed folder with an existing subfolder, next to a folder that is not allowed.
+    fn folders() -> (PathBuf, Vec<String>) {
+        let root = std::env::temp_dir().join(format!("raccolta-policy-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("consentita").join("2025")).unwrap();
+        std::fs::create_dir_all(root.join("altrove")).unwrap();
+        let allowed = vec![root.join("consentita").display().to_string()];
+        (root, allowed)
+    }
+
+    #[test]
+    fn allows_paths_inside_the_folders() {
+        let (root, allowed) = folders();
+        let inside = root.join("consentita");
+
+        assert!(is_within(&inside.join("ordini.xlsx"), &allowed));
+        assert!(is_within(
+            &inside.join("2025").join("ordini.xlsx"),
+            &allowed
+        ));
+        assert!(is_within(
+            &inside.join("nuova").join("ordini.xlsx"),
+            &allowed
+        ));
+        assert!(is_within(
+            &inside.join("2025").join("..").join("ordini.xlsx"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn denies_paths_outside_the_folders() {
+        let (root, allowed) = folders();
+
+        assert!(!is_within(
+            &root.join("altrove").join("ordini.xlsx"),
+            &allowed
+        ));
+        assert!(!is_within(
+            &root.join("consentita-2").join("ordini.xlsx"),
+            &allowed
+        ));
+        assert!(!is_within(
+            &root
+                .join("consentita")
+                .join("..")
+                .join("altrove")
+                .join("ordini.xlsx"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn parent_segments_below_a_missing_folder_cannot_escape() {
+        let (root, allowed) = folders();
+        let escape = root
+            .join("consentita")
+            .join("non-esiste")
+            .join("..")
+            .join("..")
+            .join("altrove")
+            .join("ordini.xlsx");
+
+        assert!(!is_within(&escape, &allowed));
+        assert_eq!(
+            resolve(&escape),
+            canonical(&root).join("altrove").join("ordini.xlsx")
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::modules::workspace;
+
+use serde_json::json;
+use std::collections::HashMap;
+use tauri::{command, AppHandle};
+
+const PROMPT_AUFTRAG: &str = include_str!("../../../src/prompts/PromptAuftrag.txt");
+const PROMPT_RECHNUNG: &str = include_str!("../../../src/prompts/PromptRechnung.txt");
+
+pub const PLACEHOLDER_EXAMPLES: &str = "{{EXAMPLES}}";
+pub const PLACEHOLDER_LAYOUT: &str = "{{LAYOUT}}";
+pub const PLACEHOLDER_DOCUMENT: &str = "{{DOCUMENT}}";
+pub const PLACEHOLDER_LANGUAGE: &str = "{{LANGUAGE}}";
+pub const PLACEHOLDER_GLOSSARY: &str = "{{GLOSSARY}}";
+
+pub const DEFAULT_LANGUAGE: &str = "it";
+
+/// Version reported for the bundled prompts. Count it up whenever the files in
+/// src/prompts or `default_template` change, so results name the text they came from.
+pub const DEFAULT_PROMPT_VERSION: u32 = 3;
+
+pub const DOC_TYPES: [&str; 2] = ["auftrag", "rechnung"];
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub version: u32,
+    pub template: String,
+    pub custom: bool,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+fn check_doc_type(doc_type: &str) -> Result<(), String> {
+    if DOC_TYPES.contains(&doc_type) {
+        Ok(())
+    } else {
+        Err(format!("Tipo di documento sconosciuto: {}", doc_type))
+    }
+}
+
+/// The bundled prompt with the placeholders where the extraction fills in its parts.
+pub fn default_template(doc_type: &str) -> String {
+    let base = if doc_type == "rechnung" {
+        PROMPT_RECHNUNG
+    } else {
+        PROMPT_AUFTRAG
+    };
+    format!(
+        "{}\n\n{}IMPORTANT LAYOUT-INFORMATION: {}\n\nContent document:\n{}",
+        base, PLACEHOLDER_EXAMPLES, PLACEHOLDER_LAYOUT, PLACEHOLDER_DOCUMENT
+    )
+}
+
+fn default_prompt(doc_type: &str) -> PromptTemplate {
+    PromptTemplate {
+        version: DEFAULT_PROMPT_VERSION,
+        template: default_template(doc_type),
+        custom: false,
+        updated_at: None,
+    }
+}
+
+fn load_stored(app: &AppHandle) -> Result<HashMap<String, PromptTemplate>, String> {
+    let store = workspace::store(app, "prompts.json")?;
+
+    match store.get("prompts") {
+        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn save_stored(app: &AppHandle, prompts: &HashMap<String, PromptTemplate>) -> Result<(), String> {
+    let store = workspace::store(app, "prompts.json")?;
+    store.set("prompts", json!(prompts));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+fn resolve(stored: Option<PromptTemplate>, doc_type: &str) -> PromptTemplate {
+    match stored {
+        Some(p) if p.custom => p,
+        // Non-custom entries only keep the edit counter, text and version follow the
+        // bundled prompt.
+        Some(p) => PromptTemplate {
+            updated_at: p.updated_at,
+            ..default_prompt(doc_type)
+        },
+        None => default_prompt(doc_type),
+    }
+}
+
+/// Returns the active prompt for a document type. Falls back to the bundled
+/// default if the store is missing or unreadable.
+pub fn load_prompt(app: &AppHandle, doc_type: &str) -> PromptTemplate {
+    let stored = load_stored(app).ok().and_then(|mut p| p.remove(doc_type));
+    resolve(stored, doc_type)
+}
+
+pub fn language_name(code: &str) -> Option<&'static str> {
+    match code {
+        "it" => Some("Italian"),
+        "de" => Some("German"),
+        "en" => Some("English"),
+        "fr" => Some("French"),
+        "es" => Some("Spanish"),
+        _ => None,
+    }
+}
+
+/// The language rule for the `{{LANGUAGE}}` placeholder. `"none"` keeps every value
+/// in the language of the document.
+pub fn language_instruction(code: &str) -> String {
+    let name = match language_name(code) {
+        Some(n) => n,
+        None => {
+            return "Do not translate anything. Keep all data, in particular product names, in the language and spelling of the document.".to_string()
+        }
+    };
+
+    let mut rule = format!(
+        "All data must be translated into {}. This applies in particular to product names: translate them word by word, i.e., each word separately, not the entire string at once.",
+        name
+    );
+    if code == "it" {
+        rule.push_str(" Example: from “CARDO MARIANO SEMEN” you make “CARDO MARIANO SEMI” and NOT “SEMI DI CARDO MARIANO”.");
+    }
+    rule.push_str(" \"produktOriginal\" is never translated.");
+    rule
+}
+
+/// The parts of a prompt that stay the same across the retries of one extraction.
+pub struct PromptContext<'a> {
+    pub examples: &'a str,
+    pub language: &'a str,
+    pub glossary: &'a str,
+}
+
+/// Custom prompts saved before `{{LANGUAGE}}` and `{{GLOSSARY}}` existed get those
+/// sections inserted ahead of the examples and layout, so the language rule and the
+/// glossary still reach the model.
+fn complete_sections(template: &str) -> String {
+    let missing: Vec<String> = [
+        ("Language", PLACEHOLDER_LANGUAGE),
+        ("GLOSSARY", PLACEHOLDER_GLOSSARY),
+    ]
+    .iter()
+    .filter(|(_, placeholder)| !template.contains(placeholder))
+    .map(|(label, placeholder)| format!("{}: {}\n\n", label, placeholder))
+    .collect();
+    if missing.is_empty() {
+        return template.to_string();
+    }
+
+    let at = [PLACEHOLDER_EXAMPLES, PLACEHOLDER_LAYOUT]
+        .iter()
+        .filter_map(|p| template.find(p))
+        .min()
+        .map(|i| template[..i].rfind('\n').map_or(0, |n| n + 1))
+        .unwrap_or(template.len());
+
+    let mut full = template.to_string();
+    full.insert_str(at, &missing.concat());
+    full
+}
+
+pub fn render(template: &str, context: &PromptContext, layout: &str, document: &str) -> String {
+    let template = complete_sections(template);
+    let examples = if context.examples.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n\n", context.examples)
+    };
+    template
+        .replace(PLACEHOLDER_EXAMPLES, &examples)
+        .replace(
+            PLACEHOLDER_LANGUAGE,
+            &language_instruction(context.language),
+        )
+        .replace(PLACEHOLDER_GLOSSARY, context.glossary)
+        .replace(PLACEHOLDER_LAYOUT, layout)
+        .replace(PLACEHOLDER_DOCUMENT, document)
+}
+
+#[command]
+pub async fn get_prompts(app: AppHandle) -> Result<HashMap<String, PromptTemplate>, String> {
+    let mut stored = load_stored(&app)?;
+    Ok(DOC_TYPES
+        .iter()
+        .map(|t| (t.to_string(), resolve(stored.remove(*t), t)))
+        .collect())
+}
+
+#[command]
+pub async fn save_prompt(
+    app: AppHandle,
+    doc_type: String,
+    template: String,
+) -> Result<PromptTemplate, String> {
+    store_prompt(&app, &doc_type, template)
+}
+
+pub fn check_template(doc_type: &str, template: &str) -> Result<(), String> {
+    check_doc_type(doc_type)?;
+    for placeholder in [PLACEHOLDER_LAYOUT, PLACEHOLDER_DOCUMENT] {
+        if !template.contains(placeholder) {
+            return Err(format!(
+                "Il prompt deve contenere il segnaposto {}.",
+                placeholder
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Saves a new text for a prompt, counting its version up if the text changed.
+pub fn store_prompt(
+    app: &AppHandle,
+    doc_type: &str,
+    template: String,
+) -> Result<PromptTemplate, String> {
+    check_template(doc_type, &template)?;
+
+    let mut stored = load_stored(app)?;
+    let last_version = stored.get(doc_type).map_or(0, |p| p.version);
+    let current = resolve(stored.get(doc_type).cloned(), doc_type);
+    if current.template == template {
+        return Ok(current);
+    }
+
+    let prompt = PromptTemplate {
+        version: last_version.max(current.version) + 1,
+        custom: template != default_template(doc_type),
+        template,
+        updated_at: Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+    };
+    stored.insert(doc_type.to_string(), prompt.clone());
+    save_stored(app, &stored)?;
+    Ok(resolve(Some(prompt), doc_type))
+}
+
+/// Turns the custom prompts of `targets` back into the bundled one. Their counter stays,
+/// so that a later custom text still gets a version no earlier text had.
+fn reset_stored(stored: &mut HashMap<String, PromptTemplate>, targets: &[String]) {
+    for t in targets {
+        if let Some(current) = stored.get_mut(t).filter(|p| p.custom) {
+            *current = PromptTemplate {
+                version: current.version,
+                template: default_template(t),
+                custom: false,
+                updated_at: Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+            };
+        }
+    }
+}
+
+/// Restores the bundled prompt for one document type, or for all of them if none is
+/// given. Results then report the bundled version again.
+#[command]
+pub async fn reset_prompts(
+    app: AppHandle,
+    doc_type: Option<String>,
+) -> Result<HashMap<String, PromptTemplate>, String> {
+    let targets: Vec<String> = match doc_type {
+        Some(t) => {
+            check_doc_type(&t)?;
+            vec![t]
+        }
+        None => DOC_TYPES.iter().map(|t| t.to_string()).collect(),
+    };
+
+    let mut stored = load_stored(&app)?;
+    reset_stored(&mut stored, &targets);
+    save_stored(&app, &stored)?;
+
+    get_prompts(app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(version: u32) -> PromptTemplate {
+        PromptTemplate {
+            version,
+            template: format!("Estrai gli ordini.\n{}", PLACEHOLDER_DOCUMENT),
+            custom: true,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn reset_reports_the_bundled_version() {
+        let mut stored = HashMap::from([("auftrag".to_string(), custom(7))]);
+        assert_eq!(
+            resolve(stored.get("auftrag").cloned(), "auftrag").version,
+            7
+        );
+
+        reset_stored(
+            &mut stored,
+            &["auftrag".to_string(), "rechnung".to_string()],
+        );
+
+        let entry = stored["auftrag"].clone();
+        assert!(!entry.custom);
+        assert_eq!(entry.version, 7);
+        let reported = resolve(Some(entry), "auftrag");
+        assert_eq!(reported.version, DEFAULT_PROMPT_VERSION);
+        assert_eq!(reported.template, default_template("auftrag"));
+        assert!(!stored.contains_key("rechnung"));
+        assert_eq!(resolve(None, "rechnung").version, DEFAULT_PROMPT_VERSION);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::modules::config::KEYRING_USER;
+use crate::modules::credentials::{self, DEFAULT_PROVIDER};
+use crate::modules::workspace;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use keyring::Entry;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager};
+
+const KEYRING_SERVICE: &str = "com.silas.raccolta-dati";
+const SECRETS_FILE: &str = "secrets.enc.json";
+const NONCE_LEN: usize = 12;
+
+/// Version 1 files hold values sealed with the machine-derived key, version 2 files
+/// values protected by DPAPI for the Windows user account.
+#[cfg(windows)]
+const FILE_VERSION: u32 = 2;
+#[cfg(not(windows))]
+const FILE_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SecretBackend {
+    Keyring,
+    File,
+    None,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStatus {
+    pub backend: SecretBackend,
+    pub keyring_available: bool,
+    pub file_path: Option<String>,
+    /// Whether the file fallback is encrypted with a key of the OS user (DPAPI). Without
+    /// it the file is only protected by its owner-only permissions.
+    pub file_encrypted: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct SecretsFile {
+    version: u32,
+    entries: HashMap<String, String>,
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cartella dati non disponibile: {}", e))?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Impossibile creare la cartella dati: {}", e))?;
+    Ok(dir.join(SECRETS_FILE))
+}
+
+/// Fallback key for systems without DPAPI. It is derived from this machine and OS user
+/// and holds no secret of its own, so it only keeps a copied file from being read
+/// elsewhere; the file itself is readable by its owner only.
+fn file_key() -> Key<Aes256Gcm> {
+    let machine = machine_uid::get().unwrap_or_else(|_| {
+        std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_default()
+    });
+    let user = std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEYRING_SERVICE.as_bytes());
+    hasher.update([0]);
+    hasher.update(machine.trim().as_bytes());
+    hasher.update([0]);
+    hasher.update(user.as_bytes());
+    hasher.finalize()
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn seal(value: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(&file_key());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, value)
+        .map_err(|_| "Cifratura non riuscita.".to_string())?;
+
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Ok(data)
+}
+
+fn unseal(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() <= NONCE_LEN {
+        return Err("Segreto non valido.".to_string());
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+    Aes256Gcm::new(&file_key())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            "Impossibile decifrare il segreto (creato su un altro computer o utente?)".to_string()
+        })
+}
+
+/// DPAPI ties the data to the Windows user account; the service name is mixed in as
+/// entropy so other programs of the same user cannot unprotect it by accident.
+#[cfg(windows)]
+mod dpapi {
+    use super::KEYRING_SERVICE;
+    use windows_sys::Win32::Foundation::LocalFree;
+    use windows_sys::Win32::Security::Cryptography::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+        CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        }
+    }
+
+    fn take(output: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+        // SAFETY: on success DPAPI returns a LocalAlloc buffer of `cbData` bytes that
+        // the caller must free.
+        unsafe {
+            let data = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+            LocalFree(output.pbData as _);
+            data
+        }
+    }
+
+    pub fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+        let input = blob(data);
+        let entropy = blob(KEYRING_SERVICE.as_bytes());
+        let mut output = blob(&[]);
+        // SAFETY: all pointers refer to live buffers for the duration of the call.
+        let ok = unsafe {
+            CryptProtectData(
+                &input,
+                std::ptr::null(),
+                &entropy,
+                std::ptr::null(),
+                std::ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )
+        };
+        if ok == 0 {
+            return Err(format!(
+                "Cifratura non riuscita: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(take(output))
+    }
+
+    pub fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+        let input = blob(data);
+        let entropy = blob(KEYRING_SERVICE.as_bytes());
+        let mut output = blob(&[]);
+        // SAFETY: all pointers refer to live buffers for the duration of the call.
+        let ok = unsafe {
+            CryptUnprotectData(
+                &input,
+                std::ptr::null_mut(),
+                &entropy,
+                std::ptr::null(),
+                std::ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )
+        };
+        if ok == 0 {
+            return Err(
+                "Impossibile decifrare il segreto (creato su un altro computer o utente?)"
+                    .to_string(),
+            );
+        }
+        Ok(take(output))
+    }
+}
+
+#[cfg(windows)]
+fn protect(value: &[u8]) -> Result<Vec<u8>, String> {
+    dpapi::protect(value)
+}
+
+#[cfg(not(windows))]
+fn protect(value: &[u8]) -> Result<Vec<u8>, String> {
+    seal(value)
+}
+
+fn encrypt(value: &str) -> Result<String, String> {
+    Ok(general_purpose::STANDARD.encode(protect(value.as_bytes())?))
+}
+
+fn decrypt(encoded: &str, version: u32) -> Result<String, String> {
+    let data = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Segreto non valido: {}", e))?;
+
+    let plain = if version < 2 {
+        unseal(&data)?
+    } else {
+        #[cfg(windows)]
+        {
+            dpapi::unprotect(&data)?
+        }
+        #[cfg(not(windows))]
+        {
+            return Err("Il segreto è protetto con DPAPI e si legge solo su Windows.".to_string());
+        }
+    };
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Writes the file so that only its owner can read it. On Windows the data folder in
+/// the user profile already grants access to the owner only.
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // Files written by older versions keep their mode on open, so tighten it first.
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+fn load_file(app: &AppHandle) -> SecretsFile {
+    secrets_path(app)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn save_file(app: &AppHandle, file: &SecretsFile) -> Result<(), String> {
+    let path = secrets_path(app)?;
+    if file.entries.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| format!("Errore di scrittura: {}", e))?;
+        }
+        return Ok(());
+    }
+    let content = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+    write_private(&path, &content).map_err(|e| format!("Errore di scrittura: {}", e))
+}
+
+fn file_read(app: &AppHandle, name: &str) -> Option<String> {
+    let mut file = load_file(app);
+    let encoded = file.entries.remove(name)?;
+    match decrypt(&encoded, file.version) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            println!("⚠️ {}", e);
+            None
+        }
+    }
+}
+
+fn file_write(app: &AppHandle, name: &str, value: Option<&str>) -> Result<(), String> {
+    let mut file = load_file(app);
+    if file.version != FILE_VERSION {
+        // Re-protect the values written by an older version with the current scheme.
+        let version = file.version;
+        file.entries = std::mem::take(&mut file.entries)
+            .into_iter()
+            .filter_map(|(key, encoded)| match decrypt(&encoded, version) {
+                Ok(plain) => Some(encrypt(&plain).map(|e| (key, e))),
+                Err(e) => {
+                    println!("⚠️ Segreto {} scartato: {}", key, e);
+                    None
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        file.version = FILE_VERSION;
+    }
+    match value {
+        Some(v) => {
+            file.entries.insert(name.to_string(), encrypt(v)?);
+        }
+        None => {
+            if file.entries.remove(name).is_none() {
+                return Ok(());
+            }
+        }
+    }
+    save_file(app, &file)
+}
+
+/// `Ok(None)` means the keyring works but has no entry; `Err` means it is unusable.
+fn keyring_read(name: &str) -> Result<Option<String>, String> {
+    let entry = Entry::new(KEYRING_SERVICE, name).map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn keyring_write(name: &str, value: &str) -> Result<(), String> {
+    Entry::new(KEYRING_SERVICE, name)
+        .and_then(|entry| entry.set_password(value))
+        .map_err(|e| e.to_string())
+}
+
+fn keyring_delete(name: &str) {
+    if let Ok(entry) = Entry::new(KEYRING_SERVICE, name) {
+        let _ = entry.delete_credential();
+    }
+}
+
+/// Stores a secret in the keyring, or in the secrets file if the keyring is not
+/// usable. An empty value deletes the secret from both.
+pub fn store_secret(app: &AppHandle, name: &str, value: &str) -> Result<SecretBackend, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        keyring_delete(name);
+        file_write(app, name, None)?;
+        return Ok(SecretBackend::None);
+    }
+
+    match keyring_write(name, value) {
+        Ok(()) => {
+            file_write(app, name, None)?;
+            Ok(SecretBackend::Keyring)
+        }
+        Err(e) => {
+            println!(
+                "⚠️ Errore di scrittura del portachiavi: {}. Utilizza il file dei segreti...",
+                e
+            );
+            file_write(app, name, Some(value))?;
+            Ok(SecretBackend::File)
+        }
+    }
+}
+
+/// Reads a secret from the keyring or the secrets file. A secret found in the file
+/// moves into the keyring as soon as the keyring works again.
+pub fn read_secret(app: &AppHandle, name: &str) -> (Option<String>, SecretBackend) {
+    let keyring = keyring_read(name);
+    if let Ok(Some(value)) = keyring {
+        return (Some(value), SecretBackend::Keyring);
+    }
+
+    match file_read(app, name) {
+        Some(value) if keyring.is_ok() => match keyring_write(name, &value) {
+            Ok(()) => {
+                let _ = file_write(app, name, None);
+                (Some(value), SecretBackend::Keyring)
+            }
+            Err(_) => (Some(value), SecretBackend::File),
+        },
+        Some(value) => (Some(value), SecretBackend::File),
+        None => (None, SecretBackend::None),
+    }
+}
+
+/// Moves a plain-text key left in `settings.json` by older versions into secure storage.
+pub fn migrate_plaintext(app: &AppHandle, setting: &str, name: &str) -> Result<(), String> {
+    let store = workspace::store(app, "settings.json")?;
+    let value = match store
+        .get(setting)
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+    {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    if !value.trim().is_empty() && read_secret(app, name).0.is_none() {
+        store_secret(app, name, &value)?;
+    }
+    store.delete(setting);
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+pub fn status(app: &AppHandle, name: &str) -> SecretStatus {
+    let (_, backend) = read_secret(app, name);
+    SecretStatus {
+        backend,
+        keyring_available: keyring_read(name).is_ok(),
+        file_path: secrets_path(app)
+            .ok()
+            .filter(|p| p.exists())
+            .map(|p| p.display().to_string()),
+        file_encrypted: cfg!(windows),
+    }
+}
+
+#[command]
+pub async fn get_secret_status(app: AppHandle) -> Result<SecretStatus, String> {
+    let name = credentials::active_secret_id(&app, DEFAULT_PROVIDER)
+        .unwrap_or_else(|| KEYRING_USER.to_string());
+    Ok(status(&app, &name))
+}
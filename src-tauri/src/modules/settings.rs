@@ -0,0 +1,332 @@
+use crate::modules::config::KEYRING_USER;
+use crate::modules::excel_columns::ExcelColumns;
+use crate::modules::utils::column_index;
+use crate::modules::{policy, prompts, secrets, workspace};
+
+use serde_json::{Map, Value};
+use std::path::Path;
+use tauri::{command, AppHandle};
+
+/// Bumped whenever a stored key is renamed or changes its type; see `MIGRATIONS`.
+pub const SCHEMA_VERSION: u32 = 2;
+pub const MAX_CONCURRENCY: u32 = 15;
+const THEMES: [&str; 2] = ["dark", "light"];
+const SHEET_MODES: [&str; 3] = ["first", "name", "orderYear"];
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilenameRules {
+    pub invoice_prefix: String,
+    pub separator: String,
+}
+
+impl Default for FilenameRules {
+    fn default() -> Self {
+        FilenameRules {
+            invoice_prefix: "FT".to_string(),
+            separator: "_".to_string(),
+        }
+    }
+}
+
+/// Which worksheet the export writes to: the first one, the one called `name`, or the
+/// one named after the year of each order.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SheetRules {
+    pub mode: String,
+    pub name: String,
+    /// Name of the yearly sheets, `{year}` stands for the year of the order.
+    pub year_pattern: String,
+    /// Looks for the order of an invoice in every sheet with a recognised header, since
+    /// invoices often arrive in the year after the order.
+    pub search_all_sheets: bool,
+}
+
+impl Default for SheetRules {
+    fn default() -> Self {
+        SheetRules {
+            mode: "first".to_string(),
+            name: String::new(),
+            year_pattern: "{year}".to_string(),
+            search_all_sheets: false,
+        }
+    }
+}
+
+/// The settings of the active workspace. Every field is stored under its own key in
+/// `settings.json`, so the frontend can still read single values from the store.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub schema_version: u32,
+    /// Parallel AI requests; 0 processes the documents one after another with pauses.
+    pub concurrency_limit: u32,
+    pub default_pdf_path: Option<String>,
+    pub default_excel_path: Option<String>,
+    pub default_processed_pdf_path: Option<String>,
+    pub default_theme: String,
+    pub move_files_enabled: bool,
+    pub auto_open_excel: bool,
+    pub target_language: String,
+    pub original_name_column: Option<String>,
+    pub original_name_in_notes: bool,
+    pub shared_corrections_path: Option<String>,
+    /// Corrections made in the table apply only to the supplier of the row instead of
+    /// to every supplier.
+    pub supplier_scoped_corrections: bool,
+    pub filename_rules: FilenameRules,
+    pub excel_columns: ExcelColumns,
+    pub sheet_rules: SheetRules,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            schema_version: SCHEMA_VERSION,
+            concurrency_limit: 5,
+            default_pdf_path: None,
+            default_excel_path: None,
+            default_processed_pdf_path: None,
+            default_theme: "dark".to_string(),
+            move_files_enabled: true,
+            auto_open_excel: false,
+            target_language: prompts::DEFAULT_LANGUAGE.to_string(),
+            original_name_column: None,
+            original_name_in_notes: false,
+            shared_corrections_path: None,
+            supplier_scoped_corrections: false,
+            filename_rules: FilenameRules::default(),
+            excel_columns: ExcelColumns::default(),
+            sheet_rules: SheetRules::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.concurrency_limit > MAX_CONCURRENCY {
+            return Err(format!(
+                "Il numero di processi paralleli deve essere tra 0 e {}.",
+                MAX_CONCURRENCY
+            ));
+        }
+        policy::check_concurrency(self.concurrency_limit)?;
+        for path in [&self.default_excel_path, &self.default_processed_pdf_path]
+            .into_iter()
+            .flatten()
+        {
+            policy::check_export_path(Path::new(path))?;
+        }
+        if !THEMES.contains(&self.default_theme.as_str()) {
+            return Err(format!("Tema sconosciuto: {}", self.default_theme));
+        }
+        if self.target_language != "none" && prompts::language_name(&self.target_language).is_none()
+        {
+            return Err(format!("Lingua sconosciuta: {}", self.target_language));
+        }
+        if let Some(column) = &self.original_name_column {
+            if column_index(column).is_none() {
+                return Err(format!(
+                    "Colonna non valida per il nome originale: {}",
+                    column
+                ));
+            }
+        }
+        self.excel_columns.validate()?;
+        match self.sheet_rules.mode.as_str() {
+            "name" if self.sheet_rules.name.is_empty() => {
+                return Err("Indicare il nome del foglio Excel.".to_string())
+            }
+            "orderYear" if !self.sheet_rules.year_pattern.contains("{year}") => {
+                return Err("Il nome dei fogli annuali deve contenere {year}.".to_string())
+            }
+            mode if !SHEET_MODES.contains(&mode) => {
+                return Err(format!("Regola del foglio sconosciuta: {}", mode))
+            }
+            _ => {}
+        }
+        if self.filename_rules.separator.is_empty() {
+            return Err("Il separatore dei nomi file non può essere vuoto.".to_string());
+        }
+        if self
+            .filename_rules
+            .invoice_prefix
+            .contains(&self.filename_rules.separator)
+        {
+            return Err("Il prefisso delle fatture non può contenere il separatore.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Trims text values and turns empty ones into `None`, the way the frontend fields
+    /// are meant to be read.
+    fn normalized(mut self) -> Self {
+        let clean = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        self.default_pdf_path = clean(self.default_pdf_path);
+        self.default_excel_path = clean(self.default_excel_path);
+        self.default_processed_pdf_path = clean(self.default_processed_pdf_path);
+        self.shared_corrections_path = clean(self.shared_corrections_path);
+        self.original_name_column = clean(self.original_name_column).map(|c| c.to_uppercase());
+        self.filename_rules.invoice_prefix =
+            self.filename_rules.invoice_prefix.trim().to_uppercase();
+        self.sheet_rules.name = self.sheet_rules.name.trim().to_string();
+        self.sheet_rules.year_pattern = self.sheet_rules.year_pattern.trim().to_string();
+        for synonyms in self.excel_columns.header_synonyms.values_mut() {
+            *synonyms = synonyms
+                .iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        self
+    }
+}
+
+type Migration = fn(&AppHandle, &mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades stored settings from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [migrate_v0_api_key, migrate_v1_types];
+
+/// Version 0 kept the API key in plain text under `apiKey`.
+fn migrate_v0_api_key(app: &AppHandle, values: &mut Map<String, Value>) {
+    if values.contains_key("apiKey") {
+        if let Err(e) = secrets::migrate_plaintext(app, "apiKey", KEYRING_USER) {
+            println!("⚠️ Migrazione della chiave API non riuscita: {}", e);
+            return;
+        }
+        values.remove("apiKey");
+    }
+}
+
+/// Version 1 stored whatever the form fields returned: numbers as strings, empty
+/// strings for unset paths and `null` for untouched toggles.
+fn migrate_v1_types(_app: &AppHandle, values: &mut Map<String, Value>) {
+    if let Some(Value::String(s)) = values.get("concurrencyLimit") {
+        match s.trim().parse::<u32>() {
+            Ok(n) => values.insert("concurrencyLimit".to_string(), Value::from(n)),
+            Err(_) => values.remove("concurrencyLimit"),
+        };
+    }
+    values.retain(|_, v| !v.is_null() && v.as_str().is_none_or(|s| !s.trim().is_empty()));
+}
+
+fn to_settings(values: Map<String, Value>) -> Settings {
+    // Fields are read one by one so that a single bad value falls back to its default
+    // instead of discarding the whole settings.
+    let defaults = serde_json::to_value(Settings::default()).unwrap_or_default();
+    let mut merged = defaults.as_object().cloned().unwrap_or_default();
+    for (key, value) in values {
+        if !merged.contains_key(&key) {
+            continue;
+        }
+        let mut candidate = merged.clone();
+        candidate.insert(key.clone(), value.clone());
+        if serde_json::from_value::<Settings>(Value::Object(candidate)).is_ok() {
+            merged.insert(key, value);
+        } else {
+            println!(
+                "⚠️ Impostazione {} non valida, uso il valore predefinito.",
+                key
+            );
+        }
+    }
+    serde_json::from_value(Value::Object(merged)).unwrap_or_default()
+}
+
+fn write(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let store = workspace::store(app, "settings.json")?;
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    for (key, value) in value.as_object().cloned().unwrap_or_default() {
+        if value.is_null() {
+            store.delete(&key);
+        } else {
+            store.set(key, value);
+        }
+    }
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+fn stored_version(values: &Map<String, Value>) -> usize {
+    values
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as usize
+}
+
+/// Builds settings from stored values of any schema version, running the migrations
+/// the values still need.
+pub fn from_values(app: &AppHandle, mut values: Map<String, Value>) -> Settings {
+    let version = stored_version(&values);
+    for migration in MIGRATIONS.iter().skip(version) {
+        migration(app, &mut values);
+    }
+    let mut settings = to_settings(values).normalized();
+    settings.schema_version = SCHEMA_VERSION;
+    settings.concurrency_limit = policy::clamp_concurrency(settings.concurrency_limit);
+    settings
+}
+
+/// Validates and saves the settings of the active workspace.
+pub fn save(app: &AppHandle, settings: Settings) -> Result<Settings, String> {
+    let mut settings = settings.normalized();
+    settings.schema_version = SCHEMA_VERSION;
+    settings.validate()?;
+    write(app, &settings)?;
+    Ok(settings)
+}
+
+/// Loads the settings of the active workspace, running any pending migrations first.
+pub fn load(app: &AppHandle) -> Settings {
+    let store = match workspace::store(app, "settings.json") {
+        Ok(s) => s,
+        Err(e) => {
+            println!("Impostazioni non disponibili: {}", e);
+            return Settings::default();
+        }
+    };
+    let values: Map<String, Value> = store.entries().into_iter().collect();
+    let outdated = stored_version(&values) < SCHEMA_VERSION as usize;
+
+    let settings = from_values(app, values);
+    if !outdated {
+        return settings;
+    }
+    if let Err(e) = write(app, &settings) {
+        println!("Impossibile salvare le impostazioni migrate: {}", e);
+    }
+    settings
+}
+
+#[command]
+pub async fn get_settings(app: AppHandle) -> Result<Settings, String> {
+    Ok(load(&app))
+}
+
+/// Applies the given fields on top of the current settings. Nothing is saved if the
+/// result does not validate.
+#[command]
+pub async fn update_settings(app: AppHandle, patch: Value) -> Result<Settings, String> {
+    let patch = match patch {
+        Value::Object(map) => map,
+        _ => return Err("Formato delle impostazioni non valido.".to_string()),
+    };
+
+    let mut merged = serde_json::to_value(load(&app))
+        .map_err(|e| e.to_string())?
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    for (key, value) in patch {
+        if !merged.contains_key(&key) {
+            return Err(format!("Impostazione sconosciuta: {}", key));
+        }
+        merged.insert(key, value);
+    }
+
+    let settings: Settings = serde_json::from_value(Value::Object(merged))
+        .map_err(|e| format!("Impostazioni non valide: {}", e))?;
+    save(&app, settings)
+}
@@ -0,0 +1,356 @@
+use crate::modules::ai::run_sidecar;
+use crate::modules::utils::format_to_uppercase;
+use crate::modules::workspace;
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use tauri::command;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSpec {
+    pub field: String,
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractionTemplate {
+    pub name: String,
+    pub supplier: String,
+    pub doc_type: String,
+    #[serde(default)]
+    pub detect: Vec<String>,
+    #[serde(default)]
+    pub start_anchor: Option<String>,
+    #[serde(default)]
+    pub end_anchor: Option<String>,
+    #[serde(default)]
+    pub line_pattern: Option<String>,
+    #[serde(default)]
+    pub columns: Vec<ColumnSpec>,
+    #[serde(default)]
+    pub invoice_number: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateTestResult {
+    pub text: String,
+    pub detected: bool,
+    pub matched_lines: Vec<usize>,
+    pub result: Value,
+}
+
+const PRODUCT_FIELDS: [&str; 5] = ["produkt", "menge", "gelieferteMenge", "preis", "waehrung"];
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Espressione regolare non valida '{}': {}", pattern, e))
+}
+
+/// Parses numbers as they appear in supplier documents: "1.234,56", "1,234.56", "2 , 5 5".
+pub fn parse_number(raw: &str) -> Option<f64> {
+    let cleaned: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == ',' || *c == '.' || *c == '-')
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+
+    let normalized = match (cleaned.rfind(','), cleaned.rfind('.')) {
+        (Some(c), Some(d)) if c > d => cleaned.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => cleaned.replace(',', ""),
+        (Some(_), None) => cleaned.replace(',', "."),
+        (None, Some(d))
+            if cleaned.len() - d - 1 == 3
+                && (cleaned.matches('.').count() > 1 || !cleaned.starts_with('0')) =>
+        {
+            cleaned.replace('.', "")
+        }
+        _ => cleaned,
+    };
+
+    normalized.parse::<f64>().ok()
+}
+
+fn slice_chars(line: &str, start: usize, end: Option<usize>) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let end = end.unwrap_or(chars.len()).min(chars.len());
+    if start >= end {
+        return String::new();
+    }
+    chars[start..end]
+        .iter()
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn set_field(product: &mut Map<String, Value>, field: &str, raw: &str) {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return;
+    }
+    match field {
+        "produkt" | "waehrung" => {
+            let text = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+            if field == "produkt" {
+                product.insert("produktOriginal".to_string(), json!(text));
+            }
+            product.insert(field.to_string(), json!(text));
+        }
+        _ => {
+            if let Some(n) = parse_number(raw) {
+                product.insert(field.to_string(), json!(n));
+            }
+        }
+    }
+}
+
+fn is_detected(template: &ExtractionTemplate, text: &str) -> bool {
+    template
+        .detect
+        .iter()
+        .filter_map(|p| Regex::new(p).ok())
+        .any(|re| re.is_match(text))
+}
+
+/// Runs a template over `pdftotext -layout` output and returns the result in the same
+/// shape the LLM prompts produce, together with the indices of the lines that matched.
+pub fn apply(template: &ExtractionTemplate, text: &str) -> Result<(Value, Vec<usize>), String> {
+    let start_re = template.start_anchor.as_deref().map(compile).transpose()?;
+    let end_re = template.end_anchor.as_deref().map(compile).transpose()?;
+    let line_re = template.line_pattern.as_deref().map(compile).transpose()?;
+
+    if line_re.is_none() && template.columns.is_empty() {
+        return Err(format!(
+            "Il modello '{}' non definisce né linePattern né colonne.",
+            template.name
+        ));
+    }
+
+    let mut in_table = start_re.is_none();
+    let mut produkte = Vec::new();
+    let mut matched_lines = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        if !in_table {
+            if start_re.as_ref().is_some_and(|re| re.is_match(line)) {
+                in_table = true;
+            }
+            continue;
+        }
+        if end_re.as_ref().is_some_and(|re| re.is_match(line)) {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut product = Map::new();
+        if let Some(re) = &line_re {
+            let caps = match re.captures(line) {
+                Some(c) => c,
+                None => continue,
+            };
+            for field in PRODUCT_FIELDS {
+                if let Some(m) = caps.name(field) {
+                    set_field(&mut product, field, m.as_str());
+                }
+            }
+        } else {
+            for col in &template.columns {
+                set_field(
+                    &mut product,
+                    &col.field,
+                    &slice_chars(line, col.start, col.end),
+                );
+            }
+        }
+
+        let has_name = product.get("produkt").is_some();
+        let has_number = ["menge", "gelieferteMenge", "preis"]
+            .iter()
+            .any(|f| product.get(*f).is_some());
+        if !has_name || !has_number {
+            continue;
+        }
+
+        if let Some(currency) = &template.currency {
+            if template.doc_type != "rechnung" {
+                product.entry("waehrung").or_insert_with(|| json!(currency));
+            }
+        }
+        produkte.push(Value::Object(product));
+        matched_lines.push(idx + 1);
+    }
+
+    let mut result = json!({ "produkte": produkte });
+    if template.doc_type == "rechnung" {
+        let number = match &template.invoice_number {
+            Some(p) => compile(p)?
+                .captures(text)
+                .and_then(|c| c.get(1).or_else(|| c.get(0)))
+                .map(|m| m.as_str().trim().to_string()),
+            None => None,
+        };
+        result["nummerRechnung"] = json!(number);
+    }
+
+    Ok((result, matched_lines))
+}
+
+pub fn load_templates(app: &tauri::AppHandle) -> Result<Vec<ExtractionTemplate>, String> {
+    let store = workspace::store(app, "templates.json")?;
+
+    match store.get("templates") {
+        Some(val) => serde_json::from_value(val).map_err(|e| format!("Parse errore: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn save_templates(
+    app: &tauri::AppHandle,
+    templates: &[ExtractionTemplate],
+) -> Result<(), String> {
+    let store = workspace::store(app, "templates.json")?;
+    store.set("templates", json!(templates));
+    store
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))
+}
+
+/// Tries the stored templates before any LLM call. A template is chosen by the
+/// filename-derived supplier first, then by its detection patterns.
+pub async fn try_templates(
+    app: &tauri::AppHandle,
+    path: &str,
+    doc_type: &str,
+    supplier: Option<&str>,
+) -> Option<Value> {
+    let templates: Vec<ExtractionTemplate> = load_templates(app)
+        .ok()?
+        .into_iter()
+        .filter(|t| t.doc_type == doc_type)
+        .collect();
+    if templates.is_empty() {
+        return None;
+    }
+
+    let text = run_sidecar(app, path, true).await.ok()?;
+    let supplier = supplier
+        .map(|s| s.trim().to_lowercase())
+        .unwrap_or_default();
+
+    let template = templates
+        .iter()
+        .find(|t| !supplier.is_empty() && t.supplier.trim().to_lowercase() == supplier)
+        .or_else(|| templates.iter().find(|t| is_detected(t, &text)))?;
+
+    match apply(template, &text) {
+        Ok((mut result, _)) => {
+            let found = result
+                .get("produkte")
+                .and_then(|p| p.as_array())
+                .is_some_and(|a| !a.is_empty());
+            if !found {
+                println!("Il modello '{}' non ha trovato prodotti", template.name);
+                return None;
+            }
+            format_to_uppercase(&mut result);
+            result["meta"] = json!({ "source": "template", "template": template.name });
+            Some(result)
+        }
+        Err(e) => {
+            println!("Modello '{}' non applicabile: {}", template.name, e);
+            None
+        }
+    }
+}
+
+pub fn validate(template: &ExtractionTemplate) -> Result<(), String> {
+    if template.name.trim().is_empty() || template.supplier.trim().is_empty() {
+        return Err("Nome del modello e fornitore sono obbligatori.".to_string());
+    }
+    for pattern in template
+        .detect
+        .iter()
+        .chain(template.start_anchor.iter())
+        .chain(template.end_anchor.iter())
+        .chain(template.line_pattern.iter())
+        .chain(template.invoice_number.iter())
+    {
+        compile(pattern)?;
+    }
+    for col in &template.columns {
+        if !PRODUCT_FIELDS.contains(&col.field.as_str()) {
+            return Err(format!(
+                "Campo sconosciuto nelle colonne del modello: {} (ammessi: {})",
+                col.field,
+                PRODUCT_FIELDS.join(", ")
+            ));
+        }
+        if col.end.is_some_and(|end| end <= col.start) {
+            return Err(format!(
+                "La colonna {} finisce prima di iniziare ({}-{}).",
+                col.field,
+                col.start,
+                col.end.unwrap_or_default()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn get_templates(app: tauri::AppHandle) -> Result<Vec<ExtractionTemplate>, String> {
+    load_templates(&app)
+}
+
+#[command]
+pub async fn save_template(
+    app: tauri::AppHandle,
+    template: ExtractionTemplate,
+) -> Result<(), String> {
+    validate(&template)?;
+
+    let mut templates = load_templates(&app)?;
+    match templates.iter_mut().find(|t| t.name == template.name) {
+        Some(existing) => *existing = template,
+        None => templates.push(template),
+    }
+    save_templates(&app, &templates)
+}
+
+#[command]
+pub async fn remove_template(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    let mut templates = load_templates(&app)?;
+    let before = templates.len();
+    templates.retain(|t| t.name != name);
+    if templates.len() != before {
+        save_templates(&app, &templates)?;
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn test_template(
+    app: tauri::AppHandle,
+    path: String,
+    template: ExtractionTemplate,
+) -> Result<TemplateTestResult, String> {
+    validate(&template)?;
+    let text = run_sidecar(&app, &path, true).await?;
+    let (result, matched_lines) = apply(&template, &text)?;
+
+    Ok(TemplateTestResult {
+        detected: is_detected(&template, &text),
+        text,
+        matched_lines,
+        result,
+    })
+}
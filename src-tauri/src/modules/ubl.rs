@@ -0,0 +1,171 @@
+use chrono::NaiveDate;
+use roxmltree::{Document, Node};
+use serde_json::{json, Value};
+use std::fs;
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn descend<'a, 'input>(node: Node<'a, 'input>, path: &[&str]) -> Option<Node<'a, 'input>> {
+    path.iter()
+        .try_fold(node, |current, name| child(current, name))
+}
+
+fn text_at(node: Node, path: &[&str]) -> Option<String> {
+    descend(node, path)
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+fn number_at(node: Node, path: &[&str]) -> Option<f64> {
+    text_at(node, path).and_then(|t| t.parse::<f64>().ok())
+}
+
+fn format_date(iso: Option<String>) -> Option<String> {
+    iso.map(|d| match NaiveDate::parse_from_str(&d, "%Y-%m-%d") {
+        Ok(parsed) => parsed.format("%d/%m/%Y").to_string(),
+        Err(_) => d,
+    })
+}
+
+fn party_name(root: Node, party_tags: &[&str]) -> Option<String> {
+    party_tags.iter().find_map(|tag| {
+        let party = descend(root, &[tag, "Party"])?;
+        text_at(party, &["PartyName", "Name"])
+            .or_else(|| text_at(party, &["PartyLegalEntity", "RegistrationName"]))
+    })
+}
+
+struct UblLine {
+    name: Option<String>,
+    qty: Option<f64>,
+    price: Option<f64>,
+    currency: Option<String>,
+}
+
+fn read_line(line: Node, qty_tag: &str, doc_currency: &Option<String>) -> UblLine {
+    let item = child(line, "Item");
+    let name = item.and_then(|i| text_at(i, &["Name"]).or_else(|| text_at(i, &["Description"])));
+    let qty = number_at(line, &[qty_tag]);
+
+    let price_node = descend(line, &["Price", "PriceAmount"]);
+    let base_qty = number_at(line, &["Price", "BaseQuantity"]).filter(|q| *q > 0.0);
+
+    let price = match price_node
+        .and_then(|n| n.text())
+        .and_then(|t| t.trim().parse::<f64>().ok())
+    {
+        Some(amount) => Some(amount / base_qty.unwrap_or(1.0)),
+        None => match (number_at(line, &["LineExtensionAmount"]), qty) {
+            (Some(total), Some(q)) if q > 0.0 => Some(total / q),
+            _ => None,
+        },
+    };
+
+    let currency = price_node
+        .and_then(|n| n.attribute("currencyID"))
+        .map(|c| c.to_string())
+        .or_else(|| doc_currency.clone());
+
+    UblLine {
+        name,
+        qty,
+        price: price.map(|p| (p * 10000.0).round() / 10000.0),
+        currency,
+    }
+}
+
+pub fn parse_document(path: &str) -> Result<Value, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Impossibile leggere il file: {}", e))?;
+    parse_str(&content)
+}
+
+pub fn parse_str(content: &str) -> Result<Value, String> {
+    let doc = Document::parse(content).map_err(|e| format!("XML non valido: {}", e))?;
+    let root = doc.root_element();
+    let doc_currency = text_at(root, &["DocumentCurrencyCode"]);
+    let own_id = text_at(root, &["ID"]);
+    let order_ref = text_at(root, &["OrderReference", "ID"]);
+    let issue_date = format_date(text_at(root, &["IssueDate"]));
+
+    match root.tag_name().name() {
+        "Invoice" | "CreditNote" => {
+            let (line_tag, qty_tag) = if root.tag_name().name() == "Invoice" {
+                ("InvoiceLine", "InvoicedQuantity")
+            } else {
+                ("CreditNoteLine", "CreditedQuantity")
+            };
+
+            let produkte: Vec<Value> = children(root, line_tag)
+                .map(|l| read_line(l, qty_tag, &doc_currency))
+                .filter(|l| l.name.is_some())
+                .map(|l| {
+                    json!({
+                        "produkt": l.name,
+                        "produktOriginal": l.name,
+                        "gelieferteMenge": l.qty,
+                        "preis": l.price,
+                        "waehrung": l.currency,
+                    })
+                })
+                .collect();
+
+            Ok(json!({
+                "docType": "rechnung",
+                "produkte": produkte,
+                "nummerRechnung": own_id,
+                "nummerAuftrag": order_ref,
+                "datumRechnung": issue_date,
+                "lieferant": party_name(root, &["AccountingSupplierParty"]),
+                "kunde": party_name(root, &["AccountingCustomerParty"]),
+            }))
+        }
+        "Order" | "OrderResponse" | "OrderResponseSimple" => {
+            let produkte: Vec<Value> = children(root, "OrderLine")
+                .filter_map(|l| child(l, "LineItem"))
+                .map(|l| read_line(l, "Quantity", &doc_currency))
+                .filter(|l| l.name.is_some())
+                .map(|l| {
+                    json!({
+                        "produkt": l.name,
+                        "produktOriginal": l.name,
+                        "menge": l.qty,
+                        "waehrung": l.currency,
+                        "preis": l.price,
+                    })
+                })
+                .collect();
+
+            let order_number = if root.tag_name().name() == "Order" {
+                own_id
+            } else {
+                order_ref.or(own_id)
+            };
+
+            Ok(json!({
+                "docType": "auftrag",
+                "produkte": produkte,
+                "nummerAuftrag": order_number,
+                "datumAuftrag": issue_date,
+                "lieferant": party_name(root, &["SellerSupplierParty", "AccountingSupplierParty"]),
+                "kunde": party_name(root, &["BuyerCustomerParty", "AccountingCustomerParty"]),
+            }))
+        }
+        other => Err(format!(
+            "Documento XML non supportato: '{}'. Sono accettati solo UBL Invoice, CreditNote, Order e OrderResponse.",
+            other
+        )),
+    }
+}
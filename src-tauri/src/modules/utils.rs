@@ -1,6 +1,7 @@
 use chrono::NaiveDate;
 use regex::Regex;
 use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
 use tauri::command;
 
 #[command]
@@ -9,6 +10,7 @@ pub async fn copy_files(file_paths: Vec<String>, target_dir: String) -> Result<(
     use std::path::Path;
 
     let target_path = Path::new(&target_dir);
+    crate::modules::policy::check_export_path(target_path)?;
     if !target_path.exists() {
         return Err("La cartella di destinazione non esiste.".to_string());
     }
@@ -24,6 +26,7 @@ pub async fn copy_files(file_paths: Vec<String>, target_dir: String) -> Result<(
     Ok(())
 }
 
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 pub fn close_excel_if_open(path: &str) {
     #[cfg(target_os = "windows")]
     {
@@ -73,11 +76,11 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
 
     let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
 
-    for i in 0..=len1 {
-        matrix[i][0] = i;
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[0] = i;
     }
-    for j in 0..=len2 {
-        matrix[0][j] = j;
+    for (j, cell) in matrix[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     for i in 0..len1 {
@@ -145,6 +148,29 @@ pub fn token_similarity(s1: &str, s2: &str) -> f64 {
     }
 }
 
+/// Unit words that, like numbers, tell product sizes apart.
+const UNIT_TOKENS: [&str; 14] = [
+    "G", "GR", "KG", "MG", "ML", "CL", "L", "LT", "PZ", "ST", "STK", "STUCK", "X", "CM",
+];
+
+/// The words of a name that carry a quantity: numbers, "1000G", and unit words.
+fn quantity_tokens(name: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = name
+        .to_uppercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().any(|c| c.is_ascii_digit()) || UNIT_TOKENS.contains(t))
+        .map(str::to_string)
+        .collect();
+    tokens.sort_unstable();
+    tokens
+}
+
+/// Whether two names state the same quantities. "ORTICA FOGLIE 1000G" is close to
+/// "ORTICA FOGLIE 2000G" by spelling, but it is a different article.
+pub fn same_quantities(a: &str, b: &str) -> bool {
+    quantity_tokens(a) == quantity_tokens(b)
+}
+
 pub fn parse_date(date_str: &str) -> Option<NaiveDate> {
     if let Ok(d) = NaiveDate::parse_from_str(date_str, "%d/%m/%Y") {
         return Some(d);
@@ -172,7 +198,11 @@ pub fn format_to_uppercase(v: &mut Value) {
             }
         }
         Value::Object(obj) => {
-            for val in obj.values_mut() {
+            for (key, val) in obj.iter_mut() {
+                // The untranslated name must stay exactly as the supplier wrote it.
+                if key == "produktOriginal" {
+                    continue;
+                }
                 format_to_uppercase(val);
             }
         }
@@ -180,9 +210,95 @@ pub fn format_to_uppercase(v: &mut Value) {
     }
 }
 
+/// Converts an Excel column letter ("A", "R", "AB") into its 1-based index.
+pub fn column_index(letters: &str) -> Option<u32> {
+    let letters = letters.trim();
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+    letters.chars().try_fold(0u32, |acc, c| {
+        c.is_ascii_alphabetic()
+            .then(|| acc * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1))
+    })
+}
+
+/// The inverse of `column_index`: 1 is "A", 27 is "AA".
+pub fn column_letters(mut index: u32) -> String {
+    let mut letters = Vec::new();
+    while index > 0 {
+        let rem = (index - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        index = (index - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Reads a CSV file exported by Excel, which uses `;` or `,` depending on the locale.
+pub fn read_csv_records(path: &str) -> Result<Vec<csv::StringRecord>, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Impossibile leggere il file: {}", e))?;
+    let first_line = content.lines().next().unwrap_or_default();
+    let delimiter = if first_line.matches(';').count() >= first_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, r)| r.map_err(|e| format!("Riga CSV {} non valida: {}", i + 1, e)))
+        .collect()
+}
+
 pub fn adjust_formula(formula: &str, old_row: u32, new_row: u32) -> String {
     let pattern = format!(r"([A-Z]){}\b", old_row);
     let re = Regex::new(&pattern).unwrap();
     re.replace_all(formula, format!("${{1}}{}", new_row))
         .to_string()
 }
+
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+pub fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = Path::new(file_name)
+        .extension()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    (2..)
+        .map(|i| {
+            if ext.is_empty() {
+                dir.join(format!("{}_{}", stem, i))
+            } else {
+                dir.join(format!("{}_{}.{}", stem, i, ext))
+            }
+        })
+        .find(|p| !p.exists())
+        .unwrap()
+}
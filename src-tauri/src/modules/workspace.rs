@@ -0,0 +1,246 @@
+use crate::modules::secrets;
+
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tauri::{command, AppHandle, Manager, Wry};
+use tauri_plugin_store::{Store, StoreExt};
+
+pub const DEFAULT_WORKSPACE: &str = "default";
+const REGISTRY_STORE: &str = "workspaces.json";
+/// Stores that hold knowledge shared by every company rather than per-company data.
+const GLOBAL_STORES: [&str; 2] = [REGISTRY_STORE, "glossary.json"];
+/// Stores that make up a workspace, removed together with it.
+const WORKSPACE_STORES: [&str; 7] = [
+    "settings.json",
+    "corrections.json",
+    "prompts.json",
+    "templates.json",
+    "examples.json",
+    "catalog.json",
+    "credentials.json",
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceState {
+    pub workspaces: Vec<Workspace>,
+    pub active: String,
+    /// The settings store of the active workspace, for the frontend to load.
+    pub settings_store: String,
+}
+
+fn registry(app: &AppHandle) -> Result<Arc<Store<Wry>>, String> {
+    app.store(REGISTRY_STORE)
+        .map_err(|e| format!("Store errore: {}", e))
+}
+
+fn default_workspace() -> Workspace {
+    Workspace {
+        id: DEFAULT_WORKSPACE.to_string(),
+        name: "Predefinito".to_string(),
+        created_at: String::new(),
+    }
+}
+
+fn load_workspaces(app: &AppHandle) -> Vec<Workspace> {
+    let mut workspaces: Vec<Workspace> = registry(app)
+        .ok()
+        .and_then(|store| store.get("workspaces"))
+        .and_then(|val| serde_json::from_value(val).ok())
+        .unwrap_or_default();
+    if !workspaces.iter().any(|w| w.id == DEFAULT_WORKSPACE) {
+        workspaces.insert(0, default_workspace());
+    }
+    workspaces
+}
+
+pub fn active_id(app: &AppHandle) -> String {
+    registry(app)
+        .ok()
+        .and_then(|store| store.get("active"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|id| load_workspaces(app).iter().any(|w| &w.id == id))
+        .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string())
+}
+
+fn scoped_name(id: &str, base: &str) -> String {
+    if id == DEFAULT_WORKSPACE || GLOBAL_STORES.contains(&base) {
+        base.to_string()
+    } else {
+        format!("workspace-{}-{}", id, base)
+    }
+}
+
+/// The file name of a store in the active workspace. The default workspace keeps the
+/// original names, so existing data stays where it was.
+pub fn store_name(app: &AppHandle, base: &str) -> String {
+    scoped_name(&active_id(app), base)
+}
+
+pub fn store(app: &AppHandle, base: &str) -> Result<Arc<Store<Wry>>, String> {
+    app.store(store_name(app, base))
+        .map_err(|e| format!("Store errore: {}", e))
+}
+
+/// The keyring name of a secret in the active workspace.
+pub fn secret_name(app: &AppHandle, base: &str) -> String {
+    let id = active_id(app);
+    if id == DEFAULT_WORKSPACE {
+        base.to_string()
+    } else {
+        format!("{}@{}", base, id)
+    }
+}
+
+fn state(app: &AppHandle) -> WorkspaceState {
+    WorkspaceState {
+        workspaces: load_workspaces(app),
+        active: active_id(app),
+        settings_store: store_name(app, "settings.json"),
+    }
+}
+
+fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    slug.split('-')
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[command]
+pub async fn get_workspaces(app: AppHandle) -> Result<WorkspaceState, String> {
+    Ok(state(&app))
+}
+
+/// Creates a workspace. With `copy_settings` it starts with the settings of the active
+/// workspace; corrections, prompts and credentials always start empty.
+#[command]
+pub async fn create_workspace(
+    app: AppHandle,
+    name: String,
+    copy_settings: bool,
+) -> Result<Workspace, String> {
+    let name = name.trim().to_string();
+    let base = slug(&name);
+    if base.is_empty() {
+        return Err("Il nome dello spazio di lavoro non è valido.".to_string());
+    }
+
+    let mut workspaces = load_workspaces(&app);
+    if workspaces
+        .iter()
+        .any(|w| w.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(format!("Lo spazio di lavoro {} esiste già.", name));
+    }
+    let mut id = base.clone();
+    let mut n = 2;
+    while workspaces.iter().any(|w| w.id == id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+
+    if copy_settings {
+        let source = store(&app, "settings.json")?;
+        let target = app
+            .store(scoped_name(&id, "settings.json"))
+            .map_err(|e| format!("Store errore: {}", e))?;
+        for (key, value) in source.entries() {
+            if key != "apiKey" {
+                target.set(key, value);
+            }
+        }
+        target
+            .save()
+            .map_err(|e| format!("Errore di memoria: {}", e))?;
+    }
+
+    let workspace = Workspace {
+        id,
+        name,
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    workspaces.push(workspace.clone());
+
+    let registry = registry(&app)?;
+    registry.set("workspaces", json!(workspaces));
+    registry
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))?;
+    Ok(workspace)
+}
+
+/// Makes another workspace active. The frontend reloads afterwards so that every
+/// module picks up the stores of the new workspace.
+#[command]
+pub async fn switch_workspace(app: AppHandle, id: String) -> Result<WorkspaceState, String> {
+    if !load_workspaces(&app).iter().any(|w| w.id == id) {
+        return Err(format!("Spazio di lavoro non trovato: {}", id));
+    }
+    let registry = registry(&app)?;
+    registry.set("active", json!(id));
+    registry
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))?;
+    Ok(state(&app))
+}
+
+/// Removes a workspace with its stores and credentials. The default and the active
+/// workspace cannot be removed.
+#[command]
+pub async fn remove_workspace(app: AppHandle, id: String) -> Result<WorkspaceState, String> {
+    if id == DEFAULT_WORKSPACE {
+        return Err("Lo spazio di lavoro predefinito non può essere eliminato.".to_string());
+    }
+    if id == active_id(&app) {
+        return Err("Passare prima a un altro spazio di lavoro.".to_string());
+    }
+
+    if let Ok(store) = app.store(scoped_name(&id, "credentials.json")) {
+        let secret_ids: Vec<String> = store
+            .get("credentials")
+            .and_then(|v| v.as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|c| c.get("secretId").and_then(Value::as_str).map(String::from))
+            .collect();
+        for secret_id in secret_ids {
+            secrets::store_secret(&app, &secret_id, "")?;
+        }
+    }
+
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cartella dati non disponibile: {}", e))?;
+    for base in WORKSPACE_STORES {
+        let name = scoped_name(&id, base);
+        if let Ok(store) = app.store(&name) {
+            store.clear();
+            store.close_resource();
+        }
+        let _ = std::fs::remove_file(data_dir.join(name));
+    }
+
+    let mut workspaces = load_workspaces(&app);
+    workspaces.retain(|w| w.id != id);
+    let registry = registry(&app)?;
+    registry.set("workspaces", json!(workspaces));
+    registry
+        .save()
+        .map_err(|e| format!("Errore di memoria: {}", e))?;
+    Ok(state(&app))
+}
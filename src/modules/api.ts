@@ -1,16 +1,69 @@
 import { invoke } from "@tauri-apps/api/core";
-import { AiResponse, PdfDataRow } from "../types";
+import {
+  AiResponse,
+  BundleImportReport,
+  BundleManifest,
+  CatalogEntry,
+  CorrectionEntry,
+  CorrectionHistoryEntry,
+  CorrectionImportReport,
+  CorrectionMergePolicy,
+  CorrectionSyncStatus,
+  CorrectionUsage,
+  Credential,
+  CredentialTestResult,
+  CatalogSeedResult,
+  ExportPreview,
+  ExtractionTemplate,
+  GlossaryImportResult,
+  GlossaryTerm,
+  ExpandedInputs,
+  PdfDataRow,
+  PolicyStatus,
+  ProductMapping,
+  PromptTemplate,
+  SecretStatus,
+  Settings,
+  Workspace,
+  WorkspaceState,
+  TemplateTestResult,
+} from "../types";
 import { appState } from "./state";
 import { setProgress, showToast } from "./ui";
 
 import { chunk } from "lodash";
 
 export const api = {
-  analyzeDocument: (path: string, docType: string) =>
-    invoke<AiResponse>("analyze_document", { path, docType }),
-
-  exportExcel: (data: PdfDataRow[], filePath: string | null) =>
-    invoke<string>("export_to_excel", { data, filePath }),
+  analyzeDocument: (
+    path: string,
+    docType: string,
+    supplier?: string | null,
+    customer?: string | null,
+  ) =>
+    invoke<AiResponse>("analyze_document", {
+      path,
+      docType,
+      supplier,
+      customer,
+    }),
+
+  expandInputs: (paths: string[]) =>
+    invoke<ExpandedInputs>("expand_inputs", { paths }),
+
+  exportExcel: (
+    data: PdfDataRow[],
+    filePath: string | null,
+    sheet: string | null = null,
+  ) => invoke<string>("export_to_excel", { data, filePath, sheet }),
+
+  previewExport: (
+    data: PdfDataRow[],
+    filePath: string | null,
+    sheet: string | null = null,
+  ) => invoke<ExportPreview>("preview_export", { data, filePath, sheet }),
+
+  getExcelSheets: (path: string) =>
+    invoke<string[]>("get_excel_sheets", { path }),
 
   checkExcelAccess: (path: string) =>
     invoke<boolean>("check_excel_access", { path }),
@@ -18,17 +71,157 @@ export const api = {
   moveFiles: (paths: string[], targetDir: string) =>
     invoke("move_files", { paths, targetDir }),
 
-  getCorrections: () => invoke<Record<string, string>>("get_corrections"),
+  getCorrections: () => invoke<CorrectionEntry[]>("get_corrections"),
+
+  learnCorrection: (
+    wrong: string,
+    correct: string,
+    supplier?: string | null,
+    customer?: string | null,
+    origin?: string,
+  ) =>
+    invoke("learn_correction", { wrong, correct, supplier, customer, origin }),
+
+  removeCorrection: (
+    wrong: string,
+    supplier?: string | null,
+    customer?: string | null,
+  ) => invoke("remove_correction", { wrong, supplier, customer }),
+
+  getCorrectionHistory: (
+    wrong?: string | null,
+    supplier?: string | null,
+    customer?: string | null,
+  ) =>
+    invoke<CorrectionHistoryEntry[]>("get_correction_history", {
+      wrong,
+      supplier,
+      customer,
+    }),
+
+  restoreCorrection: (id: number) => invoke("restore_correction", { id }),
+
+  getCorrectionUsage: () => invoke<CorrectionUsage[]>("get_correction_usage"),
+
+  getCorrectionConflicts: () =>
+    invoke<CorrectionEntry[]>("get_correction_conflicts"),
+
+  resolveCorrectionConflict: (
+    wrong: string,
+    supplier: string | null | undefined,
+    customer: string | null | undefined,
+    accept: boolean,
+  ) =>
+    invoke("resolve_correction_conflict", { wrong, supplier, customer, accept }),
+
+  syncCorrections: () => invoke<CorrectionSyncStatus>("sync_corrections"),
+
+  exportCorrections: (path: string) =>
+    invoke<number>("export_corrections", { path }),
+
+  importCorrections: (
+    path: string,
+    policy: CorrectionMergePolicy,
+    dryRun: boolean,
+  ) =>
+    invoke<CorrectionImportReport>("import_corrections", {
+      path,
+      policy,
+      dryRun,
+    }),
+
+  confirmExtraction: (
+    path: string,
+    docType: string,
+    supplier: string,
+    result: AiResponse,
+  ) => invoke("confirm_extraction", { path, docType, supplier, result }),
+
+  getPrompts: () => invoke<Record<string, PromptTemplate>>("get_prompts"),
+
+  savePrompt: (docType: string, template: string) =>
+    invoke<PromptTemplate>("save_prompt", { docType, template }),
+
+  resetPrompts: (docType?: string) =>
+    invoke<Record<string, PromptTemplate>>("reset_prompts", { docType }),
 
-  learnCorrection: (wrong: string, correct: string) =>
-    invoke("learn_correction", { wrong, correct }),
+  getCatalog: () => invoke<CatalogEntry[]>("get_catalog"),
 
-  removeCorrection: (wrong: string) => invoke("remove_correction", { wrong }),
+  saveCatalog: (entries: CatalogEntry[]) =>
+    invoke("save_catalog", { entries }),
+
+  seedCatalog: (path?: string | null) =>
+    invoke<CatalogSeedResult>("seed_catalog_from_workbook", { path }),
+
+  mapProducts: (names: string[]) =>
+    invoke<ProductMapping[]>("map_products", { names }),
+
+  getGlossary: () => invoke<GlossaryTerm[]>("get_glossary"),
+
+  saveGlossary: (terms: GlossaryTerm[]) => invoke("save_glossary", { terms }),
+
+  importGlossary: (path: string, replace: boolean) =>
+    invoke<GlossaryImportResult>("import_glossary", { path, replace }),
+
+  exportGlossary: (path: string) => invoke<number>("export_glossary", { path }),
+
+  getTemplates: () => invoke<ExtractionTemplate[]>("get_templates"),
+
+  saveTemplate: (template: ExtractionTemplate) =>
+    invoke("save_template", { template }),
+
+  removeTemplate: (name: string) => invoke("remove_template", { name }),
+
+  testTemplate: (path: string, template: ExtractionTemplate) =>
+    invoke<TemplateTestResult>("test_template", { path, template }),
 
   saveApiKey: (key: string) => invoke("save_api_key", { key }),
 
   getApiKey: () => invoke<string>("get_api_key"),
 
+  getCredentials: () => invoke<Credential[]>("get_credentials"),
+
+  saveCredential: (provider: string, name: string, key: string) =>
+    invoke<Credential>("save_credential", { provider, name, key }),
+
+  setActiveCredential: (provider: string, name: string) =>
+    invoke("set_active_credential", { provider, name }),
+
+  removeCredential: (provider: string, name: string) =>
+    invoke("remove_credential", { provider, name }),
+
+  testCredentials: (provider?: string, name?: string) =>
+    invoke<CredentialTestResult[]>("test_credentials", { provider, name }),
+
+  rotateCredential: (provider: string, name: string, key: string) =>
+    invoke<CredentialTestResult>("rotate_credential", { provider, name, key }),
+
+  getWorkspaces: () => invoke<WorkspaceState>("get_workspaces"),
+
+  createWorkspace: (name: string, copySettings: boolean) =>
+    invoke<Workspace>("create_workspace", { name, copySettings }),
+
+  switchWorkspace: (id: string) =>
+    invoke<WorkspaceState>("switch_workspace", { id }),
+
+  removeWorkspace: (id: string) =>
+    invoke<WorkspaceState>("remove_workspace", { id }),
+
+  getSecretStatus: () => invoke<SecretStatus>("get_secret_status"),
+
+  getSettings: () => invoke<Settings>("get_settings"),
+
+  updateSettings: (patch: Partial<Settings>) =>
+    invoke<Settings>("update_settings", { patch }),
+
+  getPolicy: () => invoke<PolicyStatus>("get_policy"),
+
+  exportConfigBundle: (path: string) =>
+    invoke<BundleManifest>("export_config_bundle", { path }),
+
+  importConfigBundle: (path: string, dryRun: boolean) =>
+    invoke<BundleImportReport>("import_config_bundle", { path, dryRun }),
+
   setTaskbarProgress: (progress: number, total: number) =>
     invoke("set_taskbar_progress", { progress, total }),
 
@@ -37,6 +230,47 @@ export const api = {
   },
 };
 
+async function applyCatalogMapping(rows: PdfDataRow[]) {
+  const productRows = rows.filter(
+    (row) => typeof row.produkt === "string" && row.produkt.trim(),
+  );
+  if (productRows.length === 0) return;
+
+  let mappings: ProductMapping[];
+  try {
+    mappings = await api.mapProducts(productRows.map((row) => row.produkt!));
+  } catch (e) {
+    console.error("Catalogo non disponibile:", e);
+    return;
+  }
+
+  let flagged = 0;
+  productRows.forEach((row, i) => {
+    const mapping = mappings[i];
+    if (!mapping) return;
+    row.catalogScore = mapping.matched ? mapping.score : null;
+    row.needsReview = mapping.needsReview;
+    if (mapping.matched && !mapping.needsReview) {
+      row.produkt = mapping.matched;
+    }
+    if (mapping.needsReview) flagged++;
+  });
+
+  if (flagged > 0) {
+    showToast(`${flagged} prodotti non trovati con certezza nel catalogo.`, "info");
+  }
+}
+
+function applyDocumentHeader(row: PdfDataRow, result: AiResponse) {
+  if (result.docType) row.docType = result.docType;
+  row.nummerAuftrag = row.nummerAuftrag || result.nummerAuftrag || null;
+  row.datumAuftrag = row.datumAuftrag || result.datumAuftrag || null;
+  row.datumRechnung = row.datumRechnung || result.datumRechnung || null;
+  row.kunde = row.kunde || result.kunde || null;
+  row.lieferant = row.lieferant || result.lieferant || null;
+  if (result.meta) row.extraction = result.meta;
+}
+
 export async function handleReseachStart() {
   if (!appState.hot || appState.isProcessing) return;
 
@@ -48,11 +282,8 @@ export async function handleReseachStart() {
   document.body.classList.add("app-loading");
 
   try {
-    const storedLimit = await appState.store
-      ?.get<number>("concurrencyLimit")
-      .catch(() => null);
-    const concurrencyLimit =
-      storedLimit !== null && storedLimit !== undefined ? storedLimit : 5;
+    const settings = await api.getSettings().catch(() => null);
+    const concurrencyLimit = settings?.concurrencyLimit ?? 5;
 
     const data = appState.hot.getSourceData() as PdfDataRow[];
 
@@ -64,8 +295,6 @@ export async function handleReseachStart() {
     let completedCount = 0;
     setProgress(0, totalTasks);
 
-    const corrections = await api.getCorrections().catch(() => ({} as Record<string, string>))
-
     const aiResults: any[] = new Array(data.length).fill(null);
     let cursor = 0;
 
@@ -78,29 +307,26 @@ export async function handleReseachStart() {
         if (processedItem && processedItem.result) {
           const aiResult = processedItem.result
           const products = aiResult.produkte
-          const docType = processedItem.docType
+          const docType = aiResult.docType || processedItem.docType
 
           if (products && Array.isArray(products) && products.length > 0) {
             products.forEach((prod: any, prodIndex: number) => {
               const newRow: PdfDataRow = { ...originalRow }
+              applyDocumentHeader(newRow, aiResult)
               if (prodIndex > 0) {
                 newRow.pdfName = ""
                 newRow.fullPath = ""
                 newRow.confirmed = false
               }
 
-              let finalProdukt = prod.produkt
-              if (typeof finalProdukt === "string") {
-                finalProdukt = finalProdukt.toUpperCase().trim()
-                for (const [wrong, right] of Object.entries(corrections)) {
-                  if (wrong.toUpperCase().trim() === finalProdukt) {
-                    finalProdukt = right.toUpperCase()
-                    break
-                  }
-                }
-              }
+              const finalProdukt =
+                typeof prod.produkt === "string"
+                  ? prod.produkt.toUpperCase().trim()
+                  : prod.produkt
 
               newRow.produkt = finalProdukt
+              newRow.produktOriginal = prod.produktOriginal ?? null
+              newRow.appliedCorrection = prod.correction ?? null
               if (docType === "auftrag") {
                 newRow.menge = prod.menge
                 newRow.waehrung = prod.waehrung
@@ -135,6 +361,8 @@ export async function handleReseachStart() {
               const result = await invoke<AiResponse>("analyze_document", {
                 path: task.row.fullPath,
                 docType: task.row.docType,
+                supplier: task.row.lieferant,
+                customer: task.row.kunde,
               });
 
               aiResults[task.index] = {
@@ -184,6 +412,8 @@ export async function handleReseachStart() {
             const result = await invoke<AiResponse>("analyze_document", {
               path: task.row.fullPath,
               docType: task.row.docType,
+              supplier: task.row.lieferant,
+              customer: task.row.kunde,
             });
 
             aiResults[task.index] = {
@@ -226,11 +456,12 @@ export async function handleReseachStart() {
       if (processedItem && processedItem.result) {
         const aiResult = processedItem.result;
         const products = aiResult.produkte;
-        const docType = processedItem.docType;
+        const docType = aiResult.docType || processedItem.docType;
 
         if (products && Array.isArray(products) && products.length > 0) {
           products.forEach((prod: any, prodIndex: number) => {
             const newRow: PdfDataRow = { ...originalRow };
+            applyDocumentHeader(newRow, aiResult);
 
             if (prodIndex > 0) {
               newRow.pdfName = "";
@@ -239,19 +470,14 @@ export async function handleReseachStart() {
               newRow.warnings = originalRow.warnings || false;
             }
 
-            let finalProdukt = prod.produkt
-
-            if (typeof finalProdukt === "string") {
-              finalProdukt = finalProdukt.toUpperCase().trim()
-              for (const [wrong, right] of Object.entries(corrections)) {
-                if (wrong.toUpperCase().trim() === finalProdukt) {
-                  finalProdukt = right.toUpperCase()
-                  break
-                }
-              }
-            }
+            const finalProdukt =
+              typeof prod.produkt === "string"
+                ? prod.produkt.toUpperCase().trim()
+                : prod.produkt
 
             newRow.produkt = finalProdukt
+            newRow.produktOriginal = prod.produktOriginal ?? null;
+            newRow.appliedCorrection = prod.correction ?? null;
 
             if (docType === "auftrag") {
               newRow.menge = prod.menge;
@@ -280,6 +506,8 @@ export async function handleReseachStart() {
       }
     });
 
+    await applyCatalogMapping(newTableData);
+
     appState.hot.loadData(newTableData);
     appState.hot.render();
     appState.hot.updateSettings({ allowInsertRow: true });
@@ -4,17 +4,37 @@ import { join } from "@tauri-apps/api/path";
 
 import { appState } from "./state";
 import { showToast } from "./ui";
-import { PdfDataRow } from "../types";
+import { api } from "./api";
+import { FilenameRules, InputDocument, PdfDataRow } from "../types";
+
+export const SUPPORTED_EXTENSIONS = [
+  "pdf",
+  "xml",
+  "jpg",
+  "jpeg",
+  "png",
+  "tif",
+  "tiff",
+  "eml",
+  "zip",
+];
+
+const CONTAINER_EXTENSIONS = [".eml", ".zip"];
+
+export function isSupportedDocument(path: string) {
+  const lower = path.toLowerCase();
+  return SUPPORTED_EXTENSIONS.some((ext) => lower.endsWith(`.${ext}`));
+}
 
 export async function handleSelectFiles() {
   if (appState.isProcessing) return;
   const result = await open({
-    title: "Selezionare i file PDF",
+    title: "Selezionare i documenti",
     multiple: true,
     filters: [
       {
-        name: "PDF",
-        extensions: ["pdf"],
+        name: "Documenti (PDF, UBL XML, immagini, email, ZIP)",
+        extensions: SUPPORTED_EXTENSIONS,
       },
     ],
   });
@@ -27,7 +47,7 @@ export async function handleSelectFiles() {
     appState.selectedPdfPaths = [];
   }
 
-  updateFileUI();
+  await updateFileUI();
 }
 
 export async function handleSelectFolder() {
@@ -49,14 +69,14 @@ export async function loadPdfsFromDirectory(path: string) {
 
     const pdfEntries = entries.filter(
       (entry) =>
-        entry.name?.toLowerCase().endsWith(".pdf") && !entry.isDirectory
+        entry.name && isSupportedDocument(entry.name) && !entry.isDirectory
     );
 
     appState.selectedPdfPaths = await Promise.all(
       pdfEntries.map((entry) => join(path, entry.name!))
     );
 
-    updateFileUI();
+    await updateFileUI();
   } catch (e) {
     console.error(
       "Errore durante il caricamento della cartella predefinita:",
@@ -69,13 +89,42 @@ export async function loadPdfsFromDirectory(path: string) {
   }
 }
 
-export function updateFileUI() {
+export async function updateFileUI() {
   if (!appState.hot) return
 
+  let documents: InputDocument[]
+  try {
+    const expanded = await api.expandInputs(appState.selectedPdfPaths)
+    documents = expanded.documents
+    if (expanded.errors.length > 0) {
+      expanded.errors.forEach((e) => console.error(`${e.path}: ${e.error}`))
+      const names = expanded.errors
+        .map((e) => e.path.split(/[\\/]/).pop())
+        .join(", ")
+      showToast(`File non estratti e ignorati: ${names}`, "error")
+    }
+  } catch (e) {
+    console.error("Errore durante l'estrazione dei file:", e)
+    showToast(`Errore durante l'estrazione dei file: ${e}`, "error")
+    documents = appState.selectedPdfPaths
+      .filter(
+        (path) =>
+          !CONTAINER_EXTENSIONS.some((ext) => path.toLowerCase().endsWith(ext))
+      )
+      .map((path) => ({ path, hints: {} }))
+  }
+
+  const rules = await appState.store
+    ?.get<FilenameRules>("filenameRules")
+    .catch(() => undefined)
+  const invoicePrefix = (rules?.invoicePrefix ?? "FT").toUpperCase()
+  const separator = rules?.separator || "_"
+
   let nextId = 1
 
-  const newRows = appState.selectedPdfPaths
-    .map((path): PdfDataRow | null => {
+  const newRows = documents
+    .map((doc): PdfDataRow | null => {
+      const path = doc.path
       try {
         const lastSeparatorIndex = Math.max(
           path.lastIndexOf("/"),
@@ -83,15 +132,17 @@ export function updateFileUI() {
         )
         const fileName = path
           .substring(lastSeparatorIndex + 1)
-          .replace(/\.pdf$/i, "")
+          .replace(/\.[^.]+$/, "")
           .toUpperCase()
 
-        const isInvoice = fileName.startsWith("FT")
+        const isInvoice =
+          (invoicePrefix !== "" && fileName.startsWith(invoicePrefix)) ||
+          doc.hints.docType === "rechnung"
         const docType = isInvoice ? "rechnung" : "auftrag"
 
         let datumRechnung, datumAuftrag, nummerAuftrag, kunde, lieferant
 
-        const parts = fileName.split("_")
+        const parts = fileName.split(separator)
 
         if (isInvoice) {
           datumRechnung = parseDateStrings(parts[2]?.split("-")[0])
@@ -105,6 +156,13 @@ export function updateFileUI() {
           lieferant = parts[2]?.split("-")[0] || null
         }
 
+        lieferant = lieferant || doc.hints.lieferant || null
+        if (isInvoice) {
+          datumRechnung = datumRechnung || doc.hints.datum || null
+        } else {
+          datumAuftrag = datumAuftrag || doc.hints.datum || null
+        }
+
         const missingData = isInvoice
           ? !datumRechnung || !nummerAuftrag || !kunde || !lieferant
           : !datumAuftrag || !nummerAuftrag || !kunde || !lieferant
@@ -113,6 +171,8 @@ export function updateFileUI() {
           id: nextId++,
           pdfName: fileName,
           fullPath: path,
+          sourcePath: doc.sourcePath || null,
+          entryName: doc.entryName || null,
           docType,
           confirmed: false,
           warnings: missingData,
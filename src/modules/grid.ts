@@ -1,10 +1,11 @@
-import { PdfDataRow } from "../types";
+import { AiResponse, ExportPreview, PdfDataRow } from "../types";
 import { appState } from "./state";
-import { setProgress, showToast } from "./ui";
+import { setProgress, showCustomConfirm, showToast } from "./ui";
 import { api } from "./api";
 
 import Handsontable from "handsontable";
 import { openPath } from "@tauri-apps/plugin-opener";
+import { open } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
 import "handsontable/styles/handsontable.min.css";
 import "handsontable/styles/ht-theme-main.min.css";
@@ -104,7 +105,7 @@ export function createGrid(container: Element): Handsontable {
       },
     },
     fillHandle: true,
-    cells(row, col) {
+    cells(row, col, prop) {
       if (!appState.hot) return {};
       const cellProps: any = {};
       const classList = ["htEllipsis"];
@@ -121,6 +122,9 @@ export function createGrid(container: Element): Handsontable {
         } else if (rowData.warnings) {
           classList.push("warning-row");
         }
+        if (rowData.needsReview && prop === "produkt") {
+          classList.push("review-cell");
+        }
       }
 
       cellProps.className = classList.join(" ");
@@ -155,9 +159,22 @@ export function createGrid(container: Element): Handsontable {
           const cleanOld = typeof oldVal === "string" ? oldVal.trim() : oldVal
           const cleanNew = typeof newVal === "string" ? newVal.trim() : newVal
 
+          const rowData = appState.hot?.getSourceDataAtRow(c[0]) as PdfDataRow;
+          if (cleanNew && cleanOld !== cleanNew && rowData) {
+            rowData.needsReview = false;
+          }
+
           if (cleanOld && cleanNew && cleanOld !== cleanNew) {
+            // Corrections apply to every supplier unless the settings ask for the row's one.
             api
-              .learnCorrection(cleanOld, cleanNew)
+              .getSettings()
+              .then((settings) =>
+                api.learnCorrection(
+                  cleanOld,
+                  cleanNew,
+                  settings.supplierScopedCorrections ? rowData?.lieferant : null,
+                ),
+              )
               .then(() => console.log(`Imparato: "${cleanOld}" -> "${cleanNew}"`))
               .catch((err) => console.error("Errori di apprendimento:", err))
           }
@@ -224,11 +241,11 @@ export function createGrid(container: Element): Handsontable {
 
 function ellipsisRenderer(
   this: Handsontable.Core,
-  _instance: Handsontable.Core,
+  instance: Handsontable.Core,
   td: HTMLTableCellElement,
-  _row: number,
+  row: number,
   _col: number,
-  _prop: string | number,
+  prop: string | number,
   value: Handsontable.CellValue,
   _cellProperties: Handsontable.CellProperties,
 ) {
@@ -237,6 +254,24 @@ function ellipsisRenderer(
   if (value !== null && value !== undefined) {
     td.title = String(value);
   }
+
+  if (prop === "produkt") {
+    const rowData = instance.getSourceDataAtRow(row) as PdfDataRow;
+    const original = rowData?.produktOriginal;
+    if (original && original.toUpperCase() !== String(value ?? "").toUpperCase()) {
+      td.title = `${td.title}\nOriginale: ${original}`;
+    }
+    const correction = rowData?.appliedCorrection;
+    if (correction) {
+      td.title = `${td.title}\nCorrezione: ${correction.from} ➜ ${correction.correct} (${Math.round(correction.score * 100)}%)`;
+    }
+    if (rowData?.needsReview) {
+      const score = rowData.catalogScore;
+      td.title = `${td.title}\nDa verificare: ${
+        score ? `somiglianza con il catalogo ${Math.round(score * 100)}%` : "non presente nel catalogo"
+      }`;
+    }
+  }
 }
 
 async function reAnalyzeRow(row: number) {
@@ -253,30 +288,24 @@ async function reAnalyzeRow(row: number) {
   showToast("Analizza nuovamente il PDF...", "info");
 
   try {
-    const result = await api.analyzeDocument(rowData.fullPath, rowData.docType);
+    const result = await api.analyzeDocument(
+      rowData.fullPath,
+      rowData.docType,
+      rowData.lieferant,
+      rowData.kunde,
+    );
 
     const products = result.produkte
 
-    const corrections = await api.getCorrections().catch(() => ({} as Record<string, string>))
-
-    const applyCorrection = (name: string) => {
-      if (typeof name !== "string") return name
-      const upperName = name.toUpperCase().trim()
-      for (const [wrong, right] of Object.entries(corrections)) {
-        if (wrong.toUpperCase().trim() === upperName) {
-          return right.toUpperCase()
-        }
-      }
-      return upperName
-    }
-
     const toUpper = (str: any) => (str && typeof str === 'string') ? str.toUpperCase() : str;
 
     if (products && Array.isArray(products) && products.length > 0) {
       appState.hot.batch(() => {
         const firstProd = products[0];
 
-        appState.hot!.setDataAtRowProp(row, "produkt", applyCorrection(toUpper(firstProd.produkt)), "reAnalyze" as any);
+        appState.hot!.setDataAtRowProp(row, "produkt", toUpper(firstProd.produkt), "reAnalyze" as any);
+        appState.hot!.setDataAtRowProp(row, "produktOriginal", firstProd.produktOriginal ?? null, "reAnalyze" as any);
+        appState.hot!.setDataAtRowProp(row, "appliedCorrection", firstProd.correction ?? null, "reAnalyze" as any);
 
         if (rowData.docType === "auftrag") {
           appState.hot!.setDataAtRowProp(row, "menge", toUpper(firstProd.menge));
@@ -297,7 +326,9 @@ async function reAnalyzeRow(row: number) {
           extraProducts.forEach((prod, i) => {
             const newRowIdx = row + 1 + i;
 
-            appState.hot!.setDataAtRowProp(newRowIdx, "produkt", applyCorrection(toUpper(prod.produkt)), "reAnalyze" as any);
+            appState.hot!.setDataAtRowProp(newRowIdx, "produkt", toUpper(prod.produkt), "reAnalyze" as any);
+            appState.hot!.setDataAtRowProp(newRowIdx, "produktOriginal", prod.produktOriginal ?? null, "reAnalyze" as any);
+            appState.hot!.setDataAtRowProp(newRowIdx, "appliedCorrection", prod.correction ?? null, "reAnalyze" as any);
 
             if (rowData.docType === "auftrag") {
               appState.hot!.setDataAtRowProp(newRowIdx, "menge", prod.menge);
@@ -494,6 +525,9 @@ function pdfNameRenderer(
   const rowData = appState.hot
     ? (appState.hot.getSourceDataAtRow(row) as PdfDataRow)
     : null;
+  if (rowData && rowData.sourcePath) {
+    span.title = `${span.textContent}\n${rowData.sourcePath}`;
+  }
   checkbox.checked = Boolean(rowData && rowData.confirmed);
 
   checkbox.addEventListener("change", () => {
@@ -521,6 +555,99 @@ function pdfNameRenderer(
   td.appendChild(wrapper);
 }
 
+async function rememberConfirmedExtractions(rows: PdfDataRow[]) {
+  const byDocument = new Map<string, PdfDataRow[]>();
+  rows.forEach((row) => {
+    if (!row.fullPath || !row.lieferant || !row.produkt) return;
+    const list = byDocument.get(row.fullPath) || [];
+    list.push(row);
+    byDocument.set(row.fullPath, list);
+  });
+
+  for (const [path, docRows] of byDocument) {
+    const first = docRows[0];
+    const result: AiResponse = {
+      nummerRechnung: first.nummerRechnung,
+      produkte: docRows.map((row) => ({
+        produkt: row.produkt,
+        produktOriginal: row.produktOriginal,
+        menge: row.menge,
+        waehrung: row.waehrung,
+        preis: row.preis,
+        gelieferteMenge: row.gelieferteMenge,
+      })),
+    };
+    try {
+      await api.confirmExtraction(path, first.docType, first.lieferant!, result);
+    } catch (e) {
+      console.error("Impossibile salvare l'esempio:", e);
+    }
+  }
+}
+
+function renderExportPreview(preview: ExportPreview): HTMLElement {
+  const body = document.createElement("div");
+  const summary = document.createElement("p");
+  summary.textContent = `${preview.updated} righe aggiornate, ${preview.inserted} nuove righe. Scrivere nel file Excel?`;
+  body.appendChild(summary);
+
+  const table = document.createElement("table");
+  table.className = "export-preview";
+  const head = table.createTHead().insertRow();
+  ["Dati", "Azione", "Destinazione", "Modifiche"].forEach((label) => {
+    const th = document.createElement("th");
+    th.textContent = label;
+    head.appendChild(th);
+  });
+
+  const tbody = table.createTBody();
+  preview.rows.forEach((r) => {
+    const tr = tbody.insertRow();
+    let action = "Aggiornamento";
+    if (r.action === "insert") {
+      action = r.supplierBlock ? "Inserimento nel blocco del fornitore" : "Inserimento, nuovo fornitore";
+      if (r.mergedInto !== null) action += `, unita alla riga ${r.mergedInto + 1} dei dati`;
+    }
+    const changes = r.changes
+      .map((c) => {
+        const label = c.header || c.column;
+        return r.action === "insert" ? `${label}: ${c.new}` : `${label}: ${c.old || "vuoto"} → ${c.new}`;
+      })
+      .join("\n");
+    [`${r.index + 1}`, action, `[${r.sheet}] riga ${r.row}`, changes || "nessuna modifica"].forEach(
+      (text) => {
+        tr.insertCell().textContent = text;
+      },
+    );
+  });
+  body.appendChild(table);
+  return body;
+}
+
+/**
+ * Lets the user choose the sheet to write into. `null` keeps the sheet rules of the
+ * settings, `undefined` means the export was cancelled.
+ */
+async function pickExportSheet(excelPath: string): Promise<string | null | undefined> {
+  const sheets = await api.getExcelSheets(excelPath);
+  if (sheets.length < 2) return null;
+
+  const select = document.createElement("select");
+  select.className = "input-field";
+  select.style.width = "100%";
+  select.add(new Option("Secondo le regole delle impostazioni", ""));
+  sheets.forEach((name) => select.add(new Option(name, name)));
+
+  const body = document.createElement("div");
+  const label = document.createElement("p");
+  label.textContent = "Foglio in cui scrivere le righe confermate:";
+  body.append(label, select);
+
+  const ok = await showCustomConfirm(body, "Foglio di destinazione", "Continua", "Annulla", false);
+  if (!ok) return undefined;
+  return select.value || null;
+}
+
 export async function handleExportExcel() {
   if (!appState.hot || appState.isProcessing) return;
 
@@ -529,10 +656,17 @@ export async function handleExportExcel() {
   const allData = appState.hot.getSourceData() as PdfDataRow[];
   const confirmedData = allData.filter((row) => row.confirmed);
 
+  const pendingSources = new Set(
+    allData
+      .filter((row) => row.sourcePath && !row.confirmed)
+      .map((row) => row.sourcePath as string),
+  );
+
   const pathsToMove = new Set<string>();
   confirmedData.forEach((row) => {
     if (row.fullPath && !row.warnings && row.produkt) {
-      pathsToMove.add(row.fullPath);
+      const target = row.sourcePath || row.fullPath;
+      if (!pendingSources.has(target)) pathsToMove.add(target);
     }
   });
 
@@ -545,6 +679,30 @@ export async function handleExportExcel() {
   let unlisten: (() => void) | null = null;
 
   try {
+    let excelPath =
+      (await appState.store?.get<string>("defaultExcelPath")) || null;
+    if (!excelPath) {
+      const selected = await open({
+        filters: [{ name: "Excel", extensions: ["xlsx", "xlsm"] }],
+      });
+      if (typeof selected !== "string") return;
+      excelPath = selected;
+    }
+
+    const sheet = await pickExportSheet(excelPath);
+    if (sheet === undefined) return;
+
+    const preview = await api.previewExport(confirmedData, excelPath, sheet);
+    const accepted = await showCustomConfirm(
+      renderExportPreview(preview),
+      "Anteprima esportazione",
+      "Scrivi",
+      "Annulla",
+      false,
+      "min(900px, 90vw)",
+    );
+    if (!accepted) return;
+
     document.body.classList.add("app-loading");
 
     setProgress(0, confirmedData.length);
@@ -556,14 +714,13 @@ export async function handleExportExcel() {
       },
     );
 
-    const msg = await api.exportExcel(
-      confirmedData,
-      (await appState.store?.get<string>("defaultExcelPath")) || null,
-    );
+    const msg = await api.exportExcel(confirmedData, excelPath, sheet);
 
     if (msg !== "Interruzione da parte dell'utente") {
       showToast(msg, "success");
 
+      await rememberConfirmedExtractions(confirmedData);
+
       const processedDir = await appState.store?.get<string>(
         "defaultProcessedPdfPath",
       );
@@ -585,13 +742,10 @@ export async function handleExportExcel() {
 
     const shouldOpen = await appState.store?.get<boolean>("autoOpenExcel");
     if (shouldOpen) {
-      const excelPath = await appState.store?.get<string>("defaultExcelPath");
-      if (excelPath) {
-        try {
-          await openPath(excelPath);
-        } catch (e) {
-          console.error("Impossibile aprire Excel:", e);
-        }
+      try {
+        await openPath(excelPath);
+      } catch (e) {
+        console.error("Impossibile aprire Excel:", e);
       }
     }
 
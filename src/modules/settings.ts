@@ -1,36 +1,948 @@
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { handleSelectFiles, handleSelectFolder } from "./file-manager";
-import { toggleTheme } from "./ui";
+import { showCustomConfirm, showToast, toggleTheme } from "./ui";
 import { api } from "./api";
+import {
+  BundleImportReport,
+  CorrectionEntry,
+  CorrectionImportReport,
+  CorrectionMergePolicy,
+  ExcelColumns,
+  ExtractionTemplate,
+  SheetRules,
+  TemplateColumn,
+} from "../types";
+
+const HISTORY_ORIGINS: Record<string, string> = {
+  grid: "tabella",
+  import: "importazione",
+  settings: "impostazioni",
+  restore: "ripristino",
+};
+
+async function toggleCorrectionHistory(li: HTMLLIElement, entry: CorrectionEntry) {
+  const next = li.nextElementSibling;
+  if (next?.classList.contains("correction-history")) {
+    next.remove();
+    return;
+  }
+
+  const historyLi = document.createElement("li");
+  historyLi.className = "correction-history";
+  li.after(historyLi);
+
+  try {
+    const history = await api.getCorrectionHistory(
+      entry.wrong,
+      entry.supplier,
+      entry.customer,
+    );
+    if (history.length === 0) {
+      historyLi.textContent = "Nessuna modifica registrata.";
+      return;
+    }
+
+    history.forEach((h) => {
+      const row = document.createElement("div");
+      row.className = "correction-history-row";
+      const change = `${h.oldValue ?? "—"} ➜ ${h.newValue ?? "eliminata"}`;
+      row.textContent = `${h.at} · ${h.user} · ${HISTORY_ORIGINS[h.origin] ?? h.origin}: ${change}`;
+
+      const restoreBtn = document.createElement("button");
+      restoreBtn.className = "btn btn-ghost";
+      restoreBtn.textContent = "Ripristina";
+      restoreBtn.title = h.oldValue
+        ? `Ripristina "${h.oldValue}"`
+        : "Annulla l'aggiunta";
+      restoreBtn.addEventListener("click", async () => {
+        try {
+          await api.restoreCorrection(h.id);
+          await loadAndRenderCorrections();
+        } catch (e) {
+          showToast(`Errore durante il ripristino: ${e}`, "error");
+        }
+      });
+
+      row.appendChild(restoreBtn);
+      historyLi.appendChild(row);
+    });
+  } catch (e) {
+    historyLi.textContent = `Errore: ${e}`;
+  }
+}
+
+export async function loadGlossaryCount() {
+  const countEl = document.getElementById("glossary-count");
+  if (!countEl) return;
+
+  try {
+    const terms = await api.getGlossary();
+    countEl.textContent = String(terms.length);
+  } catch (e) {
+    console.error("Errore durante il caricamento del glossario:", e);
+  }
+}
+
+const SECRET_BACKENDS: Record<string, string> = {
+  keyring: "Chiave salvata nel portachiavi di sistema.",
+  file: "Portachiavi non disponibile: chiave salvata in un file cifrato per il tuo utente.",
+  none: "Nessuna chiave salvata.",
+};
+
+export async function loadSecretStatus() {
+  const statusEl = document.getElementById("api-key-storage");
+  if (!statusEl) return;
+
+  try {
+    const status = await api.getSecretStatus();
+    statusEl.textContent =
+      status.backend === "file" && !status.fileEncrypted
+        ? "Portachiavi non disponibile: chiave salvata in un file leggibile solo dal tuo utente, non cifrato."
+        : (SECRET_BACKENDS[status.backend] ?? "");
+    statusEl.title = status.filePath ?? "";
+  } catch (e) {
+    console.error("Errore durante la lettura dello stato della chiave:", e);
+  }
+}
+
+export async function loadPolicy() {
+  const noticeEl = document.getElementById("policy-notice");
+  const slider = document.getElementById(
+    "setting-concurrency",
+  ) as HTMLInputElement | null;
+
+  try {
+    const status = await api.getPolicy();
+    if (!status.active || !noticeEl) return;
+
+    const { policy } = status;
+    const rules: string[] = [];
+    if (policy.maxConcurrency != null) {
+      rules.push(`max ${policy.maxConcurrency} processi paralleli`);
+      if (slider) slider.max = String(policy.maxConcurrency);
+    }
+    if (policy.cloudOcr === false) rules.push("OCR nel cloud disattivato");
+    if (policy.allowedEndpoints) rules.push("servizi AI limitati");
+    if (policy.allowedExportFolders) {
+      rules.push(`esportazione solo in ${policy.allowedExportFolders.join(", ")}`);
+    }
+
+    noticeEl.textContent = status.error
+      ? status.error
+      : `Gestito dall'amministratore: ${rules.join(" · ") || "nessuna restrizione"}`;
+    noticeEl.title = status.path;
+    noticeEl.classList.toggle("offline", status.error !== null);
+    noticeEl.style.display = "block";
+  } catch (e) {
+    console.error("Errore durante la lettura dei criteri:", e);
+  }
+}
+
+const EXCEL_COLUMN_LABELS: [keyof ExcelColumns, string][] = [
+  ["datumAuftrag", "Data ordine"],
+  ["nummerAuftrag", "Numero ordine"],
+  ["kunde", "Cliente"],
+  ["lieferant", "Fornitore"],
+  ["produkt", "Prodotto"],
+  ["menge", "Quantità"],
+  ["waehrung", "Valuta"],
+  ["preis", "Prezzo"],
+  ["datumRechnung", "Data fattura"],
+  ["nummerRechnung", "Numero fattura"],
+  ["gelieferteMenge", "Quantità consegnata"],
+  ["formula", "Formula (opzionale)"],
+  ["anmerkungen", "Note (opzionale)"],
+];
+
+export function renderExcelColumns(columns: ExcelColumns) {
+  const container = document.getElementById("excel-columns");
+  if (!container) return;
+
+  container.innerHTML = "";
+  EXCEL_COLUMN_LABELS.forEach(([field, label]) => {
+    const row = document.createElement("label");
+    row.textContent = label;
+    const input = document.createElement("input");
+    input.className = "input-field";
+    input.dataset.field = field;
+    input.value = (columns[field] as string | null) ?? "";
+    const synonyms = document.createElement("input");
+    synonyms.className = "input-field synonyms";
+    synonyms.dataset.synonyms = field;
+    synonyms.placeholder = "Intestazioni, separate da virgola";
+    synonyms.value = (columns.headerSynonyms[field] ?? []).join(", ");
+    row.append(input, synonyms);
+    container.appendChild(row);
+  });
+
+  const datesRow = document.createElement("label");
+  datesRow.textContent = "Altre date (opzionale)";
+  const datesInput = document.createElement("input");
+  datesInput.className = "input-field";
+  datesInput.dataset.field = "extraDateColumns";
+  datesInput.placeholder = "Lettere o intestazioni, separate da virgola";
+  datesInput.value = columns.extraDateColumns.join(", ");
+  const datesSynonyms = document.createElement("input");
+  datesSynonyms.className = "input-field synonyms";
+  datesSynonyms.dataset.synonyms = "extraDates";
+  datesSynonyms.placeholder = "Intestazioni, separate da virgola";
+  datesSynonyms.value = (columns.headerSynonyms.extraDates ?? []).join(", ");
+  datesRow.append(datesInput, datesSynonyms);
+  container.appendChild(datesRow);
+
+  const detect = document.getElementById("setting-detect-headers") as HTMLInputElement | null;
+  if (detect) detect.checked = columns.detectHeaders;
+}
+
+export function readExcelColumns(current: ExcelColumns): ExcelColumns {
+  const columns = { ...current, headerSynonyms: { ...current.headerSynonyms } };
+  document
+    .querySelectorAll<HTMLInputElement>("#excel-columns input[data-synonyms]")
+    .forEach((input) => {
+      columns.headerSynonyms[input.dataset.synonyms!] = input.value
+        .split(",")
+        .map((s) => s.trim())
+        .filter((s) => s);
+    });
+  const detect = document.getElementById("setting-detect-headers") as HTMLInputElement | null;
+  if (detect) columns.detectHeaders = detect.checked;
+
+  document
+    .querySelectorAll<HTMLInputElement>("#excel-columns input[data-field]")
+    .forEach((input) => {
+      const field = input.dataset.field as keyof ExcelColumns;
+      const value = input.value.trim();
+      if (field === "formula" || field === "anmerkungen") {
+        columns[field] = value || null;
+      } else if (field === "extraDateColumns") {
+        columns.extraDateColumns = value
+          .split(",")
+          .map((s) => s.trim())
+          .filter((s) => s);
+      } else if (
+        field !== "detectHeaders" &&
+        field !== "headerSynonyms"
+      ) {
+        columns[field] = value;
+      }
+    });
+  return columns;
+}
+
+function updateSheetFields() {
+  const mode = (document.getElementById("setting-sheet-mode") as HTMLSelectElement | null)?.value;
+  const nameInput = document.getElementById("setting-sheet-name");
+  const patternInput = document.getElementById("setting-sheet-year-pattern");
+  if (nameInput) nameInput.style.display = mode === "name" ? "block" : "none";
+  if (patternInput) patternInput.style.display = mode === "orderYear" ? "block" : "none";
+}
+
+export function renderSheetRules(rules: SheetRules) {
+  const modeSelect = document.getElementById("setting-sheet-mode") as HTMLSelectElement | null;
+  const nameInput = document.getElementById("setting-sheet-name") as HTMLInputElement | null;
+  const patternInput = document.getElementById("setting-sheet-year-pattern") as HTMLInputElement | null;
+  const searchToggle = document.getElementById("setting-search-all-sheets") as HTMLInputElement | null;
+
+  if (modeSelect) {
+    modeSelect.value = rules.mode;
+    modeSelect.onchange = updateSheetFields;
+  }
+  if (nameInput) nameInput.value = rules.name;
+  if (patternInput) patternInput.value = rules.yearPattern;
+  if (searchToggle) searchToggle.checked = rules.searchAllSheets;
+  updateSheetFields();
+}
+
+export function readSheetRules(current: SheetRules): SheetRules {
+  const modeSelect = document.getElementById("setting-sheet-mode") as HTMLSelectElement | null;
+  const nameInput = document.getElementById("setting-sheet-name") as HTMLInputElement | null;
+  const patternInput = document.getElementById("setting-sheet-year-pattern") as HTMLInputElement | null;
+  const searchToggle = document.getElementById("setting-search-all-sheets") as HTMLInputElement | null;
+
+  return {
+    mode: (modeSelect?.value as SheetRules["mode"]) ?? current.mode,
+    name: nameInput ? nameInput.value : current.name,
+    yearPattern: patternInput ? patternInput.value : current.yearPattern,
+    searchAllSheets: searchToggle?.checked ?? current.searchAllSheets,
+  };
+}
+
+export async function loadCredentials() {
+  const listEl = document.getElementById("credentials-list");
+  if (!listEl) return;
+
+  try {
+    const credentials = await api.getCredentials();
+    listEl.innerHTML = "";
+    if (credentials.length <= 1 && !credentials[0]?.lastStatus) return;
+
+    credentials.forEach((c) => {
+      const li = document.createElement("li");
+      li.title = c.lastTested
+        ? `Verificata il ${c.lastTested}: ${c.lastStatus ?? ""}`
+        : `Creata il ${c.createdAt}`;
+
+      const textDiv = document.createElement("div");
+      textDiv.className = "correction-text";
+      const nameEl = document.createElement("span");
+      nameEl.className = "correction-wrong";
+      nameEl.textContent = `${c.name}${c.active ? " (attiva)" : ""}`;
+      const statusEl = document.createElement("span");
+      statusEl.className = "correction-scope";
+      statusEl.textContent = c.lastStatus ?? "";
+      textDiv.append(nameEl, statusEl);
+      li.appendChild(textDiv);
+
+      if (!c.active) {
+        const activateBtn = document.createElement("button");
+        activateBtn.className = "btn btn-ghost";
+        activateBtn.textContent = "Attiva";
+        activateBtn.addEventListener("click", async () => {
+          try {
+            await api.setActiveCredential(c.provider, c.name);
+            await loadCredentials();
+          } catch (e) {
+            showToast(`Errore: ${e}`, "error");
+          }
+        });
+        li.appendChild(activateBtn);
+
+        const delBtn = document.createElement("button");
+        delBtn.className = "btn btn-ghost";
+        delBtn.textContent = "Elimina";
+        delBtn.addEventListener("click", async () => {
+          const confirmed = await showCustomConfirm(
+            `Eliminare la chiave "${c.name}"?`,
+            "Elimina chiave",
+            "Elimina",
+          );
+          if (!confirmed) return;
+          try {
+            await api.removeCredential(c.provider, c.name);
+            await loadCredentials();
+          } catch (e) {
+            showToast(`Errore: ${e}`, "error");
+          }
+        });
+        li.appendChild(delBtn);
+      }
+
+      listEl.appendChild(li);
+    });
+  } catch (e) {
+    console.error("Errore durante il caricamento delle credenziali:", e);
+  }
+}
+
+document
+  .getElementById("test-credentials-btn")
+  ?.addEventListener("click", async () => {
+    try {
+      const results = await api.testCredentials("mistral");
+      const failed = results.filter((r) => !r.ok);
+      if (failed.length === 0) {
+        showToast("Tutte le chiavi sono valide.", "success");
+      } else {
+        showToast(
+          failed.map((r) => `${r.name}: ${r.message}`).join(" "),
+          "error",
+        );
+      }
+      await loadCredentials();
+    } catch (e) {
+      showToast(`Errore durante la verifica: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("rotate-credential-btn")
+  ?.addEventListener("click", async () => {
+    const key = prompt("Nuova chiave API:");
+    if (!key?.trim()) return;
+    const today = new Date().toISOString().slice(0, 10);
+    const name = prompt("Nome della nuova chiave:", `chiave-${today}`);
+    if (!name?.trim()) return;
+
+    try {
+      const result = await api.rotateCredential("mistral", name.trim(), key.trim());
+      showToast(`Chiave "${result.name}" attivata, la precedente è stata ritirata.`, "success");
+      const apiKeyInput = document.getElementById("setting-api-key") as HTMLInputElement;
+      if (apiKeyInput) apiKeyInput.value = await api.getApiKey();
+      await loadCredentials();
+      await loadSecretStatus();
+    } catch (e) {
+      showToast(`${e}`, "error");
+    }
+  });
+
+export async function loadWorkspaces() {
+  const select = document.getElementById("setting-workspace") as HTMLSelectElement | null;
+  if (!select) return;
+
+  try {
+    const state = await api.getWorkspaces();
+    select.innerHTML = "";
+    state.workspaces.forEach((w) => {
+      const option = document.createElement("option");
+      option.value = w.id;
+      option.textContent = w.name;
+      option.selected = w.id === state.active;
+      select.appendChild(option);
+    });
+  } catch (e) {
+    console.error("Errore durante il caricamento degli spazi di lavoro:", e);
+  }
+}
+
+document
+  .getElementById("setting-workspace")
+  ?.addEventListener("change", async (e) => {
+    const id = (e.target as HTMLSelectElement).value;
+    try {
+      await api.switchWorkspace(id);
+      window.location.reload();
+    } catch (err) {
+      showToast(`Errore durante il cambio: ${err}`, "error");
+      await loadWorkspaces();
+    }
+  });
+
+document
+  .getElementById("create-workspace-btn")
+  ?.addEventListener("click", async () => {
+    const name = prompt("Nome del nuovo spazio di lavoro:");
+    if (!name?.trim()) return;
+    const copySettings = await showCustomConfirm(
+      "Copiare le impostazioni dello spazio di lavoro attuale?",
+      "Nuovo spazio di lavoro",
+      "Copia",
+      "Non copiare",
+      false,
+    );
+
+    try {
+      const workspace = await api.createWorkspace(name.trim(), copySettings);
+      await api.switchWorkspace(workspace.id);
+      window.location.reload();
+    } catch (e) {
+      showToast(`Errore durante la creazione: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("remove-workspace-btn")
+  ?.addEventListener("click", async () => {
+    const state = await api.getWorkspaces();
+    const others = state.workspaces.filter((w) => w.id !== state.active);
+    if (others.length === 0) {
+      showToast("Non ci sono altri spazi di lavoro.", "info");
+      return;
+    }
+    const name = prompt(
+      `Spazio di lavoro da eliminare (${others.map((w) => w.name).join(", ")}):`,
+    );
+    const target = others.find((w) => w.name === name?.trim());
+    if (!target) return;
+    const confirmed = await showCustomConfirm(
+      `Eliminare "${target.name}" con correzioni, prompt e chiavi?`,
+      "Elimina spazio di lavoro",
+      "Elimina",
+    );
+    if (!confirmed) return;
+
+    try {
+      await api.removeWorkspace(target.id);
+      showToast(`Spazio di lavoro "${target.name}" eliminato.`, "success");
+      await loadWorkspaces();
+    } catch (e) {
+      showToast(`Errore durante l'eliminazione: ${e}`, "error");
+    }
+  });
+
+const BUNDLE_SECTIONS: Record<string, string> = {
+  settings: "Impostazioni",
+  corrections: "Correzioni",
+  prompts: "Prompt",
+  glossary: "Glossario",
+  templates: "Modelli",
+};
+
+function describeBundle(report: BundleImportReport): string {
+  const header = `Pacchetto del ${report.manifest.createdAt} (versione app ${report.manifest.appVersion})`;
+  const details = report.changes.slice(0, 200).map((c) => {
+    const current = c.current !== null ? `${c.current} → ` : "nuovo: ";
+    return `[${BUNDLE_SECTIONS[c.section] ?? c.section}] ${c.key}: ${current}${c.incoming}`;
+  });
+  if (report.changes.length > details.length) {
+    details.push(`… e altre ${report.changes.length - details.length}`);
+  }
+  return [header, ...details].join("\n");
+}
+
+document
+  .getElementById("export-config-btn")
+  ?.addEventListener("click", async () => {
+    const target = await save({
+      defaultPath: "configurazione-raccolta-dati.zip",
+      filters: [{ name: "Configurazione", extensions: ["zip"] }],
+    });
+    if (!target) return;
+
+    try {
+      await api.exportConfigBundle(target);
+      showToast("Configurazione esportata (senza chiavi API).", "success");
+    } catch (e) {
+      showToast(`Errore durante l'esportazione: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("import-config-btn")
+  ?.addEventListener("click", async () => {
+    const selected = await open({
+      filters: [{ name: "Configurazione", extensions: ["zip"] }],
+    });
+    if (typeof selected !== "string") return;
+
+    try {
+      const preview = await api.importConfigBundle(selected, true);
+      if (preview.changes.length === 0) {
+        showToast("La configurazione è già aggiornata.", "info");
+        return;
+      }
+      const confirmed = await showCustomConfirm(
+        describeBundle(preview),
+        "Anteprima importazione",
+        "Applica",
+        "Annulla",
+        false,
+      );
+      if (!confirmed) return;
+
+      await api.importConfigBundle(selected, false);
+      showToast("Configurazione importata.", "success");
+      window.location.reload();
+    } catch (e) {
+      showToast(`Errore durante l'importazione: ${e}`, "error");
+    }
+  });
+
+export async function loadCatalogCount() {
+  const countEl = document.getElementById("catalog-count");
+  if (!countEl) return;
+
+  try {
+    const entries = await api.getCatalog();
+    countEl.textContent = String(entries.length);
+  } catch (e) {
+    console.error("Errore durante il caricamento del catalogo:", e);
+  }
+}
+
+document
+  .getElementById("seed-catalog-btn")
+  ?.addEventListener("click", async () => {
+    const excelPath = (
+      document.getElementById("setting-excel-path") as HTMLInputElement
+    )?.value;
+
+    try {
+      const result = await api.seedCatalog(excelPath || null);
+      showToast(
+        `Catalogo: ${result.added} nuovi prodotti, ${result.aliasesAdded} varianti riconosciute.`,
+        "success",
+      );
+      await loadCatalogCount();
+    } catch (e) {
+      showToast(`Errore durante l'importazione del catalogo: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("import-glossary-btn")
+  ?.addEventListener("click", async () => {
+    const selected = await open({
+      filters: [{ name: "CSV", extensions: ["csv", "txt"] }],
+    });
+    if (typeof selected !== "string") return;
+
+    try {
+      const result = await api.importGlossary(selected, false);
+      showToast(
+        `Glossario: ${result.added} nuovi, ${result.updated} aggiornati, ${result.skipped} ignorati.`,
+        "success",
+      );
+      await loadGlossaryCount();
+    } catch (e) {
+      showToast(`Errore durante l'importazione: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("export-glossary-btn")
+  ?.addEventListener("click", async () => {
+    const target = await save({
+      defaultPath: "glossario.csv",
+      filters: [{ name: "CSV", extensions: ["csv"] }],
+    });
+    if (!target) return;
+
+    try {
+      const count = await api.exportGlossary(target);
+      showToast(`${count} termini esportati.`, "success");
+    } catch (e) {
+      showToast(`Errore durante l'esportazione: ${e}`, "error");
+    }
+  });
+
+const TEMPLATE_INPUTS = {
+  name: "template-name",
+  supplier: "template-supplier",
+  docType: "template-doc-type",
+  detect: "template-detect",
+  startAnchor: "template-start-anchor",
+  endAnchor: "template-end-anchor",
+  linePattern: "template-line-pattern",
+  columns: "template-columns",
+  invoiceNumber: "template-invoice-number",
+  currency: "template-currency",
+} as const;
+
+function templateInput(key: keyof typeof TEMPLATE_INPUTS) {
+  return document.getElementById(TEMPLATE_INPUTS[key]) as
+    | HTMLInputElement
+    | HTMLSelectElement
+    | HTMLTextAreaElement
+    | null;
+}
+
+function formatTemplateColumns(columns: TemplateColumn[] = []): string {
+  return columns
+    .map((c) => `${c.field}:${c.start}-${c.end ?? ""}`)
+    .join(", ");
+}
+
+/** Parses "produkt:0-40, menge:40-50, preis:50-"; an empty end runs to the end of the line. */
+function parseTemplateColumns(raw: string): TemplateColumn[] {
+  return raw
+    .split(",")
+    .map((part) => part.trim())
+    .filter(Boolean)
+    .map((part) => {
+      const match = part.match(/^(\w+)\s*:\s*(\d+)\s*-\s*(\d*)$/);
+      if (!match) {
+        throw new Error(`Colonna non valida: "${part}" (formato campo:inizio-fine)`);
+      }
+      return {
+        field: match[1] as TemplateColumn["field"],
+        start: Number(match[2]),
+        end: match[3] ? Number(match[3]) : null,
+      };
+    });
+}
+
+function fillTemplateForm(template?: ExtractionTemplate) {
+  const text = (value?: string | null) => value ?? "";
+  templateInput("name")!.value = text(template?.name);
+  templateInput("supplier")!.value = text(template?.supplier);
+  templateInput("docType")!.value = template?.docType ?? "auftrag";
+  templateInput("detect")!.value = (template?.detect ?? []).join("\n");
+  templateInput("startAnchor")!.value = text(template?.startAnchor);
+  templateInput("endAnchor")!.value = text(template?.endAnchor);
+  templateInput("linePattern")!.value = text(template?.linePattern);
+  templateInput("columns")!.value = formatTemplateColumns(template?.columns);
+  templateInput("invoiceNumber")!.value = text(template?.invoiceNumber);
+  templateInput("currency")!.value = text(template?.currency);
+
+  const resultEl = document.getElementById("template-test-result");
+  if (resultEl) resultEl.textContent = "";
+}
+
+function readTemplateForm(): ExtractionTemplate {
+  const optional = (key: keyof typeof TEMPLATE_INPUTS) =>
+    templateInput(key)?.value.trim() || null;
+
+  return {
+    name: templateInput("name")?.value.trim() ?? "",
+    supplier: templateInput("supplier")?.value.trim() ?? "",
+    docType: templateInput("docType")?.value === "rechnung" ? "rechnung" : "auftrag",
+    detect: (templateInput("detect")?.value ?? "")
+      .split("\n")
+      .map((line) => line.trim())
+      .filter(Boolean),
+    startAnchor: optional("startAnchor"),
+    endAnchor: optional("endAnchor"),
+    linePattern: optional("linePattern"),
+    columns: parseTemplateColumns(templateInput("columns")?.value ?? ""),
+    invoiceNumber: optional("invoiceNumber"),
+    currency: optional("currency"),
+  };
+}
+
+export async function loadTemplates() {
+  const listEl = document.getElementById("templates-list");
+  if (!listEl) return;
+
+  try {
+    const templates = await api.getTemplates();
+    listEl.innerHTML = "";
+
+    if (templates.length === 0) {
+      const li = document.createElement("li");
+      li.className = "empty-state";
+      li.textContent = "Nessun modello salvato.";
+      listEl.appendChild(li);
+      return;
+    }
+
+    templates.forEach((template) => {
+      const li = document.createElement("li");
+
+      const textDiv = document.createElement("div");
+      textDiv.className = "correction-text";
+      const name = document.createElement("span");
+      name.className = "correction-wrong";
+      name.textContent = template.name;
+      const scope = document.createElement("span");
+      scope.className = "correction-scope";
+      scope.textContent = `${template.supplier} · ${template.docType === "rechnung" ? "fattura" : "ordine"}`;
+      textDiv.append(name, scope);
+
+      const editBtn = document.createElement("button");
+      editBtn.className = "btn btn-ghost";
+      editBtn.textContent = "Modifica";
+      editBtn.addEventListener("click", () => fillTemplateForm(template));
+
+      const deleteBtn = document.createElement("button");
+      deleteBtn.className = "btn btn-ghost";
+      deleteBtn.textContent = "Elimina";
+      deleteBtn.addEventListener("click", async () => {
+        const confirmed = await showCustomConfirm(
+          `Eliminare il modello "${template.name}"?`,
+          "Elimina modello",
+          "Elimina",
+        );
+        if (!confirmed) return;
+        try {
+          await api.removeTemplate(template.name);
+          await loadTemplates();
+        } catch (e) {
+          showToast(`Errore durante l'eliminazione: ${e}`, "error");
+        }
+      });
+
+      li.append(textDiv, editBtn, deleteBtn);
+      listEl.appendChild(li);
+    });
+  } catch (e) {
+    console.error("Errore durante il caricamento dei modelli:", e);
+  }
+}
+
+document.getElementById("new-template-btn")?.addEventListener("click", () => {
+  fillTemplateForm();
+});
+
+document
+  .getElementById("test-template-btn")
+  ?.addEventListener("click", async () => {
+    const resultEl = document.getElementById("template-test-result");
+    let template: ExtractionTemplate;
+    try {
+      template = readTemplateForm();
+    } catch (e) {
+      showToast(String(e instanceof Error ? e.message : e), "error");
+      return;
+    }
+
+    const selected = await open({
+      multiple: false,
+      filters: [{ name: "PDF", extensions: ["pdf"] }],
+    });
+    if (!selected || Array.isArray(selected)) return;
+
+    try {
+      const test = await api.testTemplate(selected, template);
+      const matched = new Set(test.matchedLines);
+      const products = test.result.produkte ?? [];
+      const summary = [
+        test.detected
+          ? "Il documento viene riconosciuto dal modello."
+          : "Attenzione: nessun pattern di riconoscimento corrisponde.",
+        `${products.length} prodotti trovati.`,
+        "",
+      ];
+      const lines = test.text
+        .split("\n")
+        .map((line, idx) => `${matched.has(idx + 1) ? "▶" : " "} ${line}`);
+      if (resultEl) resultEl.textContent = [...summary, ...lines].join("\n");
+    } catch (e) {
+      showToast(`Errore durante la prova: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("save-template-btn")
+  ?.addEventListener("click", async () => {
+    try {
+      const template = readTemplateForm();
+      await api.saveTemplate(template);
+      showToast(`Modello "${template.name}" salvato.`, "success");
+      await loadTemplates();
+    } catch (e) {
+      showToast(`Errore durante il salvataggio: ${e instanceof Error ? e.message : e}`, "error");
+    }
+  });
+
+/** Syncs with the shared corrections folder, if one is configured, and shows the result. */
+export async function refreshCorrectionsSync() {
+  const statusEl = document.getElementById("corrections-sync-status");
+
+  try {
+    const status = await api.syncCorrections();
+    if (!statusEl) return;
+    if (!status.sharedPath) {
+      statusEl.textContent = "";
+      return;
+    }
+    const pending = status.pending > 0 ? `, ${status.pending} modifiche in attesa` : "";
+    statusEl.textContent = status.online
+      ? `Condivise: aggiornate ${status.lastSync ?? "-"}${pending}`
+      : `Condivise non raggiungibili, uso la copia locale${pending}`;
+    statusEl.title = status.error || status.sharedPath;
+    statusEl.classList.toggle("offline", !status.online);
+  } catch (e) {
+    console.error("Errore durante la sincronizzazione delle correzioni:", e);
+  }
+}
+
+export function startCorrectionsSync() {
+  refreshCorrectionsSync();
+  setInterval(refreshCorrectionsSync, 5 * 60 * 1000);
+}
+
+/** Lists the imported values kept with "keep both", each to be used or dropped. */
+async function loadCorrectionConflicts() {
+  const container = document.getElementById("correction-conflicts");
+  const listEl = document.getElementById("correction-conflicts-list");
+  if (!container || !listEl) return;
+
+  const conflicts = await api.getCorrectionConflicts().catch(() => []);
+  container.style.display = conflicts.length > 0 ? "" : "none";
+  listEl.innerHTML = "";
+
+  conflicts.forEach(({ wrong, correct, supplier, customer }) => {
+    const li = document.createElement("li");
+    const scope = [supplier, customer].filter(Boolean).join(" / ") || "Tutti";
+
+    const textDiv = document.createElement("div");
+    textDiv.className = "correction-text";
+    [
+      ["correction-wrong", wrong],
+      ["correction-arrow", "➜"],
+      ["correction-right", correct],
+      ["correction-scope", scope],
+    ].forEach(([className, text]) => {
+      const el = document.createElement("span");
+      el.className = className;
+      el.textContent = text;
+      textDiv.appendChild(el);
+    });
+
+    const resolve = (label: string, title: string, accept: boolean) => {
+      const btn = document.createElement("button");
+      btn.className = "btn btn-ghost";
+      btn.textContent = label;
+      btn.title = title;
+      btn.addEventListener("click", async () => {
+        try {
+          await api.resolveCorrectionConflict(wrong, supplier, customer, accept);
+          await loadAndRenderCorrections();
+        } catch (e) {
+          showToast(`Errore durante la risoluzione del conflitto: ${e}`, "error");
+        }
+      });
+      return btn;
+    };
+
+    li.append(
+      textDiv,
+      resolve("Usa", "Sostituisce la correzione attuale con il valore importato", true),
+      resolve("Scarta", "Mantiene la correzione attuale", false),
+    );
+    listEl.appendChild(li);
+  });
+}
 
 export async function loadAndRenderCorrections() {
   const listEl = document.getElementById("corrections-list");
   if (!listEl) return;
 
+  await loadCorrectionConflicts();
+
   try {
-    const corrections = await api.getCorrections();
+    const [corrections, usage] = await Promise.all([
+      api.getCorrections(),
+      api.getCorrectionUsage().catch(() => []),
+    ]);
 
     listEl.innerHTML = "";
 
-    const entries = Object.entries(corrections);
-
-    if (entries.length === 0) {
+    if (corrections.length === 0) {
       listEl.innerHTML =
         '<li class="empty-state">Nessuna correzione imparata.</li>';
       return;
     }
 
-    entries.sort((a, b) => a[0].localeCompare(b[0]));
+    const entries = [...corrections].sort(
+      (a, b) =>
+        a.wrong.localeCompare(b.wrong) ||
+        (a.supplier || "").localeCompare(b.supplier || ""),
+    );
 
-    entries.forEach(([wrong, correct]) => {
+    entries.forEach((entry) => {
+      const { wrong, correct, supplier, customer, addedBy, addedAt } = entry;
       const li = document.createElement("li");
+      const scope = [supplier, customer].filter(Boolean).join(" / ") || "Tutti";
+      const used =
+        usage.find(
+          (u) =>
+            u.wrong.toUpperCase() === wrong.toUpperCase() &&
+            (u.supplier || "") === (supplier || "") &&
+            (u.customer || "") === (customer || ""),
+        )?.count ?? 0;
+      if (addedBy || addedAt) {
+        li.title = `Aggiunta da ${addedBy || "?"} il ${addedAt || "?"}`;
+      }
 
       const textDiv = document.createElement("div");
       textDiv.className = "correction-text";
-      textDiv.innerHTML = `
-        <span class="correction-wrong" title="${wrong}">${wrong}</span>
-        <span class="correction-arrow">➜</span>
-        <span class="correction-right" title="${correct}">${correct}</span>
+      // Shared entries come from other workstations: build them as text, never as HTML.
+      const span = (className: string, text: string, title = text) => {
+        const el = document.createElement("span");
+        el.className = className;
+        el.textContent = text;
+        el.title = title;
+        return el;
+      };
+      textDiv.append(
+        span("correction-wrong", wrong),
+        span("correction-arrow", "➜", ""),
+        span("correction-right", correct),
+        span("correction-scope", scope),
+        span("correction-usage", `×${used}`, `Applicata ${used} volte`),
+      );
+
+      const historyBtn = document.createElement("button");
+      historyBtn.className = "delete-correction-btn";
+      historyBtn.title = "Cronologia";
+      historyBtn.innerHTML = `
+        <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+          <circle cx="12" cy="12" r="10"></circle>
+          <polyline points="12 6 12 12 16 14"></polyline>
+        </svg>
       `;
+      historyBtn.addEventListener("click", () => toggleCorrectionHistory(li, entry));
 
       const delBtn = document.createElement("button");
       delBtn.className = "delete-correction-btn";
@@ -44,7 +956,7 @@ export async function loadAndRenderCorrections() {
 
       delBtn.addEventListener("click", async () => {
         try {
-          await api.removeCorrection(wrong);
+          await api.removeCorrection(wrong, supplier, customer);
           await loadAndRenderCorrections();
         } catch (e) {
           console.error(e);
@@ -53,6 +965,7 @@ export async function loadAndRenderCorrections() {
       });
 
       li.appendChild(textDiv);
+      li.appendChild(historyBtn);
       li.appendChild(delBtn);
       listEl.appendChild(li);
     });
@@ -63,6 +976,79 @@ export async function loadAndRenderCorrections() {
   }
 }
 
+function describeImport(report: CorrectionImportReport): string {
+  const summary = `${report.added} nuove, ${report.overwritten} sovrascritte, ${report.skipped} ignorate, ${report.conflicts} in conflitto, ${report.unchanged} invariate`;
+  const details = report.changes
+    .slice(0, 200)
+    .map((c) => {
+      const scope = [c.supplier, c.customer].filter(Boolean).join(" / ") || "Tutti";
+      const current = c.current ? `${c.current} → ` : "";
+      return `[${c.action}] ${c.wrong} (${scope}): ${current}${c.incoming}`;
+    });
+  if (report.changes.length > details.length) {
+    details.push(`… e altre ${report.changes.length - details.length}`);
+  }
+  return [summary, ...details].join("\n");
+}
+
+document
+  .getElementById("import-corrections-btn")
+  ?.addEventListener("click", async () => {
+    const selected = await open({
+      filters: [{ name: "Correzioni", extensions: ["csv", "json", "txt"] }],
+    });
+    if (typeof selected !== "string") return;
+
+    const policy = ((
+      document.getElementById("corrections-merge-policy") as HTMLSelectElement | null
+    )?.value || "skip") as CorrectionMergePolicy;
+
+    try {
+      const preview = await api.importCorrections(selected, policy, true);
+      if (preview.changes.length === 0) {
+        showToast("Nessuna modifica da importare.", "info");
+        return;
+      }
+      const confirmed = await showCustomConfirm(
+        describeImport(preview),
+        "Anteprima importazione",
+        "Applica",
+        "Annulla",
+        false,
+      );
+      if (!confirmed) return;
+
+      const result = await api.importCorrections(selected, policy, false);
+      showToast(
+        `Correzioni: ${result.added} nuove, ${result.overwritten} sovrascritte, ${result.conflicts} in conflitto da risolvere.`,
+        result.conflicts > 0 ? "info" : "success",
+      );
+      await loadAndRenderCorrections();
+    } catch (e) {
+      showToast(`Errore durante l'importazione: ${e}`, "error");
+    }
+  });
+
+document
+  .getElementById("export-corrections-btn")
+  ?.addEventListener("click", async () => {
+    const target = await save({
+      defaultPath: "correzioni.csv",
+      filters: [
+        { name: "CSV", extensions: ["csv"] },
+        { name: "JSON", extensions: ["json"] },
+      ],
+    });
+    if (!target) return;
+
+    try {
+      const count = await api.exportCorrections(target);
+      showToast(`${count} correzioni esportate.`, "success");
+    } catch (e) {
+      showToast(`Errore durante l'esportazione: ${e}`, "error");
+    }
+  });
+
 const selectFilesBtn = document.querySelector("#select-files-btn");
 const selectFolderBtn = document.querySelector("#select-folder-btn");
 const themeToggle = document.querySelector(
@@ -2,11 +2,27 @@ import { handleReseachStart, api } from "./api";
 import {
   handleSelectFiles,
   handleSelectFolder,
+  isSupportedDocument,
   loadPdfsFromDirectory,
   updateFileUI,
 } from "./file-manager";
 import { appState } from "./state";
-import { loadAndRenderCorrections } from "./settings";
+import {
+  loadAndRenderCorrections,
+  loadCatalogCount,
+  loadGlossaryCount,
+  loadCredentials,
+  loadWorkspaces,
+  loadSecretStatus,
+  loadPolicy,
+  loadTemplates,
+  readExcelColumns,
+  readSheetRules,
+  renderExcelColumns,
+  renderSheetRules,
+  refreshCorrectionsSync,
+  startCorrectionsSync,
+} from "./settings";
 import { createGrid, setupHeaderCheckbox, handleExportExcel } from "./grid";
 
 import { Store } from "@tauri-apps/plugin-store";
@@ -42,6 +58,7 @@ export async function setupUI() {
   api.setTaskbarProgress(0, 0).catch(() => { });
 
   checkForAppUpdates();
+  startCorrectionsSync();
 
   const container = document.querySelector("#data-grid");
   if (!container) return;
@@ -81,12 +98,16 @@ export async function setupUI() {
     console.error("CRITICO: pulsante Esporta (#export-excel-btn) non trovato!");
   }
 
+  const settingsStore = await api
+    .getWorkspaces()
+    .then((ws) => ws.settingsStore)
+    .catch(() => "settings.json");
   try {
-    appState.store = await Store.load("settings.json");
+    appState.store = await Store.load(settingsStore);
   } catch (err) {
-    console.warn("settings.json non trovato, crea nuovo archivio:", err);
+    console.warn(`${settingsStore} non trovato, crea nuovo archivio:`, err);
     await appState.store?.save();
-    appState.store = await Store.load("settings.json");
+    appState.store = await Store.load(settingsStore);
   }
 
   setupProgressBar();
@@ -240,43 +261,69 @@ export async function setupUI() {
       if (typeof selected === "string") processedPathInput.value = selected;
     });
 
+  const sharedCorrectionsInput = document.getElementById(
+    "setting-shared-corrections-path",
+  ) as HTMLInputElement;
+
+  document
+    .getElementById("setting-select-shared-corrections")
+    ?.addEventListener("click", async () => {
+      const selected = await open({ directory: true });
+      if (typeof selected === "string") sharedCorrectionsInput.value = selected;
+    });
+
   settingsBtn?.addEventListener("click", async () => {
-    const [apiKey, pdfPath, excelPath, processedPath, theme] =
-      await Promise.all([
-        api.getApiKey().catch((err) => {
-          console.warn(
-            "Impossibile caricare la chiave API (forse al primo avvio):",
-            err,
-          );
-          return "";
-        }),
-        appState.store?.get("defaultPdfPath").catch(() => null),
-        appState.store?.get("defaultExcelPath").catch(() => null),
-        appState.store?.get("defaultProcessedPdfPath").catch(() => null),
-        appState.store?.get("defaultTheme").catch(() => null),
-      ]);
-
-    const shouldMove = await appState.store?.get<boolean>("moveFilesEnabled");
-    const autoOpen = await appState.store?.get<boolean>("autoOpenExcel");
-    const isMove = shouldMove !== false;
-    const isAutoOpen = autoOpen === true;
-
-    const storedConcurrency =
-      await appState.store?.get<number>("concurrencyLimit");
-
-    const val =
-      storedConcurrency !== null && storedConcurrency !== undefined
-        ? Number(storedConcurrency)
-        : 5;
+    const [apiKey, settings] = await Promise.all([
+      api.getApiKey().catch((err) => {
+        console.warn(
+          "Impossibile caricare la chiave API (forse al primo avvio):",
+          err,
+        );
+        return "";
+      }),
+      api.getSettings(),
+    ]);
+
+    const invoicePrefixInput = document.getElementById(
+      "setting-invoice-prefix",
+    ) as HTMLInputElement;
+    if (invoicePrefixInput)
+      invoicePrefixInput.value = settings.filenameRules.invoicePrefix;
+    if (sharedCorrectionsInput)
+      sharedCorrectionsInput.value = settings.sharedCorrectionsPath || "";
+
+    const val = settings.concurrencyLimit;
 
     if (apiKeyInput) apiKeyInput.value = apiKey || "";
-    if (pdfPathInput) pdfPathInput.value = (pdfPath as string) || "";
-    if (excelPathInput) excelPathInput.value = (excelPath as string) || "";
+    if (pdfPathInput) pdfPathInput.value = settings.defaultPdfPath || "";
+    if (excelPathInput) excelPathInput.value = settings.defaultExcelPath || "";
     if (processedPathInput)
-      processedPathInput.value = (processedPath as string) || "";
-    if (theme) themeToggle.checked = theme === "light";
-    if (moveToggle) moveToggle.checked = isMove;
-    if (openToggle) openToggle.checked = isAutoOpen;
+      processedPathInput.value = settings.defaultProcessedPdfPath || "";
+    themeToggle.checked = settings.defaultTheme === "light";
+    if (moveToggle) moveToggle.checked = settings.moveFilesEnabled;
+    if (openToggle) openToggle.checked = settings.autoOpenExcel;
+
+    const languageSelect = document.getElementById(
+      "setting-target-language",
+    ) as HTMLSelectElement;
+    const originalColumnInput = document.getElementById(
+      "setting-original-column",
+    ) as HTMLInputElement;
+    const originalNotesToggle = document.getElementById(
+      "setting-original-notes",
+    ) as HTMLInputElement;
+    renderExcelColumns(settings.excelColumns);
+    renderSheetRules(settings.sheetRules);
+    if (languageSelect) languageSelect.value = settings.targetLanguage;
+    if (originalColumnInput)
+      originalColumnInput.value = settings.originalNameColumn || "";
+    if (originalNotesToggle)
+      originalNotesToggle.checked = settings.originalNameInNotes;
+    const supplierCorrectionsToggle = document.getElementById(
+      "setting-supplier-corrections",
+    ) as HTMLInputElement;
+    if (supplierCorrectionsToggle)
+      supplierCorrectionsToggle.checked = settings.supplierScopedCorrections;
     const concurrencySlider = document.getElementById(
       "setting-concurrency",
     ) as HTMLInputElement;
@@ -300,6 +347,14 @@ export async function setupUI() {
     }
 
     loadAndRenderCorrections();
+    refreshCorrectionsSync();
+    loadSecretStatus();
+    loadPolicy();
+    loadCredentials();
+    loadWorkspaces();
+    loadGlossaryCount();
+    loadCatalogCount();
+    loadTemplates();
 
     settingsModal!.style.display = "flex";
   });
@@ -317,35 +372,56 @@ export async function setupUI() {
 
   saveSettingsBtn?.addEventListener("click", async () => {
     try {
-      await api.saveApiKey(apiKeyInput.value);
-
-      await appState.store?.set("defaultPdfPath", pdfPathInput.value);
-      await appState.store?.set("defaultExcelPath", excelPathInput.value);
-      await appState.store?.set(
-        "defaultProcessedPdfPath",
-        processedPathInput.value,
-      );
-      const newTheme = themeToggle.checked ? "light" : "dark";
-      await appState.store?.set("defaultTheme", newTheme);
       const concurrencySlider = document.getElementById(
         "setting-concurrency",
       ) as HTMLInputElement;
-      if (concurrencySlider) {
-        await appState.store?.set(
-          "concurrencyLimit",
-          parseInt(concurrencySlider.value, 10),
-        );
-      }
       const moveToggle = document.getElementById("setting-file-action") as HTMLInputElement;
       const openToggle = document.getElementById("setting-auto-open") as HTMLInputElement;
-      if (moveToggle) {
-        await appState.store?.set("moveFilesEnabled", moveToggle.checked);
-      }
-      if (openToggle) {
-        await appState.store?.set("autoOpenExcel", openToggle.checked);
-      }
+      const languageSelect = document.getElementById("setting-target-language") as HTMLSelectElement;
+      const originalColumnInput = document.getElementById("setting-original-column") as HTMLInputElement;
+      const originalNotesToggle = document.getElementById("setting-original-notes") as HTMLInputElement;
+      const supplierCorrectionsToggle = document.getElementById(
+        "setting-supplier-corrections",
+      ) as HTMLInputElement;
+      const invoicePrefixInput = document.getElementById(
+        "setting-invoice-prefix",
+      ) as HTMLInputElement;
 
-      await appState.store?.save();
+      const current = await api.getSettings();
+      const newTheme = themeToggle.checked ? "light" : "dark";
+      // The backend validates the whole set and saves nothing if a value is wrong.
+      await api.updateSettings({
+        defaultPdfPath: pdfPathInput.value,
+        defaultExcelPath: excelPathInput.value,
+        defaultProcessedPdfPath: processedPathInput.value,
+        defaultTheme: newTheme,
+        concurrencyLimit: concurrencySlider
+          ? parseInt(concurrencySlider.value, 10)
+          : current.concurrencyLimit,
+        moveFilesEnabled: moveToggle?.checked ?? current.moveFilesEnabled,
+        autoOpenExcel: openToggle?.checked ?? current.autoOpenExcel,
+        targetLanguage: languageSelect?.value ?? current.targetLanguage,
+        originalNameColumn: originalColumnInput
+          ? originalColumnInput.value
+          : current.originalNameColumn,
+        originalNameInNotes:
+          originalNotesToggle?.checked ?? current.originalNameInNotes,
+        filenameRules: {
+          ...current.filenameRules,
+          invoicePrefix: invoicePrefixInput
+            ? invoicePrefixInput.value
+            : current.filenameRules.invoicePrefix,
+        },
+        sharedCorrectionsPath: sharedCorrectionsInput
+          ? sharedCorrectionsInput.value
+          : current.sharedCorrectionsPath,
+        supplierScopedCorrections:
+          supplierCorrectionsToggle?.checked ?? current.supplierScopedCorrections,
+        excelColumns: readExcelColumns(current.excelColumns),
+        sheetRules: readSheetRules(current.sheetRules),
+      });
+      await api.saveApiKey(apiKeyInput.value);
+      refreshCorrectionsSync();
 
       document.documentElement.setAttribute("data-theme", newTheme);
 
@@ -375,19 +451,15 @@ export async function setupUI() {
     }
   })
 
-  appState.store?.get<string>("defaultTheme").then((theme) => {
-    if (theme) {
-      document.documentElement.setAttribute("data-theme", theme);
-      const toggle = document.getElementById(
-        "theme-toggle-input",
-      ) as HTMLInputElement;
-      if (toggle) toggle.checked = theme === "light";
-    }
-  });
+  api.getSettings().then((settings) => {
+    document.documentElement.setAttribute("data-theme", settings.defaultTheme);
+    const toggle = document.getElementById(
+      "theme-toggle-input",
+    ) as HTMLInputElement;
+    if (toggle) toggle.checked = settings.defaultTheme === "light";
 
-  appState.store?.get<string>("defaultPdfPath").then((path) => {
-    if (path) {
-      loadPdfsFromDirectory(path);
+    if (settings.defaultPdfPath) {
+      loadPdfsFromDirectory(settings.defaultPdfPath);
     }
   });
 
@@ -399,10 +471,10 @@ export async function setupUI() {
       Array.isArray(droppedPaths) &&
       droppedPaths.length > 0
     ) {
-      const pdfs = droppedPaths.filter((p) => p.toLowerCase().endsWith(".pdf"));
+      const pdfs = droppedPaths.filter((p) => isSupportedDocument(p));
 
       if (pdfs.length === 0) {
-        showToast("Nessun file PDF rilevato.", "error");
+        showToast("Nessun documento supportato rilevato.", "error");
         return;
       }
 
@@ -527,11 +599,12 @@ export function toggleTheme(forceTheme?: "light" | "dark") {
 }
 
 export function showCustomConfirm(
-  message: string,
+  message: string | HTMLElement,
   title: string = "Attenzione",
   confirmText: string = "Avvia",
   cancelText: string = "Annulla",
-  isWarning: boolean = true
+  isWarning: boolean = true,
+  width: string = "400px"
 ): Promise<boolean> {
   return new Promise((resolve) => {
     const overlay = document.createElement("div")
@@ -540,15 +613,17 @@ export function showCustomConfirm(
 
     const content = document.createElement("div")
     content.className = "modal-content"
-    content.style.width = "400px"
+    content.style.width = width
+    content.style.maxWidth = "90vw"
 
     const titleEl = document.createElement("h2")
     titleEl.className = "modal-title"
     titleEl.textContent = title
     if (isWarning) titleEl.style.color = "var(--error-color)"
 
-    const messageEl = document.createElement("p")
-    messageEl.textContent = message
+    const messageEl = document.createElement(typeof message === "string" ? "p" : "div")
+    if (typeof message === "string") messageEl.textContent = message
+    else messageEl.appendChild(message)
     messageEl.style.whiteSpace = "pre-wrap"
     messageEl.style.maxHeight = "60vh"
     messageEl.style.overflowY = "auto"
@@ -2,6 +2,8 @@ export interface PdfDataRow {
   id: number;
   pdfName: string;
   fullPath: string;
+  sourcePath?: string | null;
+  entryName?: string | null;
   docType: "auftrag" | "rechnung";
   confirmed: boolean;
   warnings?: boolean;
@@ -12,6 +14,7 @@ export interface PdfDataRow {
   nummerAuftrag?: string | null;
 
   produkt?: string | null;
+  produktOriginal?: string | null;
   menge?: number | null;
   einheit?: string | null;
   preis?: number | null;
@@ -22,15 +25,305 @@ export interface PdfDataRow {
   gelieferteMenge?: number | null;
 
   anmerkungen?: string | null;
+
+  extraction?: ExtractionMeta | null;
+  catalogScore?: number | null;
+  needsReview?: boolean;
+  appliedCorrection?: AppliedCorrection | null;
 }
 export interface AiProduct {
   produkt?: string | null;
+  produktOriginal?: string | null;
   menge?: number | null;
   waehrung?: string | null;
   preis?: number | null;
   gelieferteMenge?: number | null;
+  correction?: AppliedCorrection | null;
+}
+export interface AppliedCorrection {
+  from: string;
+  wrong: string;
+  correct: string;
+  supplier?: string | null;
+  customer?: string | null;
+  score: number;
 }
 export interface AiResponse {
+  docType?: "auftrag" | "rechnung";
   nummerRechnung?: string | null;
+  nummerAuftrag?: string | null;
+  datumAuftrag?: string | null;
+  datumRechnung?: string | null;
+  kunde?: string | null;
+  lieferant?: string | null;
   produkte?: AiProduct[];
+  meta?: ExtractionMeta;
+}
+export interface ExtractionMeta {
+  source: "llm" | "template" | "xml";
+  docType?: string;
+  template?: string;
+  promptVersion?: number;
+  promptCustom?: boolean;
+  targetLanguage?: string;
+  credential?: string;
+}
+export interface CorrectionEntry {
+  wrong: string;
+  correct: string;
+  supplier?: string | null;
+  customer?: string | null;
+  addedBy?: string | null;
+  addedAt?: string | null;
+}
+export interface CorrectionHistoryEntry {
+  id: number;
+  wrong: string;
+  supplier?: string | null;
+  customer?: string | null;
+  oldValue?: string | null;
+  newValue?: string | null;
+  at: string;
+  user: string;
+  origin: "grid" | "import" | "settings" | "restore" | string;
+}
+export interface CorrectionUsage {
+  wrong: string;
+  supplier?: string | null;
+  customer?: string | null;
+  count: number;
+  lastUsed: string;
+}
+export interface Credential {
+  name: string;
+  provider: string;
+  secretId: string;
+  active: boolean;
+  createdAt: string;
+  lastTested?: string | null;
+  lastStatus?: string | null;
+}
+export interface CredentialTestResult {
+  name: string;
+  provider: string;
+  ok: boolean;
+  status: number | null;
+  message: string;
+}
+export interface Workspace {
+  id: string;
+  name: string;
+  createdAt: string;
+}
+export interface WorkspaceState {
+  workspaces: Workspace[];
+  active: string;
+  settingsStore: string;
+}
+export interface FilenameRules {
+  invoicePrefix: string;
+  separator: string;
+}
+export interface ExcelColumns {
+  datumAuftrag: string;
+  nummerAuftrag: string;
+  kunde: string;
+  lieferant: string;
+  produkt: string;
+  menge: string;
+  waehrung: string;
+  preis: string;
+  datumRechnung: string;
+  nummerRechnung: string;
+  gelieferteMenge: string;
+  formula: string | null;
+  anmerkungen: string | null;
+  extraDateColumns: string[];
+  detectHeaders: boolean;
+  headerSynonyms: Record<string, string[]>;
+}
+export interface SheetRules {
+  mode: "first" | "name" | "orderYear";
+  name: string;
+  yearPattern: string;
+  searchAllSheets: boolean;
+}
+export interface Settings {
+  schemaVersion: number;
+  concurrencyLimit: number;
+  defaultPdfPath: string | null;
+  defaultExcelPath: string | null;
+  defaultProcessedPdfPath: string | null;
+  defaultTheme: "dark" | "light";
+  moveFilesEnabled: boolean;
+  autoOpenExcel: boolean;
+  targetLanguage: string;
+  originalNameColumn: string | null;
+  originalNameInNotes: boolean;
+  sharedCorrectionsPath: string | null;
+  supplierScopedCorrections: boolean;
+  filenameRules: FilenameRules;
+  excelColumns: ExcelColumns;
+  sheetRules: SheetRules;
+}
+export interface BundleManifest {
+  format: string;
+  version: number;
+  appVersion: string;
+  settingsSchema: number;
+  createdAt: string;
+}
+export interface BundleChange {
+  section: "settings" | "corrections" | "prompts" | "glossary" | "templates";
+  action: "add" | "change";
+  key: string;
+  current: string | null;
+  incoming: string;
+}
+export interface BundleImportReport {
+  manifest: BundleManifest;
+  changes: BundleChange[];
+  applied: boolean;
+}
+export interface Policy {
+  allowedEndpoints?: string[] | null;
+  maxConcurrency?: number | null;
+  cloudOcr?: boolean | null;
+  allowedExportFolders?: string[] | null;
+}
+export interface PolicyStatus {
+  path: string;
+  active: boolean;
+  error: string | null;
+  policy: Policy;
+}
+export interface SecretStatus {
+  backend: "keyring" | "file" | "none";
+  keyringAvailable: boolean;
+  filePath: string | null;
+  fileEncrypted: boolean;
+}
+export interface CorrectionSyncStatus {
+  sharedPath: string | null;
+  online: boolean;
+  lastSync: string | null;
+  pending: number;
+  error: string | null;
+}
+export type CorrectionMergePolicy = "skip" | "overwrite" | "keepBoth";
+export interface CorrectionChange {
+  action: "add" | "overwrite" | "skip" | "conflict";
+  wrong: string;
+  supplier?: string | null;
+  customer?: string | null;
+  current?: string | null;
+  incoming: string;
+}
+export interface CorrectionImportReport {
+  added: number;
+  overwritten: number;
+  skipped: number;
+  unchanged: number;
+  conflicts: number;
+  changes: CorrectionChange[];
+  applied: boolean;
+}
+export interface CatalogEntry {
+  name: string;
+  aliases: string[];
+  unit?: string | null;
+  articleCodes: string[];
+}
+export interface ProductMapping {
+  input: string;
+  matched?: string | null;
+  unit?: string | null;
+  score: number;
+  needsReview: boolean;
+}
+export interface CatalogSeedResult {
+  added: number;
+  aliasesAdded: number;
+  total: number;
+}
+export interface GlossaryTerm {
+  source: string;
+  target: string;
+  language: string;
+}
+export interface GlossaryImportResult {
+  added: number;
+  updated: number;
+  skipped: number;
+}
+export interface PromptTemplate {
+  version: number;
+  template: string;
+  custom: boolean;
+  updatedAt?: string | null;
+}
+export interface DocumentHints {
+  lieferant?: string | null;
+  docType?: "auftrag" | "rechnung" | null;
+  datum?: string | null;
+  sender?: string | null;
+  subject?: string | null;
+}
+export interface InputDocument {
+  path: string;
+  sourcePath?: string | null;
+  entryName?: string | null;
+  hints: DocumentHints;
+}
+export interface InputError {
+  path: string;
+  error: string;
+}
+export interface ExpandedInputs {
+  documents: InputDocument[];
+  errors: InputError[];
+}
+export interface TemplateColumn {
+  field: "produkt" | "menge" | "gelieferteMenge" | "preis" | "waehrung";
+  start: number;
+  end?: number | null;
+}
+export interface ExtractionTemplate {
+  name: string;
+  supplier: string;
+  docType: "auftrag" | "rechnung";
+  detect?: string[];
+  startAnchor?: string | null;
+  endAnchor?: string | null;
+  linePattern?: string | null;
+  columns?: TemplateColumn[];
+  invoiceNumber?: string | null;
+  currency?: string | null;
+}
+export interface TemplateTestResult {
+  text: string;
+  detected: boolean;
+  matchedLines: number[];
+  result: AiResponse;
+}
+export interface CellChange {
+  column: string;
+  header: string;
+  old: string;
+  new: string;
+}
+export interface RowPreview {
+  index: number;
+  action: "update" | "insert";
+  sheet: string;
+  row: number;
+  insertAt: number | null;
+  supplierBlock: boolean | null;
+  mergedInto: number | null;
+  changes: CellChange[];
+}
+export interface ExportPreview {
+  rows: RowPreview[];
+  updated: number;
+  inserted: number;
 }